@@ -0,0 +1,136 @@
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, OnceLock, RwLock};
+
+// Values of a domain of their own, e.g. semver versions, with their own equality and ordering.
+// KIND tells kinds apart, hence it has to be unique: values of different kinds are never equal,
+// and are ordered by kind. Values are persisted as whatever encode returns, and read back by
+// decode, once the kind is registered with register_custom.
+pub trait Custom: Ord + Hash + Debug + Display + Send + Sync + 'static {
+    const KIND: &'static str;
+
+    fn encode(&self) -> Vec<u8>;
+    fn decode(bytes: &[u8]) -> Result<Self, String>
+    where
+        Self: Sized;
+}
+
+// The object safe counterpart of Custom, which CustomValue holds.
+trait DynCustom: Debug + Display + Send + Sync {
+    fn kind(&self) -> &'static str;
+    fn as_any(&self) -> &dyn Any;
+    fn eq_dyn(&self, other: &dyn DynCustom) -> bool;
+    fn cmp_dyn(&self, other: &dyn DynCustom) -> Ordering;
+    fn hash_dyn(&self, state: &mut dyn Hasher);
+    fn encode_dyn(&self) -> Vec<u8>;
+}
+
+impl<T: Custom> DynCustom for T {
+    fn kind(&self) -> &'static str {
+        T::KIND
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn eq_dyn(&self, other: &dyn DynCustom) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+    fn cmp_dyn(&self, other: &dyn DynCustom) -> Ordering {
+        match other.as_any().downcast_ref::<T>() {
+            Some(other) => self.cmp(other),
+            None => T::KIND.cmp(other.kind()),
+        }
+    }
+    fn hash_dyn(&self, mut state: &mut dyn Hasher) {
+        self.hash(&mut state)
+    }
+    fn encode_dyn(&self) -> Vec<u8> {
+        self.encode()
+    }
+}
+
+#[derive(Clone)]
+pub struct CustomValue(Arc<dyn DynCustom>);
+
+impl CustomValue {
+    pub fn new<T: Custom>(value: T) -> Self {
+        CustomValue(Arc::new(value))
+    }
+    pub fn kind(&self) -> &'static str {
+        self.0.kind()
+    }
+    pub fn downcast_ref<T: Custom>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+    pub fn encode(&self) -> Vec<u8> {
+        self.0.encode_dyn()
+    }
+}
+
+impl PartialEq for CustomValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_dyn(other.0.as_ref())
+    }
+}
+
+impl Eq for CustomValue {}
+
+impl PartialOrd for CustomValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CustomValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp_dyn(other.0.as_ref())
+    }
+}
+
+impl Hash for CustomValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind().hash(state);
+        self.0.hash_dyn(state);
+    }
+}
+
+impl Display for CustomValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Debug for CustomValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+type Decoder = fn(&[u8]) -> Result<CustomValue, String>;
+
+fn decoders() -> &'static RwLock<HashMap<&'static str, Decoder>> {
+    static DECODERS: OnceLock<RwLock<HashMap<&'static str, Decoder>>> = OnceLock::new();
+
+    DECODERS.get_or_init(Default::default)
+}
+
+// Makes values of T readable out of snapshots and logs. Registering a kind twice is harmless.
+pub fn register_custom<T: Custom>() {
+    let decode: Decoder = |bytes| T::decode(bytes).map(CustomValue::new);
+
+    decoders().write().unwrap().insert(T::KIND, decode);
+}
+
+pub fn decode_custom(kind: &str, bytes: &[u8]) -> Result<CustomValue, String> {
+    let decode = decoders()
+        .read()
+        .unwrap()
+        .get(kind)
+        .copied()
+        .ok_or_else(|| format!("unregistered custom value kind {}", kind))?;
+
+    decode(bytes).map_err(|err| format!("invalid {} value: {}", kind, err))
+}
@@ -0,0 +1,163 @@
+use crate::{Program, Rule, Term, TypedValue};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+// Arguments are the values of the variables a function depends on, by name.
+pub type SkolemFunctionCall =
+    Arc<dyn Fn(HashMap<&str, &TypedValue>) -> Result<TypedValue, String> + Send + Sync>;
+
+// What functions may return, either a value or, for those that can fail, a Result.
+pub trait SkolemOutput {
+    fn into_result(self) -> Result<TypedValue, String>;
+}
+
+impl SkolemOutput for TypedValue {
+    fn into_result(self) -> Result<TypedValue, String> {
+        Ok(self)
+    }
+}
+
+impl SkolemOutput for Result<TypedValue, String> {
+    fn into_result(self) -> Result<TypedValue, String> {
+        self
+    }
+}
+
+fn erase<O: SkolemOutput>(
+    func: impl Fn(HashMap<&str, &TypedValue>) -> O + Send + Sync + 'static,
+) -> SkolemFunctionCall {
+    Arc::new(move |args| func(args).into_result())
+}
+
+// Functions are told apart by their name and dependencies, never by what they point to, hence
+// two rules calling the same name are equal even if one of them is not resolved yet.
+#[derive(Clone)]
+pub struct SkolemFunction {
+    pub name: String,
+    // None until resolved by a FunctionRegistry, which is the case of parsed programs
+    pub func: Option<SkolemFunctionCall>,
+    pub deps: Vec<String>,
+}
+
+impl SkolemFunction {
+    pub fn new<O: SkolemOutput>(
+        name: &str,
+        func: impl Fn(HashMap<&str, &TypedValue>) -> O + Send + Sync + 'static,
+        deps: Vec<String>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            func: Some(erase(func)),
+            deps,
+        }
+    }
+    pub fn unresolved(name: &str, deps: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            func: None,
+            deps,
+        }
+    }
+    pub fn is_resolved(&self) -> bool {
+        self.func.is_some()
+    }
+    pub fn call(&self, args: HashMap<&str, &TypedValue>) -> Result<TypedValue, String> {
+        match &self.func {
+            Some(func) => func(args),
+            None => Err(format!("unresolved function {}", self.name)),
+        }
+    }
+}
+
+impl PartialEq for SkolemFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.deps == other.deps
+    }
+}
+
+impl Eq for SkolemFunction {}
+
+impl PartialOrd for SkolemFunction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SkolemFunction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.name, &self.deps).cmp(&(&other.name, &other.deps))
+    }
+}
+
+impl Hash for SkolemFunction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.deps.hash(state);
+    }
+}
+
+impl Display for SkolemFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.name, self.deps.join(", "))
+    }
+}
+
+impl Debug for SkolemFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut tuple = f.debug_tuple(&self.name);
+        self.deps.iter().for_each(|dep| {
+            tuple.field(dep);
+        });
+
+        tuple.finish()
+    }
+}
+
+// Functions by name, for programs whose functions are only known by their name, such as those
+// read from text.
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, SkolemFunctionCall>,
+}
+
+impl FunctionRegistry {
+    // Registering a name again replaces its function.
+    pub fn register<O: SkolemOutput>(
+        &mut self,
+        name: &str,
+        func: impl Fn(HashMap<&str, &TypedValue>) -> O + Send + Sync + 'static,
+    ) {
+        self.functions.insert(name.to_string(), erase(func));
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+    // Binds every unresolved function of `rule`, failing on names that are not registered.
+    pub fn resolve_rule(&self, mut rule: Rule) -> Result<Rule, String> {
+        for term in rule.head.terms.iter_mut() {
+            if let Term::Skolemizer(skolem_function) = term {
+                if skolem_function.func.is_none() {
+                    let func = self
+                        .functions
+                        .get(&skolem_function.name)
+                        .ok_or_else(|| format!("unknown function {}", skolem_function.name))?;
+                    skolem_function.func = Some(func.clone());
+                }
+            }
+        }
+
+        Ok(rule)
+    }
+    pub fn resolve(&self, program: Program) -> Result<Program, String> {
+        let inner = program
+            .inner
+            .into_iter()
+            .map(|rule| self.resolve_rule(rule))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Program { inner })
+    }
+}
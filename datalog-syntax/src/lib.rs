@@ -1,11 +1,82 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+
+mod custom;
+mod function;
+mod relation;
+#[cfg(feature = "serde")]
+mod serialization;
+
+pub use custom::{decode_custom, register_custom, Custom, CustomValue};
+pub use function::{FunctionRegistry, SkolemFunction, SkolemFunctionCall, SkolemOutput};
+pub use relation::{ColumnValue, Relation};
+
+// A float with a total order, see f64::total_cmp, so that it can be stored and joined like any
+// other value. -0.0 is stored as 0.0, and every NaN as the same one, hence values that compare
+// equal as f64 are equal here too, NaN aside.
+#[derive(Clone, Copy)]
+pub struct OrderedFloat(f64);
+
+impl OrderedFloat {
+    pub fn new(value: f64) -> Self {
+        if value.is_nan() {
+            OrderedFloat(f64::NAN)
+        } else if value == 0.0 {
+            OrderedFloat(0.0)
+        } else {
+            OrderedFloat(value)
+        }
+    }
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+impl PartialEq for OrderedFloat {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for OrderedFloat {}
+
+impl PartialOrd for OrderedFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedFloat {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Hash for OrderedFloat {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state)
+    }
+}
 
+// Values of different variants are never equal, e.g. Int(1), I64(1) and Float(1.0) don't join.
+// Variants are ordered as they are declared.
 #[derive(Eq, Ord, PartialEq, PartialOrd, Clone, Hash)]
 pub enum TypedValue {
     Str(String),
     Int(usize),
     Bool(bool),
+    I64(i64),
+    Float(OrderedFloat),
+    Bytes(Vec<u8>),
+    List(Vec<TypedValue>),
+    Custom(CustomValue),
+}
+
+impl TypedValue {
+    pub fn custom<T: Custom>(value: T) -> Self {
+        TypedValue::Custom(CustomValue::new(value))
+    }
 }
 
 impl Display for TypedValue {
@@ -14,16 +85,45 @@ impl Display for TypedValue {
             TypedValue::Str(x) => std::fmt::Display::fmt(&x, f),
             TypedValue::Int(x) => std::fmt::Display::fmt(&x, f),
             TypedValue::Bool(x) => std::fmt::Display::fmt(&x, f),
+            TypedValue::I64(x) => std::fmt::Display::fmt(&x, f),
+            TypedValue::Float(x) => std::fmt::Display::fmt(&x.get(), f),
+            TypedValue::Bytes(x) => std::fmt::Display::fmt(&String::from_utf8_lossy(x), f),
+            TypedValue::Custom(x) => std::fmt::Display::fmt(x, f),
+            TypedValue::List(x) => {
+                write!(f, "[")?;
+                for (index, item) in x.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    std::fmt::Display::fmt(item, f)?;
+                }
+                write!(f, "]")
+            }
         }
     }
 }
 
+// Writes values the way the rule! macro reads them, e.g. b"\x00" or [1, "a"], custom values aside.
 impl Debug for TypedValue {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             TypedValue::Str(x) => std::fmt::Debug::fmt(&x, f),
             TypedValue::Int(x) => std::fmt::Debug::fmt(&x, f),
             TypedValue::Bool(x) => std::fmt::Debug::fmt(&x, f),
+            TypedValue::I64(x) => std::fmt::Debug::fmt(&x, f),
+            TypedValue::Float(x) => std::fmt::Debug::fmt(&x.get(), f),
+            TypedValue::Bytes(x) => write!(f, "b\"{}\"", x.escape_ascii()),
+            TypedValue::Custom(x) => std::fmt::Debug::fmt(x, f),
+            TypedValue::List(x) => {
+                write!(f, "[")?;
+                for (index, item) in x.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    std::fmt::Debug::fmt(item, f)?;
+                }
+                write!(f, "]")
+            }
         }
     }
 }
@@ -52,21 +152,46 @@ impl From<bool> for TypedValue {
     }
 }
 
-pub type Variable = String;
+impl From<i64> for TypedValue {
+    fn from(value: i64) -> Self {
+        TypedValue::I64(value)
+    }
+}
 
-// Unsafe
-pub type SkolemFunctionCall = fn(args: HashMap<&str, &TypedValue>) -> TypedValue;
+impl From<f64> for TypedValue {
+    fn from(value: f64) -> Self {
+        TypedValue::Float(OrderedFloat::new(value))
+    }
+}
 
-#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Debug)]
-pub struct SkolemFunction {
-    pub func: SkolemFunctionCall,
-    pub deps: Vec<String>
+impl From<Vec<u8>> for TypedValue {
+    fn from(value: Vec<u8>) -> Self {
+        TypedValue::Bytes(value)
+    }
 }
 
+impl From<&[u8]> for TypedValue {
+    fn from(value: &[u8]) -> Self {
+        TypedValue::Bytes(value.to_vec())
+    }
+}
+
+impl From<Vec<TypedValue>> for TypedValue {
+    fn from(value: Vec<TypedValue>) -> Self {
+        TypedValue::List(value)
+    }
+}
+
+pub type Variable = String;
+
+// Written as e.g. {"variable": "x"}, {"constant": "a"} or {"function": {"name": .., "deps": ..}}
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Term {
     Variable(String),
     Constant(TypedValue),
+    #[cfg_attr(feature = "serde", serde(rename = "function"))]
     Skolemizer(SkolemFunction),
 }
 
@@ -75,7 +200,7 @@ impl Display for Term {
         match self {
             Term::Variable(x) => std::fmt::Display::fmt(&x,f),
             Term::Constant(x) => std::fmt::Display::fmt(&x,f),
-            Term::Skolemizer(x) => std::fmt::Debug::fmt(x, f)
+            Term::Skolemizer(x) => std::fmt::Display::fmt(x, f)
         }
     }
 }
@@ -83,13 +208,20 @@ impl Display for Term {
 pub type AnonymousGroundAtom = Vec<TypedValue>;
 
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Atom {
     pub terms: Vec<Term>,
     pub symbol: String,
+    // Only body atoms can be negated, meaning that no matching fact may exist
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub negated: bool,
 }
 
 impl Display for Atom {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.negated {
+            write!(f, "!")?;
+        }
         write!(f, "{}(", &self.symbol)?;
 
         for (index, term) in self.terms.iter().enumerate() {
@@ -106,6 +238,9 @@ impl Display for Atom {
 
 impl Debug for Atom {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.negated {
+            write!(f, "!")?;
+        }
         write!(f, "{:?}(", &self.symbol)?;
 
         for (index, term) in self.terms.iter().enumerate() {
@@ -120,11 +255,15 @@ impl Debug for Atom {
     }
 }
 
+// Written as "any" or {"constant": "a"}
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Matcher {
     Any,
     Constant(TypedValue),
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Query<'a> {
     pub matchers: Vec<Matcher>,
     pub symbol: &'a str,
@@ -175,9 +314,12 @@ macro_rules! build_query {
 }
 
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rule {
     pub head: Atom,
     pub body: Vec<Atom>,
+    // Given by Program::from, hence not serialised
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub id: usize,
 }
 
@@ -0,0 +1,61 @@
+use crate::{AnonymousGroundAtom, TypedValue};
+
+// Rust types that are stored as a single column, e.g. String as Str and usize as Int. Custom
+// values are read and written as TypedValue itself.
+pub trait ColumnValue: Sized {
+    fn into_value(self) -> TypedValue;
+    fn from_value(value: TypedValue) -> Result<Self, String>;
+}
+
+macro_rules! column_value {
+    ($type:ty, $variant:ident, $expected:literal) => {
+        impl ColumnValue for $type {
+            fn into_value(self) -> TypedValue {
+                self.into()
+            }
+            fn from_value(value: TypedValue) -> Result<Self, String> {
+                match value {
+                    TypedValue::$variant(x) => Ok(x),
+                    other => Err(format!("expected {}, found {:?}", $expected, other)),
+                }
+            }
+        }
+    };
+}
+
+column_value!(String, Str, "a string");
+column_value!(usize, Int, "an int");
+column_value!(bool, Bool, "a bool");
+column_value!(i64, I64, "an i64");
+column_value!(Vec<u8>, Bytes, "bytes");
+column_value!(Vec<TypedValue>, List, "a list");
+
+impl ColumnValue for f64 {
+    fn into_value(self) -> TypedValue {
+        self.into()
+    }
+    fn from_value(value: TypedValue) -> Result<Self, String> {
+        match value {
+            TypedValue::Float(x) => Ok(x.get()),
+            other => Err(format!("expected a float, found {:?}", other)),
+        }
+    }
+}
+
+impl ColumnValue for TypedValue {
+    fn into_value(self) -> TypedValue {
+        self
+    }
+    fn from_value(value: TypedValue) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+// Rust types whose values are the facts of a relation, one field per column, see
+// #[derive(Relation)] in datalog_rule_macro and MicroRuntime::insert_relation.
+pub trait Relation: Sized {
+    const NAME: &'static str;
+
+    fn into_fact(self) -> AnonymousGroundAtom;
+    fn from_fact(fact: AnonymousGroundAtom) -> Result<Self, String>;
+}
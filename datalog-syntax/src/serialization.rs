@@ -0,0 +1,149 @@
+use crate::{decode_custom, Program, Rule, SkolemFunction, TypedValue};
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{Error, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Formatter;
+
+// Strings, booleans, non-negative Ints and lists are written as JSON ones. Every other value is
+// tagged with its kind so that it reads back as the same variant, since e.g. Int(1) and I64(1)
+// never join:
+//
+//     {"i64": -1}, {"float": 1.5}, {"float": "NaN"}, {"bytes": [0, 255]},
+//     {"custom": {"kind": "semver", "bytes": [1, 2, 0]}}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Tagged {
+    I64(i64),
+    Float(Float),
+    Bytes(Vec<u8>),
+    Custom { kind: String, bytes: Vec<u8> },
+}
+
+// JSON has no NaN nor infinities, which are written as "NaN", "inf" and "-inf" instead.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Float {
+    Finite(f64),
+    NonFinite(String),
+}
+
+impl Serialize for TypedValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TypedValue::Str(x) => serializer.serialize_str(x),
+            TypedValue::Int(x) => serializer.serialize_u64(*x as u64),
+            TypedValue::Bool(x) => serializer.serialize_bool(*x),
+            TypedValue::List(x) => serializer.collect_seq(x),
+            TypedValue::I64(x) => Tagged::I64(*x).serialize(serializer),
+            TypedValue::Float(x) if x.get().is_finite() => {
+                Tagged::Float(Float::Finite(x.get())).serialize(serializer)
+            }
+            TypedValue::Float(x) => {
+                Tagged::Float(Float::NonFinite(x.get().to_string())).serialize(serializer)
+            }
+            TypedValue::Bytes(x) => Tagged::Bytes(x.clone()).serialize(serializer),
+            TypedValue::Custom(x) => Tagged::Custom {
+                kind: x.kind().to_string(),
+                bytes: x.encode(),
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+struct TypedValueVisitor;
+
+impl<'de> Visitor<'de> for TypedValueVisitor {
+    type Value = TypedValue;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        write!(formatter, "a string, a boolean, a non-negative integer, a list or a tagged value")
+    }
+    fn visit_bool<E: Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(TypedValue::Bool(v))
+    }
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        usize::try_from(v)
+            .map(TypedValue::Int)
+            .map_err(|_| E::custom(format!("{} does not fit an Int", v)))
+    }
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        match usize::try_from(v) {
+            Ok(v) => Ok(TypedValue::Int(v)),
+            Err(_) => Err(E::custom(format!("negative integers are written {{\"i64\": {}}}", v))),
+        }
+    }
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(TypedValue::Str(v.to_string()))
+    }
+    fn visit_string<E: Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(TypedValue::Str(v))
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut items = vec![];
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+
+        Ok(TypedValue::List(items))
+    }
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        Ok(match Tagged::deserialize(MapAccessDeserializer::new(map))? {
+            Tagged::I64(x) => TypedValue::I64(x),
+            Tagged::Float(Float::Finite(x)) => x.into(),
+            Tagged::Float(Float::NonFinite(x)) => x
+                .parse::<f64>()
+                .map_err(|_| A::Error::custom(format!("invalid float {}", x)))?
+                .into(),
+            Tagged::Bytes(x) => TypedValue::Bytes(x),
+            Tagged::Custom { kind, bytes } => {
+                TypedValue::Custom(decode_custom(&kind, &bytes).map_err(A::Error::custom)?)
+            }
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for TypedValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TypedValueVisitor)
+    }
+}
+
+// Functions are referenced by name, e.g. {"name": "label", "deps": ["x", "y"]}, and read back
+// unresolved, see FunctionRegistry::resolve.
+#[derive(Serialize, Deserialize)]
+struct FunctionReference {
+    name: String,
+    deps: Vec<String>,
+}
+
+impl Serialize for SkolemFunction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FunctionReference {
+            name: self.name.clone(),
+            deps: self.deps.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SkolemFunction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let reference = FunctionReference::deserialize(deserializer)?;
+
+        Ok(SkolemFunction::unresolved(&reference.name, reference.deps))
+    }
+}
+
+// Programs are written as their list of rules, whose ids are given again when read back.
+impl Serialize for Program {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.inner.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Program {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<Rule>::deserialize(deserializer).map(Program::from)
+    }
+}
@@ -6,15 +6,66 @@ use std::collections::HashMap;
 use syn::parse::{Parse, ParseStream};
 use syn::{bracketed, parenthesized, Ident, Result, Token, Lit, LitStr};
 
+// Constants are typed by their literals: unsuffixed integers are usize, negative ones i64, and
+// suffixed ones whatever their suffix says. Byte strings are bytes, and brackets make lists.
+enum Constant {
+    Literal(Lit),
+    Negative(Lit),
+    List(Vec<Constant>),
+}
+
+impl Parse for Constant {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(syn::token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            let items: syn::punctuated::Punctuated<Constant, Token![,]> =
+                content.parse_terminated(Constant::parse)?;
+
+            return Ok(Constant::List(items.into_iter().collect()));
+        }
+        if input.peek(Token![-]) {
+            input.parse::<Token![-]>()?;
+            let literal: Lit = input.parse()?;
+            return match literal {
+                Lit::Int(_) | Lit::Float(_) => Ok(Constant::Negative(literal)),
+                _ => Err(syn::Error::new(literal.span(), "expected a number")),
+            };
+        }
+
+        Ok(Constant::Literal(input.parse()?))
+    }
+}
+
+impl ToTokens for Constant {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let expanded = match self {
+            Constant::Literal(Lit::Int(literal)) if literal.suffix().is_empty() => {
+                quote! { TypedValue::Int(#literal) }
+            }
+            Constant::Literal(Lit::ByteStr(literal)) => quote! { TypedValue::from(&#literal[..]) },
+            Constant::Literal(literal) => quote! { TypedValue::from(#literal) },
+            Constant::Negative(Lit::Int(literal)) if literal.suffix().is_empty() => {
+                quote! { TypedValue::I64(-#literal) }
+            }
+            Constant::Negative(literal) => quote! { TypedValue::from(-#literal) },
+            Constant::List(items) => quote! { TypedValue::List(vec![#(#items),*]) },
+        };
+
+        tokens.extend(expanded);
+    }
+}
+
 enum TermArg {
     Variable(Ident),
-    Constant(Lit),
+    Constant(Constant),
     SkolemFunction(Ident, Vec<Ident>)
 }
 
 struct AtomArgs {
     name: Ident,
     args: Vec<TermArg>,
+    negated: bool,
 }
 
 struct RuleMacroInput {
@@ -25,6 +76,9 @@ struct RuleMacroInput {
 impl Parse for RuleMacroInput {
     fn parse(input: ParseStream) -> Result<Self> {
         let head = input.parse::<AtomArgs>()?;
+        if head.negated {
+            return Err(syn::Error::new(head.name.span(), "the head cannot be negated"));
+        }
         let mut distinguished_variables: HashMap<String, (&Ident, bool)> = head
             .args
             .iter()
@@ -43,28 +97,32 @@ impl Parse for RuleMacroInput {
         let body: syn::punctuated::Punctuated<AtomArgs, Token![,]> =
             content2.parse_terminated(AtomArgs::parse)?;
         let body_vec: Vec<AtomArgs> = body.into_iter().collect();
-        body_vec.iter().for_each(|body_atom| {
-            body_atom
-                .args
-                .iter()
-                .filter(|term| matches!(term, TermArg::Variable(_)))
-                .for_each(|variable| match variable {
-                    TermArg::Variable(ident) => {
-                        let owned_ident = ident.to_string();
-
-                        if distinguished_variables.contains_key(&owned_ident) {
-                            (distinguished_variables.get_mut(&owned_ident).unwrap()).1 = true;
+        // Negated atoms cannot bind variables
+        body_vec
+            .iter()
+            .filter(|body_atom| !body_atom.negated)
+            .for_each(|body_atom| {
+                body_atom
+                    .args
+                    .iter()
+                    .filter(|term| matches!(term, TermArg::Variable(_)))
+                    .for_each(|variable| match variable {
+                        TermArg::Variable(ident) => {
+                            let owned_ident = ident.to_string();
+
+                            if distinguished_variables.contains_key(&owned_ident) {
+                                (distinguished_variables.get_mut(&owned_ident).unwrap()).1 = true;
+                            }
                         }
-                    }
-                    _ => unreachable!(),
-                });
-        });
+                        _ => unreachable!(),
+                    });
+            });
 
         for (key, value) in distinguished_variables {
             if !value.1 {
                 return Err(syn::Error::new(
                     value.0.span(),
-                    format!("variable {} not found in the body", key),
+                    format!("variable {} not found in a positive body atom", key),
                 ));
             }
         }
@@ -78,6 +136,10 @@ impl Parse for RuleMacroInput {
 
 impl Parse for AtomArgs {
     fn parse(input: ParseStream) -> Result<Self> {
+        let negated = input.peek(Token![!]);
+        if negated {
+            input.parse::<Token![!]>()?;
+        }
         let name = input.parse()?;
         let content;
         parenthesized!(content in input);
@@ -121,6 +183,7 @@ impl Parse for AtomArgs {
         Ok(AtomArgs {
             name,
             args: args.into_iter().collect(),
+            negated,
         })
     }
 }
@@ -137,11 +200,11 @@ pub fn rule(input: TokenStream) -> TokenStream {
         .iter()
         .map(|arg| match arg {
             TermArg::Variable(ident) => quote! { Term::Variable(stringify!(#ident).to_string()) } ,
-            TermArg::Constant(lit) => quote! { Term::Constant(TypedValue::from(#lit)) },
+            TermArg::Constant(constant) => quote! { Term::Constant(#constant) },
             TermArg::SkolemFunction(ident, vars) => {
                 let var_strings: Vec<_> = vars.iter().map(|var| quote! { stringify!(#var).to_string() }).collect();
 
-                quote! { Term::Skolemizer(SkolemFunction { func: #ident, deps: vec![#(#var_strings),*]}) }
+                quote! { Term::Skolemizer(SkolemFunction::new(stringify!(#ident), ::core::clone::Clone::clone(&#ident), vec![#(#var_strings),*])) }
             }
         })
         .collect();
@@ -151,26 +214,27 @@ pub fn rule(input: TokenStream) -> TokenStream {
         .iter()
         .map(|atom| {
             let name = &atom.name;
+            let negated = atom.negated;
             let terms: Vec<_> = atom
                 .args
                 .iter()
                 .map(|arg| match arg {
                     TermArg::Variable(ident) => quote! { Term::Variable(stringify!(#ident).to_string()) } ,
-                    TermArg::Constant(lit) => quote! { Term::Constant(TypedValue::from(#lit)) },
+                    TermArg::Constant(constant) => quote! { Term::Constant(#constant) },
                     TermArg::SkolemFunction(ident, vars) => {
                         let var_strings: Vec<_> = vars.iter().map(|var| quote! { stringify!(#var).to_string() }).collect();
 
-                        quote! { Term::Skolemizer(SkolemFunction { func: #ident, deps: vec![#(#var_strings),*]}) }
+                        quote! { Term::Skolemizer(SkolemFunction::new(stringify!(#ident), ::core::clone::Clone::clone(&#ident), vec![#(#var_strings),*])) }
                     }
                 })
                 .collect();
-            quote! { Atom { terms: vec![#(#terms),*], symbol: stringify!(#name).to_string() } }
+            quote! { Atom { terms: vec![#(#terms),*], symbol: stringify!(#name).to_string(), negated: #negated } }
         })
         .collect();
 
     let expanded = quote! {
         Rule {
-            head: Atom { terms: vec![#(#head_terms),*], symbol: stringify!(#head_name).to_string() },
+            head: Atom { terms: vec![#(#head_terms),*], symbol: stringify!(#head_name).to_string(), negated: false },
             body: vec![#(#body_atoms),*],
             id: 0
         }
@@ -202,11 +266,11 @@ pub fn program(input: TokenStream) -> TokenStream {
             .iter()
             .map(|arg| match arg {
                 TermArg::Variable(ident) => quote! { Term::Variable(stringify!(#ident).to_string()) } ,
-                TermArg::Constant(lit) => quote! { Term::Constant(TypedValue::from(#lit)) },
+                TermArg::Constant(constant) => quote! { Term::Constant(#constant) },
                 TermArg::SkolemFunction(ident, vars) => {
                     let var_strings: Vec<_> = vars.iter().map(|var| quote! { stringify!(#var).to_string() }).collect();
 
-                    quote! { Term::Skolemizer(SkolemFunction { func: #ident, deps: vec![#(#var_strings),*]}) }
+                    quote! { Term::Skolemizer(SkolemFunction::new(stringify!(#ident), ::core::clone::Clone::clone(&#ident), vec![#(#var_strings),*])) }
                 }
             })
             .collect();
@@ -216,26 +280,27 @@ pub fn program(input: TokenStream) -> TokenStream {
             .iter()
             .map(|atom| {
                 let name = &atom.name;
+                let negated = atom.negated;
                 let terms: Vec<_> = atom
                     .args
                     .iter()
                     .map(|arg| match arg {
                         TermArg::Variable(ident) => quote! { Term::Variable(stringify!(#ident).to_string()) } ,
-                        TermArg::Constant(lit) => quote! { Term::Constant(TypedValue::from(#lit)) },
+                        TermArg::Constant(constant) => quote! { Term::Constant(#constant) },
                         TermArg::SkolemFunction(ident, vars) => {
                             let var_strings: Vec<_> = vars.iter().map(|var| quote! { stringify!(#var).to_string() }).collect();
 
-                            quote! { Term::Skolemizer(SkolemFunction { func: #ident, deps: vec![#(#var_strings),*]}) }
+                            quote! { Term::Skolemizer(SkolemFunction::new(stringify!(#ident), ::core::clone::Clone::clone(&#ident), vec![#(#var_strings),*])) }
                         }
                     })
                     .collect();
-                quote! { Atom { terms: vec![#(#terms),*], symbol: stringify!(#name).to_string() } }
+                quote! { Atom { terms: vec![#(#terms),*], symbol: stringify!(#name).to_string(), negated: #negated } }
             })
             .collect();
 
         quote! {
             Rule {
-                head: Atom { terms: vec![#(#head_terms),*], symbol: stringify!(#head_name).to_string() },
+                head: Atom { terms: vec![#(#head_terms),*], symbol: stringify!(#head_name).to_string(), negated: false },
                 body: vec![#(#body_atoms),*],
                 id: 0
             }
@@ -248,3 +313,117 @@ pub fn program(input: TokenStream) -> TokenStream {
 
     expanded.into()
 }
+
+// The relation of a struct is its name in snake case, e.g. TransitiveClosure is
+// transitive_closure, unless given with #[relation(name = "tc")]. Its columns are its fields, in
+// order, each of which has to implement ColumnValue.
+#[proc_macro_derive(Relation, attributes(relation))]
+pub fn derive_relation(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+
+    match relation_impl(&input) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn relation_name(input: &syn::DeriveInput) -> Result<String> {
+    let mut name = None;
+    for attribute in input.attrs.iter().filter(|attr| attr.path.is_ident("relation")) {
+        let syn::Meta::List(list) = attribute.parse_meta()? else {
+            return Err(syn::Error::new_spanned(attribute, "expected #[relation(name = \"..\")]"));
+        };
+        for nested in list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("name") => name = Some(lit.value()),
+                other => return Err(syn::Error::new_spanned(other, "expected name = \"..\"")),
+            }
+        }
+    }
+
+    Ok(name.unwrap_or_else(|| snake_case(&input.ident.to_string())))
+}
+
+// A run of capitals is a single word, which ends before the capital starting the next one, e.g.
+// HTTPServer becomes http_server.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (index, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next_is_lowercase = chars.get(index + 1).is_some_and(|next| next.is_lowercase());
+            if !previous.is_uppercase() || next_is_lowercase {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+
+    snake
+}
+
+fn relation_impl(input: &syn::DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let syn::Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(input, "only structs can be relations"));
+    };
+    let ident = &input.ident;
+    let name = relation_name(input)?;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let arity = data.fields.len();
+
+    // Fields are read in order, and put back either by name or by position.
+    let members: Vec<_> = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => syn::Index::from(index).into_token_stream(),
+        })
+        .collect();
+    let types: Vec<_> = data.fields.iter().map(|field| &field.ty).collect();
+    let indices: Vec<_> = (0..arity).collect();
+    let columns: Vec<_> = indices
+        .iter()
+        .map(|index| quote::format_ident!("column_{}", index))
+        .collect();
+    let constructor = match &data.fields {
+        syn::Fields::Named(_) => quote! { #ident { #(#members: #columns),* } },
+        syn::Fields::Unnamed(_) => quote! { #ident ( #(#columns),* ) },
+        syn::Fields::Unit => quote! { #ident },
+    };
+    let values = (arity > 0).then(|| quote! { let mut values = fact.into_iter(); });
+
+    Ok(quote! {
+        impl #impl_generics ::datalog_syntax::Relation for #ident #type_generics #where_clause {
+            const NAME: &'static str = #name;
+
+            fn into_fact(self) -> ::datalog_syntax::AnonymousGroundAtom {
+                vec![#(::datalog_syntax::ColumnValue::into_value(self.#members)),*]
+            }
+            fn from_fact(
+                fact: ::datalog_syntax::AnonymousGroundAtom,
+            ) -> ::core::result::Result<Self, ::std::string::String> {
+                if fact.len() != #arity {
+                    return Err(format!(
+                        "{} has {} columns, found {}", #name, #arity, fact.len()
+                    ));
+                }
+                #values
+                #(
+                    let #columns = <#types as ::datalog_syntax::ColumnValue>::from_value(
+                        values.next().unwrap(),
+                    )
+                    .map_err(|err| format!("column {} of {}: {}", #indices, #name, err))?;
+                )*
+
+                Ok(#constructor)
+            }
+        }
+    })
+}
@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod tests {
+    use datalog_rule_macro::Relation;
+    use datalog_syntax::*;
+    use pretty_assertions::assert_eq;
+
+    #[derive(Relation, Debug, PartialEq)]
+    struct TransitiveClosure {
+        from: String,
+        to: String,
+    }
+
+    #[derive(Relation, Debug, PartialEq)]
+    struct HTTPServer(String);
+
+    #[derive(Relation, Debug, PartialEq)]
+    struct ServesHTTP(String);
+
+    #[derive(Relation, Debug, PartialEq)]
+    #[relation(name = "duration")]
+    struct Duration(String, usize, TypedValue);
+
+    #[test]
+    fn test_relation() {
+        assert_eq!("transitive_closure", TransitiveClosure::NAME);
+        assert_eq!("duration", Duration::NAME);
+        // Runs of capitals are single words
+        assert_eq!("http_server", HTTPServer::NAME);
+        assert_eq!("serves_http", ServesHTTP::NAME);
+
+        let closure = TransitiveClosure {
+            from: "a".to_string(),
+            to: "b".to_string(),
+        };
+        let fact = vec![TypedValue::from("a"), "b".into()];
+        assert_eq!(fact, closure.into_fact());
+        assert_eq!(
+            Ok(TransitiveClosure {
+                from: "a".to_string(),
+                to: "b".to_string(),
+            }),
+            TransitiveClosure::from_fact(fact)
+        );
+
+        let duration = Duration("build".to_string(), 60, TypedValue::List(vec![true.into()]));
+        let fact = duration.into_fact();
+        assert_eq!(
+            Ok(Duration("build".to_string(), 60, TypedValue::List(vec![true.into()]))),
+            Duration::from_fact(fact)
+        );
+        assert_eq!(
+            Err("column 0 of duration: expected a string, found 1".to_string()),
+            Duration::from_fact(vec![1usize.into(), 1usize.into(), 1usize.into()])
+        );
+    }
+}
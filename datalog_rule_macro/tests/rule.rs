@@ -15,6 +15,7 @@ mod tests {
                     Term::Variable("z".to_string()),
                 ],
                 symbol: "tc".to_string(),
+                negated: false,
             },
             body: vec![
                 Atom {
@@ -23,6 +24,7 @@ mod tests {
                         Term::Variable("y".to_string()),
                     ],
                     symbol: "e".to_string(),
+                    negated: false,
                 },
                 Atom {
                     terms: vec![
@@ -30,8 +32,10 @@ mod tests {
                         Term::Variable("z".to_string()),
                     ],
                     symbol: "tc".to_string(),
+                    negated: false,
                 },
             ],
+            id: 0,
         };
 
         assert_eq!(rule_output, expected_output);
@@ -48,6 +52,7 @@ mod tests {
                     Term::Constant(TypedValue::from(1.325829)),
                 ],
                 symbol: "tc".to_string(),
+                negated: false,
             },
             body: vec![
                 Atom {
@@ -56,6 +61,7 @@ mod tests {
                         Term::Constant(TypedValue::from("haha")),
                     ],
                     symbol: "e".to_string(),
+                    negated: false,
                 },
                 Atom {
                     terms: vec![
@@ -63,10 +69,66 @@ mod tests {
                         Term::Constant(TypedValue::from(true)),
                     ],
                     symbol: "tc".to_string(),
+                    negated: false,
                 },
             ],
+            id: 0,
         };
 
         assert_eq!(rule_output, expected_output);
     }
+
+    #[test]
+    fn test_typed_literals() {
+        let rule_output = rule! { p(?x, [1, -2, 3i64]) <- [q(?x, -1.5, b"a\x00", [])] };
+
+        let list = TypedValue::List(vec![
+            TypedValue::Int(1),
+            TypedValue::I64(-2),
+            TypedValue::I64(3),
+        ]);
+        assert_eq!(Term::Constant(list), rule_output.head.terms[1]);
+        assert_eq!(
+            vec![
+                Term::Variable("x".to_string()),
+                Term::Constant(TypedValue::from(-1.5)),
+                Term::Constant(TypedValue::Bytes(vec![b'a', 0])),
+                Term::Constant(TypedValue::List(vec![])),
+            ],
+            rule_output.body[0].terms
+        );
+    }
+
+    #[test]
+    fn test_named_functions() {
+        fn label(args: std::collections::HashMap<&str, &TypedValue>) -> TypedValue {
+            args["x"].clone()
+        }
+        let fallible = |_: std::collections::HashMap<&str, &TypedValue>| {
+            Err::<TypedValue, _>("no label".to_string())
+        };
+
+        let rule_output = rule! { p(label(?x)) <- [q(?x)] };
+        let Term::Skolemizer(function) = &rule_output.head.terms[0] else {
+            panic!("expected a function");
+        };
+        assert_eq!("label(x)", function.to_string());
+        assert_eq!(
+            Ok(TypedValue::from("a")),
+            function.call([("x", &TypedValue::from("a"))].into())
+        );
+
+        // Functions are equal by name, regardless of what they point to
+        let unresolved = SkolemFunction::unresolved("label", vec!["x".to_string()]);
+        assert_eq!(Term::Skolemizer(unresolved), rule_output.head.terms[0]);
+
+        let rule_output = rule! { p(fallible(?x)) <- [q(?x)] };
+        let Term::Skolemizer(function) = &rule_output.head.terms[0] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            Err("no label".to_string()),
+            function.call([("x", &TypedValue::from("a"))].into())
+        );
+    }
 }
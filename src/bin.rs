@@ -1,30 +1,77 @@
 use act_logically::engine::datalog::MicroRuntime;
+use act_logically::engine::fact_storage::StorageLayout;
+use act_logically::loaders::ntriples::load_ntriples_file;
 use datalog_rule_macro::program;
 use datalog_syntax::*;
+use std::time::Instant;
 
+// RDFS entailment plus owl:inverseOf, over a single T(subject, predicate, object) relation.
+fn rdfs_program() -> Program {
+    program! {
+        T(?x, "http://www.w3.org/1999/02/22-rdf-syntax-ns#type", ?c) <- [T(?p, "http://www.w3.org/2000/01/rdf-schema#domain", ?c), T(?x, ?p, ?y)],
+        T(?y, "http://www.w3.org/1999/02/22-rdf-syntax-ns#type", ?c) <- [T(?p, "http://www.w3.org/2000/01/rdf-schema#range", ?c), T(?x, ?p, ?y)],
+        T(?p, "http://www.w3.org/2000/01/rdf-schema#subPropertyOf", ?r) <- [T(?p, "http://www.w3.org/2000/01/rdf-schema#subPropertyOf", ?q), T(?q, "http://www.w3.org/2000/01/rdf-schema#subPropertyOf", ?r)],
+        T(?x, ?q, ?y) <- [T(?p, "http://www.w3.org/2000/01/rdf-schema#subPropertyOf", ?q), T(?x, ?p, ?y)],
+        T(?x, "http://www.w3.org/1999/02/22-rdf-syntax-ns#type", ?d) <- [T(?c, "http://www.w3.org/2000/01/rdf-schema#subClassOf", ?d), T(?x, "http://www.w3.org/1999/02/22-rdf-syntax-ns#type", ?c)],
+        T(?c, "http://www.w3.org/2000/01/rdf-schema#subClassOf", ?e) <- [T(?c, "http://www.w3.org/2000/01/rdf-schema#subClassOf", ?d), T(?d, "http://www.w3.org/2000/01/rdf-schema#subClassOf", ?e)],
+        T(?y, ?q, ?x) <- [T(?p, "http://www.w3.org/2002/07/owl#inverseOf", ?q), T(?x, ?p, ?y)]
+    }
+}
+
+fn fact_count(runtime: &MicroRuntime) -> usize {
+    runtime.query(&build_query!(T(_, _, _))).unwrap().count()
+}
+
+// Usage: benches [--row-major] [FILE.nt[.gz]...]
+// Every file, typically the LUBM TBox and its data, is loaded into T.
 fn main() {
-    // Skolem Functions + Infinite relations seem to be all that it is necessary.
-    let f: SkolemFunctionCall = |args| {
-        let x_variable_value = args.get("x").unwrap();
-        let y_variable_value = args.get("y").unwrap();
+    let mut layout = StorageLayout::Hashed;
+    let mut paths = vec![];
+    std::env::args().skip(1).for_each(|argument| {
+        if argument == "--row-major" {
+            layout = StorageLayout::RowMajor;
+        } else {
+            paths.push(argument);
+        }
+    });
+    if paths.is_empty() {
+        paths.push("./data/lubm1.nt.gz".to_string());
+    }
 
-        return format!("{}={}", x_variable_value, y_variable_value).into()
-    };
+    let mut runtime = MicroRuntime::with_layout(rdfs_program(), layout);
 
-    let program = program! {
-        ENV(?x, ?y)     <- [INPUTS("env", ?x, ?y)],
-        FENV(f(?x, ?y)) <- [ENV(?x, ?y)]
-    };
+    let now = Instant::now();
+    let mut triple_count = 0;
+    for path in &paths {
+        triple_count += load_ntriples_file(&mut runtime, "T", path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+    }
+    println!("load: {} triples in {:?}", triple_count, now.elapsed());
 
-    let mut micro_runtime = MicroRuntime::new(program);
-    micro_runtime.insert("INPUTS", vec!["env".into(), "a".into(), "b".into()]);
+    let now = Instant::now();
+    runtime.poll();
+    let elapsed = now.elapsed();
+    println!("materialisation: {} facts in {:?}", fact_count(&runtime), elapsed);
 
-    micro_runtime.poll();
-    let q = build_query!(FENV(_));
-    let answer: Vec<_> = micro_runtime.query(&q).unwrap().into_iter().collect();
-    answer
-        .into_iter()
-        .for_each(|formatted_env| {
-            println!("{}({})", "FENV", formatted_env[0])
-        })
+    let rdf_type = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+    let lubm = "http://swat.cse.lehigh.edu/onto/univ-bench.owl#";
+    let student = "http://www.Department0.University0.edu/IncrementalStudent";
+    let now = Instant::now();
+    runtime.insert(
+        "T",
+        vec![student.into(), rdf_type.into(), format!("{}GraduateStudent", lubm).into()],
+    );
+    runtime.insert(
+        "T",
+        vec![
+            student.into(),
+            format!("{}takesCourse", lubm).into(),
+            "http://www.Department0.University0.edu/GraduateCourse0".into(),
+        ],
+    );
+    runtime.poll();
+    let elapsed = now.elapsed();
+    println!("incremental update: {} facts in {:?}", fact_count(&runtime), elapsed);
 }
@@ -0,0 +1,672 @@
+use crate::engine::datalog::MicroRuntime;
+use crate::engine::external::ExternalRelations;
+use crate::engine::fact_storage::StorageLayout;
+use crate::loaders::csv::CsvFile;
+use crate::engine::explanation::{explain, format_fact};
+use crate::parser::{parse_fact, parse_query, parse_source};
+use crate::repl::Repl;
+use crate::souffle::parse_souffle;
+use crate::workflows::emitter::emit_workflows;
+use crate::workflows::importer::import_workflow_file;
+use crate::workflows::lints::{collect_diagnostics, with_lint_rules, Severity};
+use crate::workflows::matrix::with_matrix_rules;
+use crate::workflows::schedule::with_schedule_rules;
+use crate::workflows::simulation::{collect_simulation, insert_event, with_simulation_rules, Event};
+use datalog_syntax::{Program, Query};
+use std::io::{BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+
+const USAGE: &str = "usage: act-logically build [--out-dir DIR] SOURCES...
+       act-logically check [--out-dir DIR] SOURCES...
+       act-logically query QUERY SOURCES...
+       act-logically explain FACT SOURCES...
+       act-logically simulate EVENT [--branch B] [--tag T] [--path P]... [--context K=V]... SOURCES...
+       act-logically repl [SOURCES...]
+
+SOURCES are .dl files, Soufflé .souffle files, workflow .yml files to import, or directories whose
+.dl files are all read.
+DIR defaults to .github/workflows.";
+
+const DEFAULT_OUT_DIR: &str = ".github/workflows";
+
+enum Failure {
+    Usage(String),
+    Error(String),
+}
+
+impl From<String> for Failure {
+    fn from(value: String) -> Self {
+        Failure::Error(value)
+    }
+}
+
+// Expands directories into the .dl files they hold, sorted so that runs are reproducible.
+fn source_paths(arguments: &[String]) -> Result<Vec<PathBuf>, Failure> {
+    if arguments.is_empty() {
+        return Err(Failure::Usage("no sources given".to_string()));
+    }
+
+    let mut paths = vec![];
+    for argument in arguments {
+        let path = PathBuf::from(argument);
+        if !path.is_dir() {
+            paths.push(path);
+            continue;
+        }
+
+        let mut sources = std::fs::read_dir(&path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        sources.retain(|source| source.extension().is_some_and(|extension| extension == "dl"));
+        sources.sort();
+        paths.extend(sources);
+    }
+
+    Ok(paths)
+}
+
+// Reads every source into a single runtime, along with the lint, matrix and schedule rules and the
+// files the sources declare as inputs, and materialises it. Workflow files given as sources are
+// imported, and rules may call the string built-ins.
+fn load(arguments: &[String]) -> Result<MicroRuntime, Failure> {
+    load_with(arguments, |program| program).map(|(runtime, _outputs)| runtime)
+}
+
+// Also returns the files the sources declare outputs to, which only build writes.
+fn load_with(
+    arguments: &[String],
+    extend: impl FnOnce(Program) -> Program,
+) -> Result<(MicroRuntime, Vec<CsvFile>), Failure> {
+    let mut rules = vec![];
+    let mut facts = vec![];
+    let mut inputs = vec![];
+    let mut outputs = vec![];
+    let mut workflows = vec![];
+    for path in source_paths(arguments)? {
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "yml" || extension == "yaml")
+        {
+            workflows.push(path);
+            continue;
+        }
+
+        let source = std::fs::read_to_string(&path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        let parsed = match path.extension().is_some_and(|extension| extension == "souffle") {
+            true => parse_souffle(&source),
+            false => parse_source(&source),
+        };
+        let parsed = parsed.map_err(|err| format!("{}:{}", path.display(), err))?;
+
+        // Files are relative to the source declaring them
+        let directory = path.parent().unwrap_or(Path::new(""));
+        rules.extend(parsed.rules);
+        facts.extend(parsed.facts);
+        inputs.extend(parsed.inputs.into_iter().map(|file| file.relative_to(directory)));
+        outputs.extend(parsed.outputs.into_iter().map(|file| file.relative_to(directory)));
+    }
+
+    let program = with_schedule_rules(with_matrix_rules(with_lint_rules(Program::from(rules))));
+    let program = extend(program);
+    let externals = ExternalRelations::builtins();
+    let mut runtime =
+        MicroRuntime::try_with_externals(program, StorageLayout::default(), externals)?;
+    for (relation, fact) in facts {
+        runtime.try_insert(&relation, fact)?;
+    }
+    for input in inputs {
+        input.load(&mut runtime)?;
+    }
+    for path in workflows {
+        import_workflow_file(&mut runtime, path)?;
+    }
+    runtime.try_poll()?;
+
+    Ok((runtime, outputs))
+}
+
+// Splits `--out-dir DIR` off the arguments.
+fn out_dir(arguments: &[String]) -> Result<(PathBuf, Vec<String>), Failure> {
+    let mut out_dir = PathBuf::from(DEFAULT_OUT_DIR);
+    let mut rest = vec![];
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--out-dir" => match arguments.next() {
+                Some(dir) => out_dir = PathBuf::from(dir),
+                None => return Err(Failure::Usage("--out-dir needs a value".to_string())),
+            },
+            option if option.starts_with("--") => {
+                return Err(Failure::Usage(format!("unknown option {}", option)))
+            }
+            _ => rest.push(argument.clone()),
+        }
+    }
+
+    Ok((out_dir, rest))
+}
+
+// Compiles the sources, reporting diagnostics. Returns None when any of them is an error.
+fn compile(
+    runtime: &MicroRuntime,
+    stderr: &mut impl Write,
+) -> Result<Option<Vec<(String, String)>>, Failure> {
+    let diagnostics = collect_diagnostics(runtime)?;
+    diagnostics
+        .iter()
+        .try_for_each(|diagnostic| writeln!(stderr, "{}", diagnostic))
+        .map_err(|err| err.to_string())?;
+
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(emit_workflows(runtime)?))
+}
+
+fn build(
+    arguments: &[String],
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> Result<i32, Failure> {
+    let (out_dir, sources) = out_dir(arguments)?;
+    let (runtime, outputs) = load_with(&sources, |program| program)?;
+    let Some(workflows) = compile(&runtime, stderr)? else {
+        return Ok(1);
+    };
+
+    let workflows = workflow_paths(&out_dir, workflows)?;
+    std::fs::create_dir_all(&out_dir).map_err(|err| format!("{}: {}", out_dir.display(), err))?;
+    for (path, document) in workflows {
+        std::fs::write(&path, document).map_err(|err| format!("{}: {}", path.display(), err))?;
+        writeln!(stdout, "wrote {}", path.display()).map_err(|err| err.to_string())?;
+    }
+    for output in outputs {
+        output.write(&runtime)?;
+        writeln!(stdout, "wrote {}", output.path.display()).map_err(|err| err.to_string())?;
+    }
+
+    Ok(0)
+}
+
+// Places the workflows in the output directory, refusing any file name that would land elsewhere,
+// e.g. an absolute one or one with a `..`, since names come from the facts of the sources.
+fn workflow_paths(
+    out_dir: &Path,
+    workflows: Vec<(String, String)>,
+) -> Result<Vec<(PathBuf, String)>, String> {
+    workflows
+        .into_iter()
+        .map(|(file, document)| {
+            let mut components = Path::new(&file).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(_)), None) => Ok((out_dir.join(&file), document)),
+                _ => Err(format!("{}: workflow file name is not a plain file name", file)),
+            }
+        })
+        .collect()
+}
+
+// Same as build, without writing anything: fails when the workflows on disk differ from the
+// generated ones.
+fn check(arguments: &[String], stderr: &mut impl Write) -> Result<i32, Failure> {
+    let (out_dir, sources) = out_dir(arguments)?;
+    let Some(workflows) = compile(&load(&sources)?, stderr)? else {
+        return Ok(1);
+    };
+
+    let mut status = 0;
+    for (path, document) in workflow_paths(&out_dir, workflows)? {
+        let problem = match std::fs::read_to_string(&path) {
+            Ok(existing) if existing == document => continue,
+            Ok(_) => "is out of date",
+            Err(_) => "is missing",
+        };
+        writeln!(stderr, "error: {} {}", path.display(), problem).map_err(|err| err.to_string())?;
+        status = 1;
+    }
+
+    Ok(status)
+}
+
+fn query(arguments: &[String], stdout: &mut impl Write) -> Result<i32, Failure> {
+    let Some((text, sources)) = arguments.split_first() else {
+        return Err(Failure::Usage("no query given".to_string()));
+    };
+    let (symbol, matchers) = parse_query(text)?;
+    let runtime = load(sources)?;
+
+    let mut facts: Vec<_> = runtime
+        .query(&Query {
+            matchers,
+            symbol: &symbol,
+        })?
+        .collect();
+    facts.sort();
+    for fact in facts {
+        writeln!(stdout, "{}.", format_fact(&symbol, &fact)).map_err(|err| err.to_string())?;
+    }
+
+    Ok(0)
+}
+
+fn explain_fact(
+    arguments: &[String],
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> Result<i32, Failure> {
+    let Some((text, sources)) = arguments.split_first() else {
+        return Err(Failure::Usage("no fact given".to_string()));
+    };
+    let (symbol, fact) = parse_fact(text)?;
+    let runtime = load(sources)?;
+
+    match explain(&runtime, &symbol, &fact)? {
+        Some(derivation) => {
+            write!(stdout, "{}", derivation).map_err(|err| err.to_string())?;
+            Ok(0)
+        }
+        None => {
+            writeln!(stderr, "{} does not hold", format_fact(&symbol, &fact))
+                .map_err(|err| err.to_string())?;
+            Ok(1)
+        }
+    }
+}
+
+// Parses `EVENT [--branch B] [--tag T] [--path P]... [--context K=V]...` off the arguments.
+fn event(arguments: &[String]) -> Result<(Event, Vec<String>), Failure> {
+    let Some((kind, arguments)) = arguments.split_first() else {
+        return Err(Failure::Usage("no event given".to_string()));
+    };
+    let mut event = Event::new(kind);
+    let mut rest = vec![];
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if !argument.starts_with("--") {
+            rest.push(argument.clone());
+            continue;
+        }
+
+        let Some(value) = arguments.next() else {
+            return Err(Failure::Usage(format!("{} needs a value", argument)));
+        };
+        match argument.as_str() {
+            "--branch" => event.branch = Some(value.clone()),
+            "--tag" => event.tag = Some(value.clone()),
+            "--path" => event.changed_paths.push(value.clone()),
+            "--context" => match value.split_once('=') {
+                Some((context, value)) => {
+                    event.contexts.push((context.to_string(), value.to_string()))
+                }
+                None => return Err(Failure::Usage(format!("invalid context {}", value))),
+            },
+            option => return Err(Failure::Usage(format!("unknown option {}", option))),
+        }
+    }
+
+    Ok((event, rest))
+}
+
+fn simulate(arguments: &[String], stdout: &mut impl Write) -> Result<i32, Failure> {
+    let (event, sources) = event(arguments)?;
+    let (mut runtime, _outputs) = load_with(&sources, with_simulation_rules)?;
+    insert_event(&mut runtime, &event)?;
+    runtime.try_poll()?;
+
+    for job in collect_simulation(&runtime)? {
+        let steps: Vec<_> = job.steps.iter().map(|step| step.to_string()).collect();
+        let steps = match steps.is_empty() {
+            true => "no steps".to_string(),
+            false => format!("steps {}", steps.join(", ")),
+        };
+        writeln!(stdout, "{}: {}: {}", job.file, job.job, steps).map_err(|err| err.to_string())?;
+    }
+
+    Ok(0)
+}
+
+// Unlike the other commands, the sources are read without the lint rules.
+fn repl(
+    arguments: &[String],
+    stdin: &mut impl BufRead,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> Result<i32, Failure> {
+    let mut repl = Repl::default();
+    if !arguments.is_empty() {
+        for path in source_paths(arguments)? {
+            repl.load(&path.to_string_lossy())?;
+        }
+        repl.poll()?;
+    }
+    repl.run(stdin, stdout, stderr)?;
+
+    Ok(0)
+}
+
+// Runs the command line in `arguments`, program name excluded, returning the exit status: 0 on
+// success, 1 on errors and 2 on usage errors.
+pub fn run(
+    arguments: &[String],
+    stdin: &mut impl BufRead,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> i32 {
+    let outcome = match arguments.split_first() {
+        Some((command, rest)) => match command.as_str() {
+            "build" => build(rest, stdout, stderr),
+            "check" => check(rest, stderr),
+            "query" => query(rest, stdout),
+            "explain" => explain_fact(rest, stdout, stderr),
+            "simulate" => simulate(rest, stdout),
+            "repl" => repl(rest, stdin, stdout, stderr),
+            "help" | "--help" | "-h" => {
+                let _ = writeln!(stdout, "{}", USAGE);
+                Ok(0)
+            }
+            _ => Err(Failure::Usage(format!("unknown command {}", command))),
+        },
+        None => Err(Failure::Usage("no command given".to_string())),
+    };
+
+    match outcome {
+        Ok(status) => status,
+        Err(Failure::Usage(message)) => {
+            let _ = writeln!(stderr, "error: {}\n\n{}", message, USAGE);
+            2
+        }
+        Err(Failure::Error(message)) => {
+            let _ = writeln!(stderr, "error: {}", message);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::run;
+    use std::path::{Path, PathBuf};
+
+    const CI: &str = r#"
+        workflow("ci.yml").
+        on_trigger("ci.yml", "push").
+        permission("ci.yml", "contents", "read").
+        job("ci.yml", "test").
+        runs_on("ci.yml", "test", "ubuntu-latest").
+        step("ci.yml", "test", 0).
+        run("ci.yml", "test", 0, "cargo test").
+    "#;
+
+    fn temporary_dir(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("act-logically-cli-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        path
+    }
+
+    fn run_with(arguments: &[&str]) -> (i32, String, String) {
+        let arguments: Vec<_> = arguments.iter().map(|argument| argument.to_string()).collect();
+        let mut stdout = vec![];
+        let mut stderr = vec![];
+        let status = run(&arguments, &mut "".as_bytes(), &mut stdout, &mut stderr);
+
+        (
+            status,
+            String::from_utf8(stdout).unwrap(),
+            String::from_utf8(stderr).unwrap(),
+        )
+    }
+
+    fn display(path: &Path) -> &str {
+        path.to_str().unwrap()
+    }
+
+    #[test]
+    fn test_build_and_check() {
+        let dir = temporary_dir("build");
+        let sources = dir.join("sources");
+        let out_dir = dir.join("workflows");
+        std::fs::create_dir_all(&sources).unwrap();
+        std::fs::write(sources.join("ci.dl"), CI).unwrap();
+        std::fs::write(sources.join("notes.txt"), "not datalog").unwrap();
+
+        let check = ["check", "--out-dir", display(&out_dir), display(&sources)];
+        let (status, _, stderr) = run_with(&check);
+        assert_eq!(1, status);
+        assert!(stderr.contains("ci.yml is missing"));
+
+        let (status, stdout, _) =
+            run_with(&["build", "--out-dir", display(&out_dir), display(&sources)]);
+        assert_eq!(0, status);
+        assert!(stdout.contains("ci.yml"));
+        let workflow = std::fs::read_to_string(out_dir.join("ci.yml")).unwrap();
+        assert!(workflow.contains("cargo test"));
+        assert_eq!(0, run_with(&check).0);
+
+        std::fs::write(out_dir.join("ci.yml"), "edited by hand").unwrap();
+        let (status, _, stderr) = run_with(&check);
+        assert_eq!(1, status);
+        assert!(stderr.contains("ci.yml is out of date"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_refuses_errors() {
+        let dir = temporary_dir("errors");
+        let source = dir.join("ci.dl");
+        let out_dir = dir.join("workflows");
+        std::fs::write(&source, format!("{}needs(\"ci.yml\", \"test\", \"lint\").", CI)).unwrap();
+
+        let (status, _, stderr) =
+            run_with(&["build", "--out-dir", display(&out_dir), display(&source)]);
+        assert_eq!(1, status);
+        assert_eq!("error: ci.yml: job test: needs undefined job lint\n", stderr);
+        assert!(!out_dir.exists());
+
+        std::fs::write(&source, "job(\"ci.yml\" \"test\").").unwrap();
+        let (status, _, stderr) = run_with(&["check", display(&source)]);
+        assert_eq!(1, status);
+        assert_eq!(
+            format!("error: {}:1:14: expected ), found \"test\"\n", display(&source)),
+            stderr
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_refuses_escaping_file_names() {
+        let dir = temporary_dir("escaping");
+        let source = dir.join("ci.dl");
+        let out_dir = dir.join("workflows");
+
+        for file in ["../ci.yml", "/tmp/ci.yml", "nested/ci.yml"] {
+            std::fs::write(&source, CI.replace("ci.yml", file)).unwrap();
+            let (status, _, stderr) =
+                run_with(&["build", "--out-dir", display(&out_dir), display(&source)]);
+            assert_eq!(1, status);
+            assert_eq!(
+                format!("error: {}: workflow file name is not a plain file name\n", file),
+                stderr
+            );
+            assert!(!out_dir.exists());
+
+            let (status, _, _) =
+                run_with(&["check", "--out-dir", display(&out_dir), display(&source)]);
+            assert_eq!(1, status);
+        }
+        assert!(!dir.join("ci.yml").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_csv_inputs_and_outputs() {
+        let dir = temporary_dir("csv");
+        let source = dir.join("inventory.dl");
+        std::fs::write(
+            &source,
+            r#"
+            .input repository(filename="repos.csv", types=["str", "str", "int"], header=true).
+            .output popular(filename="out/popular.tsv").
+            popular(?r, ?o) <- [repository(?r, ?o, ?s), concat("team-", ?t, ?o)].
+            "#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("repos.csv"), "name,owner,stars\napi,team-core,3\nweb,jane,5\n")
+            .unwrap();
+
+        let (status, stdout, _) = run_with(&["query", "repository(_, _, 5)", display(&source)]);
+        assert_eq!(0, status);
+        assert_eq!("repository(\"web\", \"jane\", 5).\n", stdout);
+        assert!(!dir.join("out").exists());
+
+        let out_dir = dir.join("workflows");
+        let (status, stdout, _) =
+            run_with(&["build", "--out-dir", display(&out_dir), display(&source)]);
+        assert_eq!(0, status);
+        assert!(stdout.contains("popular.tsv"));
+        assert_eq!(
+            "api\tteam-core\n",
+            std::fs::read_to_string(dir.join("out").join("popular.tsv")).unwrap()
+        );
+
+        std::fs::write(dir.join("repos.csv"), "name,owner,stars\napi,team-core,many\n").unwrap();
+        let (status, _, stderr) = run_with(&["query", "popular(_, _)", display(&source)]);
+        assert_eq!(1, status);
+        assert!(stderr.contains("line 2: column 3: \"many\" is not a int"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_souffle_sources() {
+        let dir = temporary_dir("souffle");
+        let source = dir.join("reach.souffle");
+        std::fs::write(
+            &source,
+            r#"
+            .decl edge(x: symbol, y: number)
+            .decl reach(x: symbol, y: number)
+            .input edge
+            .output reach
+            reach(X, Y) :- edge(X, Y), Y > 1.
+            "#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("edge.facts"), "a\t1\nb\t-2\nc\t3\n").unwrap();
+
+        let (status, stdout, _) = run_with(&["query", "reach(_, _)", display(&source)]);
+        assert_eq!(0, status);
+        assert_eq!("reach(\"c\", 3).\n", stdout);
+
+        let out_dir = dir.join("workflows");
+        assert_eq!(0, run_with(&["build", "--out-dir", display(&out_dir), display(&source)]).0);
+        assert_eq!("c\t3\n", std::fs::read_to_string(dir.join("reach.csv")).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sources_keep_their_relations() {
+        let dir = temporary_dir("relations");
+        let source = dir.join("ci.dl");
+        // Names the built-in rules use in their namespaces, at other arities
+        let facts = r#"
+            dependency("serde", "1.0").
+            schedulable("serde").
+            needs_path("serde").
+        "#;
+        std::fs::write(&source, format!("{}{}", CI, facts)).unwrap();
+
+        let (status, stdout, _) = run_with(&["query", "job(_, _)", display(&source)]);
+        assert_eq!(0, status);
+        assert_eq!("job(\"ci.yml\", \"test\").\n", stdout);
+        let (status, stdout, _) = run_with(&["query", "schedulable(_)", display(&source)]);
+        assert_eq!(0, status);
+        assert_eq!("schedulable(\"serde\").\n", stdout);
+
+        // A relation of the sources has a single arity
+        std::fs::write(&source, format!("{}job(\"serde\").", CI)).unwrap();
+        let (status, _, stderr) = run_with(&["query", "job(_, _)", display(&source)]);
+        assert_eq!(1, status);
+        assert!(stderr.contains("job: relation of arity 2 cannot hold a fact of arity 1"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_query_and_explain() {
+        let dir = temporary_dir("query");
+        let source = dir.join("graph.dl");
+        std::fs::write(
+            &source,
+            r#"
+            e("a", "b"). e("b", "c").
+            tc(?x, ?y) <- [e(?x, ?y)].
+            tc(?x, ?z) <- [tc(?x, ?y), e(?y, ?z)].
+            "#,
+        )
+        .unwrap();
+
+        let (status, stdout, _) = run_with(&["query", "tc(\"a\", _)", display(&source)]);
+        assert_eq!(0, status);
+        assert_eq!("tc(\"a\", \"b\").\ntc(\"a\", \"c\").\n", stdout);
+
+        let (status, stdout, _) = run_with(&["explain", "tc(\"a\", \"c\")", display(&source)]);
+        assert_eq!(0, status);
+        assert!(stdout.starts_with("tc(\"a\", \"c\") by tc(x, z) <- [tc(x, y), e(y, z)]\n"));
+
+        let (status, _, stderr) = run_with(&["explain", "tc(\"c\", \"a\")", display(&source)]);
+        assert_eq!(1, status);
+        assert_eq!("tc(\"c\", \"a\") does not hold\n", stderr);
+
+        assert_eq!(2, run_with(&["query"]).0);
+        assert_eq!(2, run_with(&["frobnicate"]).0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_simulate() {
+        let dir = temporary_dir("simulate");
+        let workflow = dir.join("ci.yml");
+        std::fs::write(
+            &workflow,
+            "on:
+  push:
+    paths: ['src/**']
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    steps:
+      - run: cargo test
+",
+        )
+        .unwrap();
+
+        let (status, stdout, _) =
+            run_with(&["simulate", "push", "--path", "src/lib.rs", display(&workflow)]);
+        assert_eq!(0, status);
+        assert_eq!("ci.yml: test: steps 0\n", stdout);
+
+        let (status, stdout, _) =
+            run_with(&["simulate", "push", "--path", "README.md", display(&workflow)]);
+        assert_eq!(0, status);
+        assert_eq!("", stdout);
+
+        assert_eq!(2, run_with(&["simulate", "push", "--context", "actor"]).0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
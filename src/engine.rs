@@ -1,3 +1,11 @@
+pub mod builtins;
 pub mod datalog;
+pub(crate) mod dictionary;
 pub(crate) mod ephemeral_storage;
+pub mod explanation;
+pub mod external;
+pub mod fact_storage;
+pub mod limits;
+pub(crate) mod persistence;
 pub(crate) mod storage;
+pub mod write_ahead_log;
@@ -0,0 +1,316 @@
+use crate::engine::external::ExternalRelations;
+use datalog_syntax::TypedValue;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// Arguments a mode binds are always given.
+fn string<'a>(value: &Option<&'a TypedValue>) -> Result<&'a str, String> {
+    match value.unwrap() {
+        TypedValue::Str(value) => Ok(value),
+        value => Err(format!("expected a string, found {:?}", value)),
+    }
+}
+
+// Compiled patterns, whichever rule or fact they come from. Patterns coming from facts may be
+// many, hence the cache keeps the ones used last, e.g. the constant patterns of rules, which are
+// used on every evaluation of their atom, and evicts the least recently used one when full.
+const REGEX_CACHE_CAPACITY: usize = 256;
+
+#[derive(Default)]
+struct RecentRegexes {
+    // The regex of each pattern, along with when it was last used
+    regexes: HashMap<String, (Regex, u64)>,
+    clock: u64,
+}
+
+#[derive(Clone)]
+struct RegexCache {
+    capacity: usize,
+    inner: Arc<Mutex<RecentRegexes>>,
+}
+
+impl Default for RegexCache {
+    fn default() -> Self {
+        Self::with_capacity(REGEX_CACHE_CAPACITY)
+    }
+}
+
+impl RegexCache {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Default::default(),
+        }
+    }
+    fn get(&self, pattern: &str) -> Result<Regex, String> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let RecentRegexes { regexes, clock } = &mut *inner;
+        if let Some((regex, last_used)) = regexes.get_mut(pattern) {
+            *last_used = *clock;
+            return Ok(regex.clone());
+        }
+
+        let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+        if regexes.len() >= self.capacity {
+            let least_recently_used = regexes
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(pattern, _)| pattern.clone());
+            if let Some(pattern) = least_recently_used {
+                regexes.remove(&pattern);
+            }
+        }
+        regexes.insert(pattern.to_string(), (regex.clone(), *clock));
+
+        Ok(regex)
+    }
+}
+
+impl ExternalRelations {
+    // String built-ins, which path filters and branch patterns can be written with:
+    //
+    //     concat(?a, ?b, ?ab)                ?ab is ?a followed by ?b, given any two of them
+    //     starts_with(?s, ?prefix)
+    //     split(?s, ?separator, ?part)       every part of ?s
+    //     regex_match(?re, ?s)               ?re matches somewhere in ?s
+    //     regex_capture(?re, ?s, ?group)     the first group of every match, or the match itself
+    //                                        if ?re has no group
+    //
+    // as well as comparisons, which values of any kind can be given to, see the ordering of
+    // TypedValue: eq(?a, ?b), which binds either argument to the other, ne, lt, le, gt and ge.
+    pub fn builtins() -> Self {
+        let mut externals = ExternalRelations::default();
+
+        externals
+            .register("concat", &["bbf", "bfb", "fbb"], |arguments| {
+                let tuple =
+                    |a: &str, b: &str| vec![vec![a.into(), b.into(), [a, b].concat().into()]];
+
+                Ok(match arguments {
+                    [Some(_), Some(_), _] => tuple(string(&arguments[0])?, string(&arguments[1])?),
+                    [Some(_), None, Some(_)] => {
+                        let a = string(&arguments[0])?;
+                        let ab = string(&arguments[2])?;
+                        ab.strip_prefix(a).map(|b| tuple(a, b)).unwrap_or_default()
+                    }
+                    _ => {
+                        let b = string(&arguments[1])?;
+                        let ab = string(&arguments[2])?;
+                        ab.strip_suffix(b).map(|a| tuple(a, b)).unwrap_or_default()
+                    }
+                })
+            })
+            .unwrap();
+
+        externals.register_filter("starts_with", 2, |arguments| {
+            Ok(string(&Some(arguments[0]))?.starts_with(string(&Some(arguments[1]))?))
+        });
+
+        externals
+            .register("split", &["bbf"], |arguments| {
+                let (text, separator) = (string(&arguments[0])?, string(&arguments[1])?);
+                if separator.is_empty() {
+                    return Err("cannot split by an empty separator".to_string());
+                }
+
+                Ok(text
+                    .split(separator)
+                    .map(|part| vec![text.into(), separator.into(), part.into()])
+                    .collect())
+            })
+            .unwrap();
+
+        let regexes = RegexCache::default();
+        let cache = regexes.clone();
+        externals.register_filter("regex_match", 2, move |arguments| {
+            let regex = cache.get(string(&Some(arguments[0]))?)?;
+
+            Ok(regex.is_match(string(&Some(arguments[1]))?))
+        });
+
+        let cache = regexes;
+        externals
+            .register("regex_capture", &["bbf"], move |arguments| {
+                let (pattern, text) = (string(&arguments[0])?, string(&arguments[1])?);
+                let regex = cache.get(pattern)?;
+                let group = if regex.captures_len() > 1 { 1 } else { 0 };
+
+                Ok(regex
+                    .captures_iter(text)
+                    .filter_map(|captures| captures.get(group))
+                    .map(|capture| vec![pattern.into(), text.into(), capture.as_str().into()])
+                    .collect())
+            })
+            .unwrap();
+
+        externals
+            .register("eq", &["bf", "fb"], |arguments| {
+                let value = arguments[0].or(arguments[1]).unwrap();
+
+                Ok(vec![vec![value.clone(), value.clone()]])
+            })
+            .unwrap();
+        let comparisons = [
+            ("ne", Ordering::is_ne as fn(Ordering) -> bool),
+            ("lt", Ordering::is_lt),
+            ("le", Ordering::is_le),
+            ("gt", Ordering::is_gt),
+            ("ge", Ordering::is_ge),
+        ];
+        for (name, compare) in comparisons {
+            externals.register_filter(name, 2, move |arguments| {
+                Ok(compare(arguments[0].cmp(arguments[1])))
+            });
+        }
+
+        externals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::builtins::RegexCache;
+    use crate::engine::datalog::MicroRuntime;
+    use crate::engine::external::ExternalRelations;
+    use crate::engine::fact_storage::StorageLayout;
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+
+    fn with_builtins(program: Program) -> MicroRuntime {
+        let externals = ExternalRelations::builtins();
+        MicroRuntime::try_with_externals(program, StorageLayout::default(), externals).unwrap()
+    }
+
+    fn query(runtime: &MicroRuntime, relation: &str, arity: usize) -> Vec<Vec<String>> {
+        let mut facts: Vec<_> = runtime
+            .query(&Query {
+                matchers: (0..arity).map(|_| Matcher::Any).collect(),
+                symbol: relation,
+            })
+            .unwrap()
+            .map(|fact| fact.iter().map(|value| value.to_string()).collect())
+            .collect();
+        facts.sort();
+        facts
+    }
+
+    #[test]
+    fn test_concat_and_split() {
+        let program = program! {
+            branch(?b)      <- [reference(?r), concat("refs/heads/", ?b, ?r)],
+            archive(?n)     <- [file(?f), concat(?n, ".tar.gz", ?f)],
+            qualified(?q)   <- [branch(?b), concat("origin/", ?b, ?q)],
+            segment(?s)     <- [reference(?r), starts_with(?r, "refs/tags/"), split(?r, "/", ?s)]
+        };
+        let mut runtime = with_builtins(program);
+        runtime.insert("reference", vec!["refs/heads/main".into()]);
+        runtime.insert("reference", vec!["refs/tags/v1".into()]);
+        runtime.insert("file", vec!["dist.tar.gz".into()]);
+        runtime.insert("file", vec!["dist.zip".into()]);
+        runtime.poll();
+
+        assert_eq!(vec![vec!["main"]], query(&runtime, "branch", 1));
+        assert_eq!(vec![vec!["dist"]], query(&runtime, "archive", 1));
+        assert_eq!(vec![vec!["origin/main"]], query(&runtime, "qualified", 1));
+        assert_eq!(
+            vec![vec!["refs"], vec!["tags"], vec!["v1"]],
+            query(&runtime, "segment", 1)
+        );
+
+        assert_eq!(
+            Err("concat is an external relation, its facts are computed".to_string()),
+            runtime.try_insert("concat", vec!["a".into(), "b".into(), "ab".into()])
+        );
+        assert!(runtime.safe());
+    }
+
+    #[test]
+    fn test_regex() {
+        let program = program! {
+            release(?b)     <- [branch(?b), regex_match("^release/[0-9]+$", ?b)],
+            version(?b, ?v) <- [branch(?b), regex_capture("release/([0-9]+)", ?b, ?v)],
+            word(?w)        <- [branch(?b), regex_capture("[a-z]+", ?b, ?w)]
+        };
+        let mut runtime = with_builtins(program);
+        runtime.insert("branch", vec!["release/12".into()]);
+        runtime.insert("branch", vec!["release/next".into()]);
+        runtime.poll();
+
+        assert_eq!(vec![vec!["release/12"]], query(&runtime, "release", 1));
+        assert_eq!(vec![vec!["release/12", "12"]], query(&runtime, "version", 2));
+        assert_eq!(
+            vec![vec!["next"], vec!["release"]],
+            query(&runtime, "word", 1)
+        );
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let program = program! {
+            older(?a, ?b)   <- [age(?a, ?x), age(?b, ?y), gt(?x, ?y)],
+            peer(?a, ?b)    <- [age(?a, ?x), age(?b, ?y), eq(?x, ?y), ne(?a, ?b)],
+            adult(?a)       <- [age(?a, ?x), ge(?x, 18)],
+            named(?a, ?n)   <- [age(?a, ?x), eq(?n, ?a)]
+        };
+        let mut runtime = with_builtins(program);
+        runtime.insert("age", vec!["ann".into(), 30usize.into()]);
+        runtime.insert("age", vec!["bob".into(), 12usize.into()]);
+        runtime.insert("age", vec!["cat".into(), 12usize.into()]);
+        runtime.poll();
+
+        assert_eq!(
+            vec![vec!["ann", "bob"], vec!["ann", "cat"]],
+            query(&runtime, "older", 2)
+        );
+        assert_eq!(vec![vec!["bob", "cat"], vec!["cat", "bob"]], query(&runtime, "peer", 2));
+        assert_eq!(vec![vec!["ann"]], query(&runtime, "adult", 1));
+        assert_eq!(3, query(&runtime, "named", 2).len());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut runtime = with_builtins(program! { p(?b) <- [branch(?b), regex_match("(", ?b)] });
+        runtime.insert("branch", vec!["main".into()]);
+        assert!(runtime.try_poll().unwrap_err().contains("regex_match"));
+
+        let mut runtime = with_builtins(program! { p(?s) <- [path(?p), split(?p, "", ?s)] });
+        runtime.insert("path", vec!["src".into()]);
+        assert!(runtime
+            .try_poll()
+            .unwrap_err()
+            .contains("cannot split by an empty separator"));
+
+        let mut runtime = with_builtins(program! { p(?s) <- [size(?n), concat(?n, "kb", ?s)] });
+        runtime.insert("size", vec![1usize.into()]);
+        assert!(runtime.try_poll().unwrap_err().contains("expected a string"));
+
+        // Neither ?a nor ?b is bound
+        let program = program! { p(?a) <- [q(?ab), concat(?a, ?b, ?ab)] };
+        let externals = ExternalRelations::builtins();
+        assert!(
+            MicroRuntime::try_with_externals(program, StorageLayout::default(), externals).is_err()
+        );
+    }
+
+    #[test]
+    fn test_regex_cache_evicts_least_recently_used() {
+        let cache = RegexCache::with_capacity(2);
+        cache.get("a").unwrap();
+        cache.get("b").unwrap();
+        cache.get("a").unwrap();
+        cache.get("c").unwrap();
+
+        let patterns = |cache: &RegexCache| {
+            let inner = cache.inner.lock().unwrap();
+            let mut patterns: Vec<_> = inner.regexes.keys().cloned().collect();
+            patterns.sort();
+            patterns
+        };
+        assert_eq!(vec!["a", "c"], patterns(&cache));
+        assert!(cache.get("(").is_err());
+        assert_eq!(vec!["a", "c"], patterns(&cache));
+    }
+}
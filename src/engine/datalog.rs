@@ -1,25 +1,139 @@
+use crate::engine::dictionary::Dictionary;
+use crate::engine::external::{order_body, ExternalRelations};
+use crate::engine::fact_storage::{check_arity, StorageLayout};
+use crate::engine::limits::{Budget, Limits};
+use crate::engine::persistence;
+use crate::engine::write_ahead_log::{LogRecord, WriteAheadLog};
 use crate::engine::storage::RelationStorage;
-use crate::evaluation::query::pattern_match;
+use crate::evaluation::query::{encode_query, pattern_match};
 use crate::evaluation::semi_naive::semi_naive_evaluation;
-use crate::helpers::helpers::{
-    split_program, DELTA_PREFIX
-};
+use crate::helpers::helpers::{check_safety, relation_arities, DELTA_PREFIX};
 use crate::program_transformations::delta_program::make_delta_program;
 use datalog_syntax::*;
-use std::collections::HashSet;
-use crate::program_transformations::dependency_graph::sort_program;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use crate::program_transformations::dependency_graph::{negation_dependencies, stratify};
 
 // Hairy
 pub struct MicroRuntime {
+    dictionary: Dictionary,
     processed: RelationStorage,
     unprocessed_insertions: RelationStorage,
-    nonrecursive_delta_program: Program,
-    recursive_delta_program: Program,
+    program: Program,
+    // One delta program per stratum, in evaluation order
+    delta_strata: Vec<Program>,
+    intensional_relations: HashSet<String>,
+    has_negation: bool,
+    // Relations whose insertions make a poll evaluate the program again, see prepare_reevaluation
+    negation_dependencies: HashSet<String>,
+    // The arity of every stored relation of the program, which insertions have to match
+    arities: HashMap<String, usize>,
+    // Facts inserted straight into intensional relations, which reevaluations start from. They are
+    // only kept for programs with negation.
+    intensional_insertions: RelationStorage,
+    fingerprint: u64,
+    log: Option<WriteAheadLog>,
+    limits: Limits,
+    externals: ExternalRelations,
 }
 
 impl MicroRuntime {
+    // Panics if the write-ahead log cannot be appended to, or if the fact does not have the arity
+    // of the facts already in the relation, see try_insert.
     pub fn insert(&mut self, relation: &str, ground_atom: AnonymousGroundAtom) -> bool {
-        self.unprocessed_insertions.insert(relation, ground_atom)
+        self.try_insert(relation, ground_atom).unwrap()
+    }
+    pub fn try_insert(
+        &mut self,
+        relation: &str,
+        ground_atom: AnonymousGroundAtom,
+    ) -> Result<bool, String> {
+        self.check_insertion(relation, &ground_atom, None)?;
+
+        if let Some(log) = &mut self.log {
+            let is_pending = self
+                .dictionary
+                .try_encode(&ground_atom)
+                .is_some_and(|encoded| self.unprocessed_insertions.contains(relation, &encoded));
+
+            if !is_pending {
+                log.append(&LogRecord::Insertion(relation.to_string(), ground_atom.clone()))?;
+            }
+        }
+
+        let encoded_ground_atom = self.dictionary.encode(ground_atom);
+
+        Ok(self.unprocessed_insertions.insert(relation, encoded_ground_atom))
+    }
+    // The checks of try_insert: external relations are never stored, and facts have the arity of
+    // their relation. Relations that are neither in the program nor stored yet take the arity of
+    // the facts in `pending`, if any.
+    fn check_insertion(
+        &self,
+        relation: &str,
+        ground_atom: &AnonymousGroundAtom,
+        pending: Option<&HashMap<&str, usize>>,
+    ) -> Result<(), String> {
+        if self.externals.contains(relation) {
+            return Err(format!("{} is an external relation, its facts are computed", relation));
+        }
+        let arity = self
+            .arities
+            .get(relation)
+            .copied()
+            .or_else(|| self.processed.arity(relation))
+            .or_else(|| self.unprocessed_insertions.arity(relation))
+            .or_else(|| pending.and_then(|pending| pending.get(relation).copied()));
+
+        check_arity(arity, ground_atom.len()).map_err(|err| format!("{}: {}", relation, err))
+    }
+    // check_insertion over a batch of facts, each relation unknown so far taking the arity of its
+    // first fact.
+    fn check_insertions<'a>(
+        &self,
+        facts: impl Iterator<Item = (&'a String, &'a AnonymousGroundAtom)>,
+    ) -> Result<(), String> {
+        let mut pending = HashMap::new();
+        for (relation, ground_atom) in facts {
+            self.check_insertion(relation, ground_atom, Some(&pending))?;
+            pending.insert(relation.as_str(), ground_atom.len());
+        }
+
+        Ok(())
+    }
+    // Inserts every fact, see try_insert, unless one of them fails its checks, in which case none
+    // is.
+    pub fn try_insert_all(
+        &mut self,
+        facts: Vec<(String, AnonymousGroundAtom)>,
+    ) -> Result<(), String> {
+        self.check_insertions(facts.iter().map(|(relation, ground_atom)| (relation, ground_atom)))?;
+        for (relation, ground_atom) in facts {
+            self.try_insert(&relation, ground_atom)?;
+        }
+
+        Ok(())
+    }
+    // From now on every insertion is logged before being accepted. Whatever the log already holds
+    // is replayed as unprocessed insertions, once every record has passed the checks of try_insert:
+    // a log written for another program fails without inserting anything.
+    pub fn attach_log(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let (log, records) = WriteAheadLog::open(path)?;
+
+        self.check_insertions(records.iter().map(|record| match record {
+            LogRecord::Insertion(relation_symbol, ground_atom) => (relation_symbol, ground_atom),
+        }))?;
+        records.into_iter().for_each(|record| match record {
+            LogRecord::Insertion(relation_symbol, ground_atom) => {
+                let encoded_ground_atom = self.dictionary.encode(ground_atom);
+
+                self.unprocessed_insertions
+                    .insert(&relation_symbol, encoded_ground_atom);
+            }
+        });
+        self.log = Some(log);
+
+        Ok(())
     }
     pub fn contains(
         &self,
@@ -30,8 +144,14 @@ impl MicroRuntime {
             return Err("poll needed to obtain correct results".to_string());
         }
 
-        if !self.processed.contains(relation, ground_atom) {
-            return Ok(self.unprocessed_insertions.contains(relation, ground_atom));
+        let Some(encoded_ground_atom) = self.dictionary.try_encode(ground_atom) else {
+            return Ok(false);
+        };
+
+        if !self.processed.contains(relation, &encoded_ground_atom) {
+            return Ok(self
+                .unprocessed_insertions
+                .contains(relation, &encoded_ground_atom));
         }
 
         Ok(true)
@@ -43,18 +163,56 @@ impl MicroRuntime {
         if !self.safe() {
             return Err("poll needed to obtain correct results".to_string());
         }
-        return Ok(self
+        let matchers = encode_query(query, &self.dictionary);
+
+        // A relation that was never mentioned nor inserted into is simply empty.
+        Ok(self
             .processed
-            .get_relation(query.symbol)
-            .iter()
-            .filter(|fact| pattern_match(query, fact))
-            .map(|fact| fact.clone()));
+            .inner
+            .get(query.symbol)
+            .into_iter()
+            .flat_map(|relation| relation.iter())
+            .filter(move |fact| {
+                matchers
+                    .as_ref()
+                    .is_some_and(|matchers| pattern_match(matchers, fact))
+            })
+            .map(|fact| self.dictionary.decode(fact)))
     }
+    // Panics if a function fails, see try_poll.
     pub fn poll(&mut self) {
+        self.try_poll().unwrap()
+    }
+    // Fails if a function fails, once everything else has been derived. Facts a function fails on
+    // are not derived. Fails as well if the limits are exceeded, in which case the poll is undone:
+    // the insertions are pending again, e.g. until the limits are raised. Fails before evaluating
+    // anything if the write-ahead log cannot be synced.
+    pub fn try_poll(&mut self) -> Result<(), String> {
+        let mut errors = vec![];
         if !self.unprocessed_insertions.is_empty() {
+            if let Some(log) = &mut self.log {
+                log.sync()?;
+            }
+
+            // Copying is only worth it if evaluation may be aborted
+            let rollback = (!self.limits.is_unbounded()).then(|| {
+                (
+                    self.processed.clone(),
+                    self.unprocessed_insertions.clone(),
+                    self.intensional_insertions.clone(),
+                )
+            });
+
             // Additions
+            let mut reevaluate = false;
             self.unprocessed_insertions.drain_all_relations().for_each(
                 |(relation_symbol, unprocessed_facts)| {
+                    reevaluate |= !unprocessed_facts.is_empty()
+                        && self.negation_dependencies.contains(&relation_symbol);
+                    if self.has_negation && self.intensional_relations.contains(&relation_symbol) {
+                        self.intensional_insertions
+                            .insert_all(&relation_symbol, unprocessed_facts.clone().into_iter());
+                    }
                     // We dump all unprocessed EDB relations into delta EDB relations
                     self.processed.insert_registered(
                         &format!("{}{}", DELTA_PREFIX, relation_symbol),
@@ -66,18 +224,196 @@ impl MicroRuntime {
                 },
             );
 
-            semi_naive_evaluation(
+            if reevaluate {
+                self.prepare_reevaluation();
+            }
+
+            let evaluation = semi_naive_evaluation(
                 &mut self.processed,
-                &self.nonrecursive_delta_program,
-                &self.recursive_delta_program,
+                &mut self.dictionary,
+                &self.delta_strata,
+                &self.externals,
+                &mut Budget::new(self.limits),
+                &mut errors,
             );
+            if let Err(err) = evaluation {
+                let (processed, unprocessed_insertions, intensional_insertions) = rollback.unwrap();
+                self.processed = processed;
+                self.unprocessed_insertions = unprocessed_insertions;
+                self.intensional_insertions = intensional_insertions;
+
+                return Err(err);
+            }
 
             self.processed.drain_deltas()
         }
+
+        errors.sort();
+        errors.dedup();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+    // New facts may invalidate conclusions drawn from a negation, which can't be taken back by
+    // evaluating insertions alone. Hence a poll inserting into a relation that a negated atom
+    // reads, directly or not, evaluates the whole program again from its extensional facts, all
+    // of which count as new. This costs as much as the first poll did, however few the insertions.
+    // Other polls stay incremental, since the negated relations don't change.
+    fn prepare_reevaluation(&mut self) {
+        let relation_symbols: Vec<_> = self
+            .processed
+            .inner
+            .keys()
+            .filter(|relation_symbol| !relation_symbol.starts_with(DELTA_PREFIX))
+            .cloned()
+            .collect();
+
+        relation_symbols.into_iter().for_each(|relation_symbol| {
+            let facts: Vec<_> = if self.intensional_relations.contains(&relation_symbol) {
+                self.intensional_insertions
+                    .inner
+                    .get(&relation_symbol)
+                    .into_iter()
+                    .flat_map(|relation| relation.iter().map(|fact| fact.to_vec()))
+                    .collect()
+            } else {
+                self.processed
+                    .get_relation(&relation_symbol)
+                    .iter()
+                    .map(|fact| fact.to_vec())
+                    .collect()
+            };
+
+            self.processed.replace_relation(
+                &format!("{}{}", DELTA_PREFIX, relation_symbol),
+                facts.clone().into_iter(),
+            );
+            self.processed
+                .replace_relation(&relation_symbol, facts.into_iter());
+        });
     }
+    // Panics if the program is not valid, see try_new.
     pub fn new(program: Program) -> Self {
-        let mut processed: RelationStorage = Default::default();
-        let mut unprocessed_insertions: RelationStorage = Default::default();
+        Self::try_new(program).unwrap()
+    }
+    // Fails if a head variable is not bound by a positive body atom, if a function is not resolved,
+    // or if a relation depends on its own negation.
+    pub fn try_new(program: Program) -> Result<Self, String> {
+        Self::try_with_layout(program, StorageLayout::default())
+    }
+    // Snapshots are only meaningful in between polls, hence unprocessed insertions are refused.
+    pub fn save(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        if !self.safe() {
+            return Err("poll needed before saving".to_string());
+        }
+
+        persistence::save(
+            path.as_ref(),
+            self.processed.layout,
+            self.fingerprint,
+            &self.dictionary,
+            &self.processed,
+            &self.intensional_relations,
+            &self.intensional_insertions,
+        )?;
+
+        // The snapshot now holds everything the log did.
+        if let Some(log) = &mut self.log {
+            log.compact()?;
+        }
+
+        Ok(())
+    }
+    // If the snapshot was written by a different program, only its extensional facts and the facts
+    // inserted straight into intensional relations are kept, and everything else is materialised
+    // again. Fails if those facts don't fit the program, e.g. by arity, or if a function fails on
+    // them.
+    pub fn load(path: impl AsRef<Path>, program: Program) -> Result<Self, String> {
+        Self::load_with_externals(path, program, ExternalRelations::default())
+    }
+    // Same as load, for runtimes built with try_with_externals. The layout is the snapshot's.
+    pub fn load_with_externals(
+        path: impl AsRef<Path>,
+        program: Program,
+        externals: ExternalRelations,
+    ) -> Result<Self, String> {
+        let snapshot = persistence::load(path.as_ref())?;
+        let mut runtime = Self::try_with_externals(program, snapshot.layout, externals)?;
+
+        if snapshot.fingerprint == runtime.fingerprint {
+            runtime.dictionary = snapshot.dictionary;
+            snapshot
+                .relations
+                .into_iter()
+                .for_each(|(relation_symbol, _intensional, facts)| {
+                    runtime
+                        .processed
+                        .insert_registered(&relation_symbol, facts.into_iter());
+                });
+            snapshot
+                .intensional_insertions
+                .into_iter()
+                .for_each(|(relation_symbol, facts)| {
+                    runtime
+                        .intensional_insertions
+                        .insert_all(&relation_symbol, facts.into_iter());
+                });
+
+            return Ok(runtime);
+        }
+
+        let extensional_relations: Vec<_> = snapshot
+            .relations
+            .into_iter()
+            .filter(|(relation_symbol, intensional, _facts)| {
+                !intensional && !runtime.intensional_relations.contains(relation_symbol)
+            })
+            .collect();
+        let kept_relations = extensional_relations
+            .into_iter()
+            .map(|(relation_symbol, _intensional, facts)| (relation_symbol, facts))
+            .chain(snapshot.intensional_insertions);
+        for (relation_symbol, facts) in kept_relations {
+            for fact in facts {
+                runtime.try_insert(&relation_symbol, snapshot.dictionary.decode(&fact))?;
+            }
+        }
+        runtime.try_poll()?;
+
+        Ok(runtime)
+    }
+    pub fn with_layout(program: Program, layout: StorageLayout) -> Self {
+        Self::try_with_layout(program, layout).unwrap()
+    }
+    pub fn try_with_layout(program: Program, layout: StorageLayout) -> Result<Self, String> {
+        Self::try_with_externals(program, layout, ExternalRelations::default())
+    }
+    // Atoms of external relations are computed by them instead of being stored, see
+    // ExternalRelations. Fails as well if an external atom can't be computed with any of its
+    // modes.
+    pub fn try_with_externals(
+        program: Program,
+        layout: StorageLayout,
+        externals: ExternalRelations,
+    ) -> Result<Self, String> {
+        check_safety(&program)?;
+        let arities = relation_arities(&program, &externals)?;
+        for rule in &program.inner {
+            order_body(rule, &externals)?;
+        }
+        let strata = stratify(&program)?;
+
+        let fingerprint = persistence::fingerprint(&program, &externals);
+        let intensional_relations = program
+            .inner
+            .iter()
+            .map(|rule| rule.head.symbol.clone())
+            .collect();
+
+        let mut processed = RelationStorage::new(layout);
+        let mut unprocessed_insertions = RelationStorage::new(layout);
 
         let mut relations = HashSet::new();
         let mut delta_relations = HashSet::new();
@@ -86,42 +422,160 @@ impl MicroRuntime {
             relations.insert(&rule.head.symbol);
             delta_relations.insert(format!("{}{}", DELTA_PREFIX, rule.head.symbol));
 
-            rule.body.iter().for_each(|body_atom| {
-                relations.insert(&body_atom.symbol);
-                delta_relations.insert(format!("{}{}", DELTA_PREFIX, body_atom.symbol));
-            })
+            rule.body
+                .iter()
+                .filter(|body_atom| !externals.contains(&body_atom.symbol))
+                .for_each(|body_atom| {
+                    relations.insert(&body_atom.symbol);
+                    delta_relations.insert(format!("{}{}", DELTA_PREFIX, body_atom.symbol));
+                })
         });
 
         relations.iter().for_each(|relation_symbol| {
-            processed
-                .inner
-                .entry(relation_symbol.to_string())
-                .or_default();
-
-            unprocessed_insertions
-                .inner
-                .entry(relation_symbol.to_string())
-                .or_default();
+            processed.register(relation_symbol);
+            unprocessed_insertions.register(relation_symbol);
         });
 
         delta_relations.iter().for_each(|relation_symbol| {
-            processed
-                .inner
-                .entry(relation_symbol.to_string())
-                .or_default();
+            processed.register(relation_symbol);
         });
 
-        let (nonrecursive_delta_program, recursive_delta_program) =
-            split_program(make_delta_program(&program, true));
+        // External relations never change, hence they have no deltas.
+        let delta_strata = strata
+            .iter()
+            .map(|stratum| {
+                let mut delta_program = make_delta_program(stratum, true);
+                delta_program.inner.retain(|rule| {
+                    !rule.body.iter().any(|body_atom| {
+                        body_atom
+                            .symbol
+                            .strip_prefix(DELTA_PREFIX)
+                            .is_some_and(|symbol| externals.contains(symbol))
+                    })
+                });
 
-        let nonrecursive_delta_program = sort_program(nonrecursive_delta_program);
+                delta_program
+            })
+            .collect();
+        let has_negation = program
+            .inner
+            .iter()
+            .any(|rule| rule.body.iter().any(|body_atom| body_atom.negated));
+        let negation_dependencies = negation_dependencies(&program);
 
-        Self {
+        Ok(Self {
+            program,
+            dictionary: Default::default(),
             processed,
             unprocessed_insertions,
-            nonrecursive_delta_program,
-            recursive_delta_program,
+            delta_strata,
+            intensional_relations,
+            has_negation,
+            negation_dependencies,
+            arities,
+            intensional_insertions: RelationStorage::new(layout),
+            fingerprint,
+            log: None,
+            limits: Limits::default(),
+            externals,
+        })
+    }
+    // Bounds every following poll, see Limits.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+    pub fn externals(&self) -> &ExternalRelations {
+        &self.externals
+    }
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+    // Every relation along with its number of facts, sorted by name.
+    pub fn relation_sizes(&self) -> Result<Vec<(String, usize)>, String> {
+        if !self.safe() {
+            return Err("poll needed to obtain correct results".to_string());
         }
+
+        let mut sizes: Vec<_> = self
+            .processed
+            .inner
+            .iter()
+            .filter(|(relation_symbol, _)| !relation_symbol.starts_with(DELTA_PREFIX))
+            .map(|(relation_symbol, facts)| (relation_symbol.clone(), facts.len()))
+            .collect();
+        sizes.sort();
+
+        Ok(sizes)
+    }
+    // Every relation as a JSON object mapping it to its facts, e.g. {"e": [["a", "b"]]}, see the
+    // serialisation of TypedValue. Relations and facts are sorted so that exports are reproducible.
+    // For programs with negation, derived relations only hold the facts inserted into them: once
+    // imported, derived facts would count as inserted, and never be withdrawn.
+    #[cfg(feature = "serde")]
+    pub fn export_json(&self) -> Result<String, String> {
+        if !self.safe() {
+            return Err("poll needed to obtain correct results".to_string());
+        }
+
+        let relations: std::collections::BTreeMap<_, _> = self
+            .processed
+            .inner
+            .iter()
+            .filter(|(relation_symbol, _)| !relation_symbol.starts_with(DELTA_PREFIX))
+            .map(|(relation_symbol, facts)| {
+                let facts = match self.has_negation
+                    && self.intensional_relations.contains(relation_symbol)
+                {
+                    true => self.intensional_insertions.inner.get(relation_symbol),
+                    false => Some(facts),
+                };
+                let mut facts: Vec<_> = facts
+                    .into_iter()
+                    .flat_map(|facts| facts.iter())
+                    .map(|fact| self.dictionary.decode(fact))
+                    .collect();
+                facts.sort();
+
+                (relation_symbol, facts)
+            })
+            .collect();
+
+        serde_json::to_string_pretty(&relations).map_err(|err| err.to_string())
+    }
+    // Inserts the facts of an export, which are pending until the next poll. Nothing is inserted
+    // if the JSON is not valid, or if any fact fails the checks of try_insert.
+    #[cfg(feature = "serde")]
+    pub fn import_json(&mut self, json: &str) -> Result<(), String> {
+        let relations: std::collections::BTreeMap<String, Vec<AnonymousGroundAtom>> =
+            serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        self.try_insert_all(
+            relations
+                .into_iter()
+                .flat_map(|(relation_symbol, facts)| {
+                    facts.into_iter().map(move |fact| (relation_symbol.clone(), fact))
+                })
+                .collect(),
+        )
+    }
+    // Typed counterparts of insert, try_insert and query, for structs deriving Relation.
+    pub fn insert_relation<R: Relation>(&mut self, value: R) -> bool {
+        self.insert(R::NAME, value.into_fact())
+    }
+    pub fn try_insert_relation<R: Relation>(&mut self, value: R) -> Result<bool, String> {
+        self.try_insert(R::NAME, value.into_fact())
+    }
+    // Every fact of the relation, which fails on the first one that does not fit R.
+    pub fn query_relation<R: Relation>(&self) -> Result<Vec<R>, String> {
+        self.query(&Query {
+            matchers: vec![],
+            symbol: R::NAME,
+        })?
+        .map(R::from_fact)
+        .collect()
     }
     pub fn safe(&self) -> bool {
         self.unprocessed_insertions.is_empty()
@@ -131,9 +585,133 @@ impl MicroRuntime {
 #[cfg(test)]
 mod tests {
     use crate::engine::datalog::MicroRuntime;
+    use crate::engine::fact_storage::StorageLayout;
+    use crate::engine::limits::Limits;
+    use crate::parser::parse_source;
     use datalog_rule_macro::program;
+    use datalog_rule_macro::Relation;
     use datalog_syntax::*;
-    use std::collections::HashSet;
+    use std::cmp::Ordering;
+    use std::collections::{HashMap, HashSet};
+    use std::hash::{Hash, Hasher};
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // A semver version, whose build metadata neither equality nor ordering look at
+    #[derive(Clone, Debug)]
+    struct Version {
+        release: (usize, usize, usize),
+        build: String,
+    }
+
+    impl Version {
+        fn parse(text: &str) -> Result<Self, String> {
+            let (release, build) = text.split_once('+').unwrap_or((text, ""));
+            let numbers = release
+                .split('.')
+                .map(|number| number.parse::<usize>().map_err(|err| err.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let [major, minor, patch] = numbers.as_slice() else {
+                return Err(format!("{} is not a version", text));
+            };
+
+            Ok(Version {
+                release: (*major, *minor, *patch),
+                build: build.to_string(),
+            })
+        }
+    }
+
+    impl PartialEq for Version {
+        fn eq(&self, other: &Self) -> bool {
+            self.release == other.release
+        }
+    }
+
+    impl Eq for Version {}
+
+    impl PartialOrd for Version {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Version {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.release.cmp(&other.release)
+        }
+    }
+
+    impl Hash for Version {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.release.hash(state)
+        }
+    }
+
+    impl std::fmt::Display for Version {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let (major, minor, patch) = self.release;
+            write!(f, "{}.{}.{}", major, minor, patch)?;
+            if !self.build.is_empty() {
+                write!(f, "+{}", self.build)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Custom for Version {
+        const KIND: &'static str = "semver";
+
+        fn encode(&self) -> Vec<u8> {
+            self.to_string().into_bytes()
+        }
+        fn decode(bytes: &[u8]) -> Result<Self, String> {
+            Version::parse(std::str::from_utf8(bytes).map_err(|err| err.to_string())?)
+        }
+    }
+
+    fn version(text: &str) -> TypedValue {
+        TypedValue::custom(Version::parse(text).unwrap())
+    }
+
+    fn newer(args: HashMap<&str, &TypedValue>) -> TypedValue {
+        (args["v"] > args["w"]).into()
+    }
+
+    #[test]
+    fn integration_test_custom_values() {
+        let program = program! {
+            usable(?t, ?v)           <- [tool(?t, ?v), supported(?v)],
+            compared(?t, ?u, newer(?v, ?w)) <- [usable(?t, ?v), usable(?u, ?w)],
+            outdated(?u)             <- [compared(?t, ?u, true)],
+        };
+
+        let mut micro_runtime = MicroRuntime::new(program);
+        micro_runtime.insert("tool", vec!["cargo".into(), version("1.70.0+abc")]);
+        micro_runtime.insert("tool", vec!["rustc".into(), version("1.75.2")]);
+        micro_runtime.insert("tool", vec!["clippy".into(), version("0.1.0")]);
+        micro_runtime.insert("supported", vec![version("1.70.0")]);
+        micro_runtime.insert("supported", vec![version("1.75.2+def")]);
+        micro_runtime.insert("supported", vec!["0.1.0".into()]);
+        micro_runtime.poll();
+
+        // Build metadata doesn't matter, whereas a string isn't a version
+        let mut usable: Vec<_> = micro_runtime
+            .query(&build_query!(usable(_, _)))
+            .unwrap()
+            .map(|fact| fact[0].to_string())
+            .collect();
+        usable.sort();
+        assert_eq!(vec!["cargo", "rustc"], usable);
+        let outdated: Vec<_> = micro_runtime.query(&build_query!(outdated(_))).unwrap().collect();
+        assert_eq!(vec![vec![TypedValue::from("cargo")]], outdated);
+        assert!(micro_runtime
+            .contains("usable", &vec!["rustc".into(), version("1.75.2+ghi")])
+            .unwrap());
+        assert!(version("1.70.0") < version("1.75.2"));
+        assert_eq!("1.70.0+abc", version("1.70.0+abc").to_string());
+    }
 
     #[test]
     fn integration_test_stupid() {
@@ -147,7 +725,7 @@ mod tests {
 
         micro_runtime.poll();
         let q = build_query!(FENV(_));
-        let actual_answer: HashSet<_> = micro_runtime.query(&q).unwrap().into_iter().collect();
+        let actual_answer: HashSet<_> = micro_runtime.query(&q).unwrap().collect();
 
         let expected_answer: HashSet<AnonymousGroundAtom> = vec![
             vec!["a".into()]
@@ -158,6 +736,391 @@ mod tests {
         assert_eq!(expected_answer, actual_answer)
     }
 
+    #[test]
+    fn integration_test_skolem_function() {
+        fn f(args: HashMap<&str, &TypedValue>) -> TypedValue {
+            let x_variable_value = args.get("x").unwrap();
+            let y_variable_value = args.get("y").unwrap();
+
+            format!("{}={}", x_variable_value, y_variable_value).into()
+        }
+
+        let program = program! {
+            ENV(?x, ?y)     <- [INPUTS("env", ?x, ?y)],
+            FENV(f(?x, ?y)) <- [ENV(?x, ?y)]
+        };
+
+        let mut micro_runtime = MicroRuntime::new(program);
+        micro_runtime.insert("INPUTS", vec!["env".into(), "a".into(), "b".into()]);
+        micro_runtime.poll();
+
+        let actual_answer: Vec<_> = micro_runtime.query(&build_query!(FENV(_))).unwrap().collect();
+
+        assert_eq!(vec![vec![TypedValue::from("a=b")]], actual_answer)
+    }
+
+    #[test]
+    fn integration_test_fallible_functions() {
+        // Functions may capture state, here the number of times they were called
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let port = move |args: HashMap<&str, &TypedValue>| {
+            counted.fetch_add(1, AtomicOrdering::SeqCst);
+            match args["x"] {
+                TypedValue::Str(x) => x
+                    .parse::<usize>()
+                    .map(TypedValue::Int)
+                    .map_err(|err| format!("{:?}: {}", x, err)),
+                x => Err(format!("{:?} is not a string", x)),
+            }
+        };
+
+        let program = program! {
+            PORT(?x, port(?x)) <- [SETTING(?x)]
+        };
+
+        let mut micro_runtime = MicroRuntime::new(program);
+        micro_runtime.insert("SETTING", vec!["8080".into()]);
+        micro_runtime.insert("SETTING", vec!["http".into()]);
+
+        assert_eq!(
+            Err("port(x): \"http\": invalid digit found in string".to_string()),
+            micro_runtime.try_poll()
+        );
+        assert_eq!(2, calls.load(AtomicOrdering::SeqCst));
+        // Whatever the function did not fail on is derived nonetheless
+        let actual_answer: Vec<_> = micro_runtime
+            .query(&build_query!(PORT(_, _)))
+            .unwrap()
+            .collect();
+        assert_eq!(vec![vec!["8080".into(), TypedValue::Int(8080)]], actual_answer);
+
+        let source = parse_source("PORT(?x, port(?x)) <- [SETTING(?x)].").unwrap();
+        let unresolved = Program::from(source.rules);
+        assert!(MicroRuntime::try_new(unresolved.clone()).is_err());
+
+        let mut functions = FunctionRegistry::default();
+        functions.register("port", port);
+        assert!(MicroRuntime::try_new(functions.resolve(unresolved).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn integration_test_limits() {
+        fn succ(args: HashMap<&str, &TypedValue>) -> Result<TypedValue, String> {
+            match args["x"] {
+                TypedValue::Int(x) if *x < 20 => Ok(TypedValue::Int(x + 1)),
+                x => Err(format!("{} is too large", x)),
+            }
+        }
+
+        let program = program! {
+            nat(succ(?x)) <- [nat(?x)],
+            tc(?x, ?y)    <- [e(?x, ?y)],
+            tc(?x, ?z)    <- [tc(?x, ?y), e(?y, ?z)]
+        };
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("e", vec!["a".into(), "b".into()]);
+        runtime.insert("e", vec!["b".into(), "c".into()]);
+        runtime.set_limits(Limits {
+            max_skolem_depth: Some(5),
+            ..Default::default()
+        });
+        runtime.try_poll().unwrap();
+        assert_eq!(3, runtime.query(&build_query!(tc(_, _))).unwrap().count());
+
+        // Aborted polls are undone, and what they were given stays pending
+        runtime.insert("nat", vec![0usize.into()]);
+        runtime.insert("e", vec!["c".into(), "d".into()]);
+        assert_eq!(
+            Err("succ(x) exceeded a nesting depth of 5".to_string()),
+            runtime.try_poll()
+        );
+        assert!(!runtime.safe());
+        assert_eq!(0, runtime.processed.get_relation("nat").len());
+        assert_eq!(3, runtime.processed.get_relation("tc").len());
+
+        let limits = [
+            (
+                Limits { max_iterations: Some(4), ..Default::default() },
+                "evaluation exceeded 4 iterations",
+            ),
+            (
+                Limits { max_facts_per_relation: Some(10), ..Default::default() },
+                "relation nat exceeded 10 facts",
+            ),
+            (
+                Limits { time_budget: Some(Duration::ZERO), ..Default::default() },
+                "evaluation exceeded its time budget of 0ns",
+            ),
+        ];
+        for (limits, expected_error) in limits {
+            runtime.set_limits(limits);
+            assert_eq!(Err(expected_error.to_string()), runtime.try_poll());
+        }
+
+        runtime.set_limits(Limits::default());
+        assert_eq!(Err("succ(x): 20 is too large".to_string()), runtime.try_poll());
+        assert_eq!(21, runtime.query(&build_query!(nat(_))).unwrap().count());
+        assert_eq!(6, runtime.query(&build_query!(tc(_, _))).unwrap().count());
+    }
+
+    #[test]
+    fn integration_test_unknown_values() {
+        let program = program! {
+            FENV(?x)     <- [INPUTS("env", ?x, ?y)]
+        };
+
+        let mut micro_runtime = MicroRuntime::new(program);
+        micro_runtime.insert("INPUTS", vec!["env".into(), "a".into(), "b".into()]);
+        micro_runtime.poll();
+
+        let q = build_query!(FENV("never_seen"));
+        assert_eq!(0, micro_runtime.query(&q).unwrap().count());
+        assert!(!micro_runtime.contains("FENV", &vec!["never_seen".into()]).unwrap());
+        assert!(micro_runtime.contains("FENV", &vec!["a".into()]).unwrap());
+    }
+
+    #[test]
+    fn integration_test_relation_sizes() {
+        let program = program! {
+            FENV(?x)     <- [INPUTS("env", ?x, ?y)]
+        };
+
+        let mut micro_runtime = MicroRuntime::new(program);
+        micro_runtime.insert("INPUTS", vec!["env".into(), "a".into(), "b".into()]);
+        micro_runtime.insert("INPUTS", vec!["env".into(), "a".into(), "c".into()]);
+        micro_runtime.insert("OTHER", vec!["x".into()]);
+        assert!(micro_runtime.relation_sizes().is_err());
+        micro_runtime.poll();
+
+        assert_eq!(
+            vec![
+                ("FENV".to_string(), 1),
+                ("INPUTS".to_string(), 2),
+                ("OTHER".to_string(), 1),
+            ],
+            micro_runtime.relation_sizes().unwrap()
+        );
+    }
+
+    #[test]
+    fn integration_test_value_kinds() {
+        let program = program! {
+            timeout(?j, ?t)        <- [job(?j), default_timeout(?t)],
+            same_branches(?j, ?k)  <- [branches(?j, ?b), branches_of(?k, ?b)],
+            shifted(?j)            <- [offset(?j, -1)],
+        };
+
+        let mut micro_runtime = MicroRuntime::new(program);
+        micro_runtime.insert("job", vec!["test".into()]);
+        micro_runtime.insert("default_timeout", vec![1.5.into()]);
+        let branches = TypedValue::List(vec!["main".into(), "release/*".into()]);
+        micro_runtime.insert("branches", vec!["test".into(), branches.clone()]);
+        micro_runtime.insert("branches_of", vec!["lint".into(), branches]);
+        micro_runtime.insert(
+            "branches_of",
+            vec!["docs".into(), TypedValue::List(vec!["main".into()])],
+        );
+        // Values of different kinds never join
+        micro_runtime.insert("offset", vec!["test".into(), (-1i64).into()]);
+        micro_runtime.insert("offset", vec!["lint".into(), (-1.0).into()]);
+        micro_runtime.poll();
+
+        assert!(micro_runtime.contains("timeout", &vec!["test".into(), 1.5.into()]).unwrap());
+        let same_branches: Vec<_> = micro_runtime
+            .query(&build_query!(same_branches(_, _)))
+            .unwrap()
+            .collect();
+        assert_eq!(vec![vec![TypedValue::from("test"), "lint".into()]], same_branches);
+        let shifted: Vec<_> = micro_runtime.query(&build_query!(shifted(_))).unwrap().collect();
+        assert_eq!(vec![vec![TypedValue::from("test")]], shifted);
+        assert_eq!(TypedValue::from(0.0), TypedValue::from(-0.0));
+    }
+
+    #[test]
+    fn integration_test_layouts_agree() {
+        let tc_program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z)],
+        };
+
+        let answers: Vec<HashSet<AnonymousGroundAtom>> = [StorageLayout::Hashed, StorageLayout::RowMajor]
+            .into_iter()
+            .map(|layout| {
+                let mut runtime = MicroRuntime::with_layout(tc_program.clone(), layout);
+                (0..10usize).for_each(|node| {
+                    runtime.insert("e", vec![node.into(), (node + 1).into()]);
+                });
+                runtime.poll();
+                runtime.insert("e", vec![10usize.into(), 0usize.into()]);
+                assert_eq!(
+                    Err("e: relation of arity 2 cannot hold a fact of arity 1".to_string()),
+                    runtime.try_insert("e", vec![0usize.into()])
+                );
+                runtime.poll();
+
+                runtime.query(&build_query!(tc(_, _))).unwrap().collect()
+            })
+            .collect();
+
+        assert_eq!(11 * 11, answers[0].len());
+        assert_eq!(answers[0], answers[1]);
+    }
+
+    #[test]
+    fn integration_test_arities() {
+        // Relations take their arity from the program, before any fact is inserted
+        let mut runtime = MicroRuntime::new(program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+        });
+        assert_eq!(
+            Err("e: relation of arity 2 cannot hold a fact of arity 1".to_string()),
+            runtime.try_insert("e", vec!["a".into()])
+        );
+        assert_eq!(
+            Err("tc: relation of arity 2 cannot hold a fact of arity 3".to_string()),
+            runtime.try_insert("tc", vec!["a".into(), "b".into(), "c".into()])
+        );
+        runtime.try_poll().unwrap();
+
+        let program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            sink(?x) <- [e(?x), !tc(?x, ?y)],
+        };
+        assert!(MicroRuntime::try_new(program)
+            .err()
+            .unwrap()
+            .starts_with("relation e is used with arities"));
+    }
+
+    #[test]
+    fn integration_test_three_way_join() {
+        let program = program! {
+            hop3(?a, ?d) <- [t(?a, "x", ?b), t(?b, "x", ?c), t(?c, "x", ?d)]
+        };
+
+        let mut runtime = MicroRuntime::new(program);
+        vec![
+            vec!["a".into(), "x".into(), "b".into()],
+            vec!["b".into(), "x".into(), "c".into()],
+            vec!["c".into(), "x".into(), "d".into()],
+            vec!["c".into(), "y".into(), "e".into()],
+        ]
+        .into_iter()
+        .for_each(|fact| {
+            runtime.insert("t", fact);
+        });
+        runtime.poll();
+
+        let actual: HashSet<AnonymousGroundAtom> =
+            runtime.query(&build_query!(hop3(_, _))).unwrap().collect();
+        let expected: HashSet<AnonymousGroundAtom> =
+            vec![vec!["a".into(), "d".into()]].into_iter().collect();
+
+        assert_eq!(expected, actual);
+    }
+
+    // The third atom joins on a column of the first one, which is not the last fact of the product
+    // it is joined with
+    #[test]
+    fn integration_test_join_on_earlier_atom() {
+        let program = program! {
+            p(?a, ?c, ?d) <- [e(?a, ?b), f(?b, ?c), g(?a, ?d)]
+        };
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("e", vec!["a".into(), "b".into()]);
+        runtime.insert("f", vec!["b".into(), "c".into()]);
+        runtime.insert("g", vec!["a".into(), "d".into()]);
+        runtime.insert("g", vec!["b".into(), "x".into()]);
+        runtime.poll();
+
+        let actual: Vec<_> = runtime.query(&build_query!(p(_, _, _))).unwrap().collect();
+        assert_eq!(vec![vec![TypedValue::from("a"), "c".into(), "d".into()]], actual);
+    }
+
+    #[test]
+    fn integration_test_strata() {
+        // from_a is not recursive, but has to wait for tc to be complete
+        let program = program! {
+            tc(?x, ?y)  <- [e(?x, ?y)],
+            tc(?x, ?z)  <- [tc(?x, ?y), e(?y, ?z)],
+            from_a(?y)  <- [tc("a", ?y)],
+        };
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("e", vec!["a".into(), "b".into()]);
+        runtime.insert("e", vec!["b".into(), "c".into()]);
+        runtime.poll();
+        runtime.insert("e", vec!["c".into(), "d".into()]);
+        runtime.poll();
+
+        let actual: HashSet<AnonymousGroundAtom> =
+            runtime.query(&build_query!(from_a(_))).unwrap().collect();
+        let expected: HashSet<AnonymousGroundAtom> =
+            vec![vec!["b".into()], vec!["c".into()], vec!["d".into()]]
+                .into_iter()
+                .collect();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn integration_test_negation() {
+        let program = program! {
+            reachable(?x)   <- [root(?x)],
+            reachable(?y)   <- [reachable(?x), e(?x, ?y)],
+            unreachable(?x) <- [node(?x), !reachable(?x)],
+            leaf(?x)        <- [node(?x), !e(?x, ?y)],
+        };
+
+        let mut runtime = MicroRuntime::new(program);
+        ["a", "b", "c"].into_iter().for_each(|node| {
+            runtime.insert("node", vec![node.into()]);
+        });
+        runtime.insert("root", vec!["a".into()]);
+        runtime.insert("e", vec!["a".into(), "b".into()]);
+        runtime.poll();
+
+        let unreachable: Vec<_> = runtime.query(&build_query!(unreachable(_))).unwrap().collect();
+        assert_eq!(vec![vec![TypedValue::from("c")]], unreachable);
+        let leaves: HashSet<AnonymousGroundAtom> =
+            runtime.query(&build_query!(leaf(_))).unwrap().collect();
+        assert_eq!(
+            vec![vec!["b".into()], vec!["c".into()]].into_iter().collect::<HashSet<_>>(),
+            leaves
+        );
+
+        // c becoming reachable withdraws a conclusion, and b is no longer a leaf
+        runtime.insert("e", vec!["b".into(), "c".into()]);
+        runtime.poll();
+
+        assert_eq!(0, runtime.query(&build_query!(unreachable(_))).unwrap().count());
+        let leaves: Vec<_> = runtime.query(&build_query!(leaf(_))).unwrap().collect();
+        assert_eq!(vec![vec![TypedValue::from("c")]], leaves);
+
+        // No negated atom reads node, hence this poll is incremental
+        runtime.insert("node", vec!["d".into()]);
+        runtime.poll();
+        let unreachable: Vec<_> = runtime.query(&build_query!(unreachable(_))).unwrap().collect();
+        assert_eq!(vec![vec![TypedValue::from("d")]], unreachable);
+        let leaves: HashSet<AnonymousGroundAtom> =
+            runtime.query(&build_query!(leaf(_))).unwrap().collect();
+        assert_eq!(
+            vec![vec!["c".into()], vec!["d".into()]].into_iter().collect::<HashSet<_>>(),
+            leaves
+        );
+    }
+
+    #[test]
+    fn integration_test_invalid_programs() {
+        assert!(MicroRuntime::try_new(program! { p(?x) <- [q(?x), !p(?x)] }).is_err());
+
+        let mut unsafe_program = program! { p(?x) <- [q(?x), r(?x)] };
+        unsafe_program.inner[0].body.iter_mut().for_each(|body_atom| body_atom.negated = true);
+        assert!(MicroRuntime::try_new(unsafe_program).is_err());
+    }
+
     #[test]
     fn integration_test_insertions_only() {
         let tc_program = program! {
@@ -260,4 +1223,158 @@ mod tests {
             actual_all_from_a_after_update
         );
     }
+
+    #[derive(Relation, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Edge {
+        from: String,
+        to: String,
+    }
+
+    #[derive(Relation, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    #[relation(name = "tc")]
+    struct Reachable(String, String);
+
+    #[derive(Relation, Debug, PartialEq)]
+    struct JobDuration {
+        job: String,
+        seconds: usize,
+        weight: f64,
+    }
+
+    #[test]
+    fn integration_test_relations() {
+        assert_eq!("edge", Edge::NAME);
+        assert_eq!("job_duration", JobDuration::NAME);
+        let edge = Edge {
+            from: "a".to_string(),
+            to: "b".to_string(),
+        };
+        assert_eq!(vec![TypedValue::from("a"), "b".into()], edge.into_fact());
+
+        let mut runtime = MicroRuntime::new(program! {
+            tc(?x, ?y) <- [edge(?x, ?y)],
+            tc(?x, ?z) <- [edge(?x, ?y), tc(?y, ?z)]
+        });
+        for (from, to) in [("a", "b"), ("b", "c")] {
+            runtime.insert_relation(Edge {
+                from: from.to_string(),
+                to: to.to_string(),
+            });
+        }
+        assert!(runtime.query_relation::<Reachable>().is_err());
+        runtime.poll();
+
+        let mut actual = runtime.query_relation::<Reachable>().unwrap();
+        actual.sort();
+        let expected: Vec<_> = [("a", "b"), ("a", "c"), ("b", "c")]
+            .into_iter()
+            .map(|(x, y)| Reachable(x.to_string(), y.to_string()))
+            .collect();
+        assert_eq!(expected, actual);
+
+        // Facts that don't fit the struct are reported rather than skipped
+        runtime.insert("job_duration", vec!["build".into(), 60usize.into(), 0.5f64.into()]);
+        runtime.insert("job_duration", vec!["test".into(), 1i64.into(), 1f64.into()]);
+        runtime.poll();
+        assert_eq!(
+            Err("column 1 of job_duration: expected an int, found 1".to_string()),
+            runtime.query_relation::<JobDuration>()
+        );
+        assert_eq!(
+            Ok(JobDuration {
+                job: "build".to_string(),
+                seconds: 60,
+                weight: 0.5,
+            }),
+            JobDuration::from_fact(vec!["build".into(), 60usize.into(), 0.5f64.into()])
+        );
+        assert_eq!(
+            Err("edge has 2 columns, found 1".to_string()),
+            Edge::from_fact(vec!["c".into()])
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn integration_test_json() {
+        register_custom::<Version>();
+        let values: Vec<TypedValue> = vec![
+            "a".into(),
+            1usize.into(),
+            true.into(),
+            (-1i64).into(),
+            1.5f64.into(),
+            f64::NAN.into(),
+            f64::NEG_INFINITY.into(),
+            vec![0u8, 255].into(),
+            vec![TypedValue::from("a"), 2usize.into()].into(),
+            version("1.2.0"),
+        ];
+        let json = serde_json::to_string(&values).unwrap();
+        assert_eq!(
+            [
+                r#"["a",1,true,{"i64":-1},{"float":1.5},{"float":"NaN"},{"float":"-inf"},"#,
+                r#"{"bytes":[0,255]},["a",2],{"custom":{"kind":"semver","bytes":[49,46,50,46,48]}}]"#,
+            ]
+            .concat(),
+            json
+        );
+        assert_eq!(values, serde_json::from_str::<Vec<TypedValue>>(&json).unwrap());
+        assert!(serde_json::from_str::<TypedValue>("-1").is_err());
+        assert!(serde_json::from_str::<TypedValue>("1.5").is_err());
+
+        // Functions are shipped by name and resolved again on arrival
+        let source = "p(?x, label(?x)) <- [e(?x, 1)]. q(?x) <- [e(?x, ?n), !p(?x, ?x)].";
+        let program = Program::from(parse_source(source).unwrap().rules);
+        let json = serde_json::to_string(&program).unwrap();
+        let shipped: Program = serde_json::from_str(&json).unwrap();
+        assert_eq!(program, shipped);
+        assert!(MicroRuntime::try_new(shipped.clone()).is_err());
+        let mut functions = FunctionRegistry::default();
+        functions.register("label", |args| TypedValue::from(format!("<{}>", args["x"])));
+        let mut runtime = MicroRuntime::new(functions.resolve(shipped).unwrap());
+
+        let query: Query = serde_json::from_str(r#"{"matchers": ["any"], "symbol": "p"}"#).unwrap();
+        runtime
+            .import_json(r#"{"e": [["a", 1], ["b", 2]], "unrelated": [[{"i64": -3}]]}"#)
+            .unwrap();
+        assert!(runtime.export_json().is_err());
+        runtime.poll();
+        assert_eq!(
+            vec![vec![TypedValue::from("a"), "<a>".into()]],
+            runtime.query(&query).unwrap().collect::<Vec<_>>()
+        );
+
+        let export = runtime.export_json().unwrap();
+        let mut imported = MicroRuntime::new(functions.resolve(program).unwrap());
+        imported.import_json(&export).unwrap();
+        imported.poll();
+        assert_eq!(export, imported.export_json().unwrap());
+        assert!(imported.import_json(r#"{"e": [["a", -1]]}"#).is_err());
+        // The valid facts before an invalid one are not inserted either
+        assert!(imported.import_json(r#"{"e": [["c", 1]], "p": [["c"]]}"#).is_err());
+        assert!(imported.safe());
+
+        // Conclusions drawn by negation are left out, so that they can be withdrawn once imported
+        let program = program! {
+            reachable(?y)   <- [e(?x, ?y)],
+            unreachable(?x) <- [node(?x), !reachable(?x)],
+        };
+        let mut runtime = MicroRuntime::new(program.clone());
+        runtime.insert("node", vec!["b".into()]);
+        runtime.insert("reachable", vec!["a".into()]);
+        runtime.poll();
+        let export = runtime.export_json().unwrap();
+        let relations: HashMap<String, Vec<AnonymousGroundAtom>> =
+            serde_json::from_str(&export).unwrap();
+        assert!(relations["unreachable"].is_empty());
+        let mut imported = MicroRuntime::new(program);
+        imported.import_json(&export).unwrap();
+        imported.poll();
+        assert!(imported.contains("unreachable", &vec!["b".into()]).unwrap());
+        assert!(imported.contains("reachable", &vec!["a".into()]).unwrap());
+        imported.insert("e", vec!["a".into(), "b".into()]);
+        imported.poll();
+        assert!(!imported.contains("unreachable", &vec!["b".into()]).unwrap());
+    }
 }
@@ -0,0 +1,107 @@
+use ahash::HashMap;
+use datalog_syntax::{AnonymousGroundAtom, TypedValue};
+use indexmap::IndexSet;
+
+pub type ValueId = u32;
+pub type EncodedGroundAtom = Vec<ValueId>;
+
+// Every value that enters the runtime is interned exactly once, and its position in the set is
+// its id. Facts are then stored, hashed and joined as plain integers.
+#[derive(Default)]
+pub struct Dictionary {
+    inner: IndexSet<TypedValue, ahash::RandomState>,
+    // How many function calls values returned by functions are nested in, see skolem_depth. Not
+    // persisted, hence values read from a snapshot start over.
+    skolem_depths: HashMap<ValueId, usize>,
+}
+
+impl Dictionary {
+    // Interned values count as given, hence as not returned by a function, see skolem_depth.
+    pub fn intern(&mut self, value: &TypedValue) -> ValueId {
+        if let Some(id) = self.get_id(value) {
+            self.forget_depth(id);
+            return id;
+        }
+
+        self.intern_owned(value.clone())
+    }
+    pub fn intern_owned(&mut self, value: TypedValue) -> ValueId {
+        let (id, _) = self.inner.insert_full(value);
+
+        id as ValueId
+    }
+    // Interns a value returned by a function called on values of `input_depth` at most. A value
+    // that was also reached in fewer calls keeps the smaller depth.
+    pub fn intern_invented(&mut self, value: TypedValue, input_depth: usize) -> ValueId {
+        let known = self.get_id(&value);
+        let id = self.intern_owned(value);
+        let depth = input_depth + 1;
+        if known.is_none() || self.skolem_depth(id) > depth {
+            self.skolem_depths.insert(id, depth);
+        }
+
+        id
+    }
+    fn forget_depth(&mut self, id: ValueId) {
+        if !self.skolem_depths.is_empty() {
+            self.skolem_depths.remove(&id);
+        }
+    }
+    // 0 for values that were not returned by a function
+    pub fn skolem_depth(&self, id: ValueId) -> usize {
+        self.skolem_depths.get(&id).copied().unwrap_or(0)
+    }
+    pub fn get_id(&self, value: &TypedValue) -> Option<ValueId> {
+        self.inner.get_index_of(value).map(|id| id as ValueId)
+    }
+    pub fn resolve(&self, id: ValueId) -> &TypedValue {
+        self.inner.get_index(id as usize).unwrap()
+    }
+    pub fn has_id(&self, id: ValueId) -> bool {
+        (id as usize) < self.inner.len()
+    }
+    // Values in id order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &TypedValue> {
+        self.inner.iter()
+    }
+    pub fn encode(&mut self, ground_atom: AnonymousGroundAtom) -> EncodedGroundAtom {
+        ground_atom
+            .into_iter()
+            .map(|value| {
+                let id = self.intern_owned(value);
+                self.forget_depth(id);
+                id
+            })
+            .collect()
+    }
+    // Encodes without interning, hence it fails if any of the values has never been seen.
+    pub fn try_encode(&self, ground_atom: &AnonymousGroundAtom) -> Option<EncodedGroundAtom> {
+        ground_atom.iter().map(|value| self.get_id(value)).collect()
+    }
+    pub fn decode(&self, encoded_ground_atom: &[ValueId]) -> AnonymousGroundAtom {
+        encoded_ground_atom
+            .iter()
+            .map(|id| self.resolve(*id).clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::dictionary::Dictionary;
+    use datalog_syntax::{AnonymousGroundAtom, TypedValue};
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let mut dictionary = Dictionary::default();
+
+        let fact: AnonymousGroundAtom = vec!["a".into(), 1usize.into(), true.into(), "a".into()];
+        let encoded_fact = dictionary.encode(fact.clone());
+
+        assert_eq!(Some(encoded_fact[2]), dictionary.get_id(&true.into()));
+        assert_eq!(encoded_fact[0], encoded_fact[3]);
+        assert_eq!(fact, dictionary.decode(&encoded_fact));
+        assert_eq!(Some(encoded_fact.clone()), dictionary.try_encode(&fact));
+        assert_eq!(None, dictionary.try_encode(&vec![TypedValue::from("b")]));
+    }
+}
@@ -1,10 +1,42 @@
-use ahash::{HashMap};
-use datalog_syntax::AnonymousGroundAtom;
+use ahash::HashMap;
+use crate::engine::dictionary::ValueId;
 
 #[derive(Clone, Hash, Eq, PartialEq)]
 pub enum EphemeralValue<'a> {
-    FactRef(&'a AnonymousGroundAtom),
-    JoinResult(Vec<&'a AnonymousGroundAtom>),
+    FactRef(&'a [ValueId]),
+    JoinResult(Vec<&'a [ValueId]>),
+    // Values computed during evaluation, e.g. by external relations, which no fact holds
+    Owned(Vec<ValueId>),
+}
+
+impl EphemeralValue<'_> {
+    // Join results are the concatenation of their facts, hence columns keep counting across them.
+    pub fn column(&self, column: usize) -> ValueId {
+        match self {
+            EphemeralValue::FactRef(fact) => fact[column],
+            EphemeralValue::Owned(values) => values[column],
+            EphemeralValue::JoinResult(product) => {
+                let mut offset = column;
+                for fact in product {
+                    if offset < fact.len() {
+                        return fact[offset];
+                    }
+                    offset -= fact.len();
+                }
+
+                unreachable!()
+            }
+        }
+    }
+    pub fn to_row(&self) -> Vec<ValueId> {
+        match self {
+            EphemeralValue::FactRef(fact) => fact.to_vec(),
+            EphemeralValue::Owned(values) => values.clone(),
+            EphemeralValue::JoinResult(product) => {
+                product.iter().copied().flatten().copied().collect()
+            }
+        }
+    }
 }
 
 #[derive(Default)]
@@ -12,9 +44,9 @@ pub struct EphemeralStorage<'a> {
     pub(crate) inner: HashMap<String, Vec<EphemeralValue<'a>>>,
 }
 
-impl<'b, 'a> EphemeralStorage<'a> {
+impl<'a> EphemeralStorage<'a> {
     pub fn get_relation(&self, relation_symbol: &str) -> &Vec<EphemeralValue<'a>> {
-        return self.inner.get(relation_symbol).unwrap();
+        self.inner.get(relation_symbol).unwrap()
     }
     pub fn borrow_all(
         &mut self,
@@ -22,9 +54,23 @@ impl<'b, 'a> EphemeralStorage<'a> {
         facts: impl Iterator<Item = EphemeralValue<'a>>,
     ) {
         if let Some(ephemeral_relation) = self.inner.get_mut(relation_symbol) {
-            ephemeral_relation.extend(facts.into_iter())
+            ephemeral_relation.extend(facts)
         } else {
             self.inner.insert(relation_symbol.to_string(), Vec::from_iter(facts));
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::ephemeral_storage::EphemeralValue;
+
+    #[test]
+    fn test_join_result_columns_count_across_facts() {
+        let product = EphemeralValue::JoinResult(vec![&[1, 2], &[3, 4, 5], &[6]]);
+
+        let columns: Vec<_> = (0..6).map(|column| product.column(column)).collect();
+        assert_eq!(vec![1, 2, 3, 4, 5, 6], columns);
+        assert_eq!(columns, product.to_row());
+    }
+}
@@ -0,0 +1,334 @@
+use crate::engine::datalog::MicroRuntime;
+use crate::engine::external::order_body;
+use datalog_syntax::{AnonymousGroundAtom, Atom, Matcher, Query, Rule, Term, TypedValue};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+// How a fact came to be: the rule that derived it out of its premises, or no rule at all for facts
+// that were inserted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Derivation {
+    pub relation: String,
+    pub fact: AnonymousGroundAtom,
+    pub rule: Option<Rule>,
+    pub premises: Vec<Derivation>,
+}
+
+// Formats a fact the way it is written in Datalog sources, e.g. `tc("a", "b")`.
+pub fn format_fact(relation: &str, fact: &AnonymousGroundAtom) -> String {
+    let values: Vec<_> = fact.iter().map(|value| format!("{:?}", value)).collect();
+
+    format!("{}({})", relation, values.join(", "))
+}
+
+impl Derivation {
+    fn fmt_indented(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        write!(f, "{}{}", "  ".repeat(depth), format_fact(&self.relation, &self.fact))?;
+        match &self.rule {
+            Some(rule) => writeln!(f, " by {}", rule)?,
+            None => writeln!(f)?,
+        }
+
+        self.premises
+            .iter()
+            .try_for_each(|premise| premise.fmt_indented(f, depth + 1))
+    }
+}
+
+impl Display for Derivation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+type Bindings = HashMap<String, TypedValue>;
+
+fn bind(bindings: &mut Bindings, terms: &[Term], fact: &[TypedValue]) -> Option<Vec<String>> {
+    let mut bound = vec![];
+    for (term, value) in terms.iter().zip(fact) {
+        let consistent = match term {
+            Term::Variable(name) => match bindings.get(name) {
+                Some(bound_value) => bound_value == value,
+                None => {
+                    bindings.insert(name.clone(), value.clone());
+                    bound.push(name.clone());
+                    true
+                }
+            },
+            Term::Constant(constant) => constant == value,
+            // Checked once every variable is bound
+            Term::Skolemizer(_) => true,
+        };
+
+        if !consistent {
+            bound.iter().for_each(|name| {
+                bindings.remove(name);
+            });
+            return None;
+        }
+    }
+
+    Some(bound)
+}
+
+fn matchers(atom: &Atom, bindings: &Bindings) -> Vec<Matcher> {
+    atom.terms
+        .iter()
+        .map(|term| match term {
+            Term::Variable(name) => match bindings.get(name) {
+                Some(value) => Matcher::Constant(value.clone()),
+                None => Matcher::Any,
+            },
+            Term::Constant(value) => Matcher::Constant(value.clone()),
+            Term::Skolemizer(_) => Matcher::Any,
+        })
+        .collect()
+}
+
+// Fails if a function does, in which case the atom can't have been derived with these bindings.
+fn ground(atom: &Atom, bindings: &Bindings) -> Result<AnonymousGroundAtom, String> {
+    atom.terms
+        .iter()
+        .map(|term| match term {
+            Term::Variable(name) => Ok(bindings[name].clone()),
+            Term::Constant(value) => Ok(value.clone()),
+            Term::Skolemizer(skolem_function) => {
+                let inputs = skolem_function
+                    .deps
+                    .iter()
+                    .map(|name| (name.as_str(), &bindings[name]))
+                    .collect();
+
+                skolem_function.call(inputs)
+            }
+        })
+        .collect()
+}
+
+struct Explainer<'a> {
+    runtime: &'a MicroRuntime,
+    // Facts being explained, which can't be used to explain themselves
+    path: Vec<(String, AnonymousGroundAtom)>,
+}
+
+impl Explainer<'_> {
+    // Facts of `atom` that match the bindings, whether stored or computed by an external relation.
+    fn candidates(
+        &self,
+        atom: &Atom,
+        bindings: &Bindings,
+    ) -> Result<Vec<AnonymousGroundAtom>, String> {
+        let matchers = matchers(atom, bindings);
+        if let Some(relation) = self.runtime.externals().get(&atom.symbol) {
+            let arguments: Vec<_> = matchers
+                .iter()
+                .map(|matcher| match matcher {
+                    Matcher::Constant(value) => Some(value),
+                    Matcher::Any => None,
+                })
+                .collect();
+
+            return relation
+                .matching(&arguments)
+                .map_err(|err| format!("{}: {}", atom.symbol, err));
+        }
+
+        let query = Query {
+            matchers,
+            symbol: &atom.symbol,
+        };
+
+        let facts = self.runtime.query(&query)?.collect();
+
+        Ok(facts)
+    }
+
+    fn holds(&self, atom: &Atom, bindings: &Bindings) -> Result<bool, String> {
+        let facts = self.candidates(atom, bindings)?;
+
+        Ok(facts
+            .iter()
+            .any(|fact| bind(&mut bindings.clone(), &atom.terms, fact).is_some()))
+    }
+
+    // Finds values for the variables of `positive_atoms[index..]`, backtracking until every premise
+    // they lead to can be explained as well.
+    fn search(
+        &mut self,
+        rule: &Rule,
+        fact: &AnonymousGroundAtom,
+        positive_atoms: &[&Atom],
+        bindings: &mut Bindings,
+    ) -> Result<Option<Vec<Derivation>>, String> {
+        let Some((atom, remaining_atoms)) = positive_atoms.split_first() else {
+            if ground(&rule.head, bindings).as_ref() != Ok(fact) {
+                return Ok(None);
+            }
+            for negated_atom in rule.body.iter().filter(|body_atom| body_atom.negated) {
+                if self.holds(negated_atom, bindings)? {
+                    return Ok(None);
+                }
+            }
+
+            let mut premises = vec![];
+            for body_atom in rule.body.iter().filter(|body_atom| !body_atom.negated) {
+                let premise = ground(body_atom, bindings)?;
+                // Tuples of external relations are premises of their own
+                if self.runtime.externals().contains(&body_atom.symbol) {
+                    premises.push(Derivation {
+                        relation: body_atom.symbol.clone(),
+                        fact: premise,
+                        rule: None,
+                        premises: vec![],
+                    });
+                    continue;
+                }
+                match self.derive(&body_atom.symbol, premise)? {
+                    Some(premise) => premises.push(premise),
+                    None => return Ok(None),
+                }
+            }
+
+            return Ok(Some(premises));
+        };
+
+        let mut candidates = self.candidates(atom, bindings)?;
+        // So that explanations do not depend on the order facts are stored in
+        candidates.sort();
+
+        for candidate in candidates {
+            let Some(bound) = bind(bindings, &atom.terms, &candidate) else {
+                continue;
+            };
+            let premises = self.search(rule, fact, remaining_atoms, bindings)?;
+            bound.iter().for_each(|name| {
+                bindings.remove(name);
+            });
+
+            if premises.is_some() {
+                return Ok(premises);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn derive(
+        &mut self,
+        relation: &str,
+        fact: AnonymousGroundAtom,
+    ) -> Result<Option<Derivation>, String> {
+        if !self.runtime.contains(relation, &fact)? {
+            return Ok(None);
+        }
+        let rules: Vec<_> = self
+            .runtime
+            .program()
+            .inner
+            .iter()
+            .filter(|rule| rule.head.symbol == relation)
+            .collect();
+        // Facts of relations that no rule derives can only have been inserted.
+        if rules.is_empty() {
+            return Ok(Some(Derivation {
+                relation: relation.to_string(),
+                fact,
+                rule: None,
+                premises: vec![],
+            }));
+        }
+        if self
+            .path
+            .iter()
+            .any(|(path_relation, path_fact)| path_relation == relation && *path_fact == fact)
+        {
+            return Ok(None);
+        }
+
+        self.path.push((relation.to_string(), fact.clone()));
+        let mut derivation = None;
+        for rule in rules {
+            let mut bindings = Bindings::new();
+            if bind(&mut bindings, &rule.head.terms, &fact).is_none() {
+                continue;
+            }
+            // In evaluation order, so that external atoms have their inputs bound
+            let body = order_body(rule, self.runtime.externals())?;
+            let positive_atoms: Vec<_> = body.iter().filter(|atom| !atom.negated).collect();
+
+            if let Some(premises) = self.search(rule, &fact, &positive_atoms, &mut bindings)? {
+                derivation = Some(Derivation {
+                    relation: relation.to_string(),
+                    fact: fact.clone(),
+                    rule: Some(rule.clone()),
+                    premises,
+                });
+                break;
+            }
+        }
+        self.path.pop();
+
+        Ok(derivation)
+    }
+}
+
+// Explains why `fact` holds, or returns None if it does not. Facts inserted straight into
+// intensional relations are told apart from derived ones only when no rule derives them.
+pub fn explain(
+    runtime: &MicroRuntime,
+    relation: &str,
+    fact: &AnonymousGroundAtom,
+) -> Result<Option<Derivation>, String> {
+    let mut explainer = Explainer {
+        runtime,
+        path: vec![],
+    };
+
+    match explainer.derive(relation, fact.clone())? {
+        Some(derivation) => Ok(Some(derivation)),
+        None if runtime.contains(relation, fact)? => Ok(Some(Derivation {
+            relation: relation.to_string(),
+            fact: fact.clone(),
+            rule: None,
+            premises: vec![],
+        })),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::engine::explanation::explain;
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+
+    #[test]
+    fn test_explain() {
+        let program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            tc(?x, ?z) <- [tc(?x, ?y), e(?y, ?z)],
+            sink(?x)   <- [tc(?y, ?x), !e(?x, ?z)],
+        };
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("e", vec!["a".into(), "b".into()]);
+        runtime.insert("e", vec!["b".into(), "c".into()]);
+        runtime.insert("e", vec!["c".into(), "a".into()]);
+        runtime.insert("e", vec!["c".into(), "d".into()]);
+        runtime.poll();
+
+        let expected = r#"sink("d") by sink(x) <- [tc(y, x), !e(x, z)]
+  tc("a", "d") by tc(x, z) <- [tc(x, y), e(y, z)]
+    tc("a", "c") by tc(x, z) <- [tc(x, y), e(y, z)]
+      tc("a", "b") by tc(x, y) <- [e(x, y)]
+        e("a", "b")
+      e("b", "c")
+    e("c", "d")
+"#;
+        let derivation = explain(&runtime, "sink", &vec!["d".into()]).unwrap().unwrap();
+        assert_eq!(expected, derivation.to_string());
+
+        assert_eq!(None, explain(&runtime, "sink", &vec!["a".into()]).unwrap());
+    }
+}
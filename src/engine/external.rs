@@ -0,0 +1,346 @@
+use datalog_syntax::{AnonymousGroundAtom, Atom, Rule, Term, TypedValue};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+// Given the arguments of an atom, None for those left unbound, returns the tuples it holds for.
+pub type ExternalCall =
+    Arc<dyn Fn(&[Option<&TypedValue>]) -> Result<Vec<AnonymousGroundAtom>, String> + Send + Sync>;
+
+// A relation computed by Rust code rather than stored, such as glob_match(?pattern, ?path). Modes
+// are the binding patterns it can be computed with: "bf" means that the first argument has to be
+// bound, and that the second one is generated.
+#[derive(Clone)]
+pub struct ExternalRelation {
+    modes: Vec<Vec<bool>>,
+    call: ExternalCall,
+}
+
+impl ExternalRelation {
+    pub fn arity(&self) -> usize {
+        self.modes[0].len()
+    }
+    // The mode binding the most arguments out of those that are bound.
+    fn mode(&self, bound: &[bool]) -> Option<&[bool]> {
+        self.modes
+            .iter()
+            .filter(|mode| mode.iter().zip(bound).all(|(needed, bound)| !needed || *bound))
+            .max_by_key(|mode| mode.iter().filter(|needed| **needed).count())
+            .map(Vec::as_slice)
+    }
+    // Tuples matching `arguments`. Only those the mode needs are given to the callback, the others
+    // are compared with what it returns.
+    pub fn matching(
+        &self,
+        arguments: &[Option<&TypedValue>],
+    ) -> Result<Vec<AnonymousGroundAtom>, String> {
+        let bound: Vec<_> = arguments.iter().map(Option::is_some).collect();
+        let mode = self
+            .mode(&bound)
+            .ok_or_else(|| "no mode fits the bound arguments".to_string())?;
+        let inputs: Vec<_> = arguments
+            .iter()
+            .zip(mode)
+            .map(|(argument, needed)| argument.filter(|_| *needed))
+            .collect();
+
+        let mut tuples = (self.call)(&inputs)?;
+        if let Some(tuple) = tuples.iter().find(|tuple| tuple.len() != arguments.len()) {
+            return Err(format!(
+                "returned {} values instead of {}",
+                tuple.len(),
+                arguments.len()
+            ));
+        }
+        tuples.retain(|tuple| {
+            tuple
+                .iter()
+                .zip(arguments)
+                .all(|(value, argument)| argument.is_none_or(|argument| argument == value))
+        });
+
+        Ok(tuples)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ExternalRelations {
+    relations: HashMap<String, ExternalRelation>,
+}
+
+impl ExternalRelations {
+    // Modes are written with b for bound and f for free arguments, e.g. ["bf", "fb"] for a relation
+    // that can be computed either way. Registering a name again replaces its relation.
+    pub fn register(
+        &mut self,
+        name: &str,
+        modes: &[&str],
+        call: impl Fn(&[Option<&TypedValue>]) -> Result<Vec<AnonymousGroundAtom>, String>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Result<(), String> {
+        let modes = modes
+            .iter()
+            .map(|mode| {
+                mode.chars()
+                    .map(|binding| match binding {
+                        'b' => Ok(true),
+                        'f' => Ok(false),
+                        _ => Err(format!("invalid mode {} of {}", mode, name)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match modes.first() {
+            None => return Err(format!("{} needs a mode", name)),
+            Some(first) if modes.iter().any(|mode| mode.len() != first.len()) => {
+                return Err(format!("modes of {} differ in arity", name))
+            }
+            _ => {}
+        }
+
+        self.relations.insert(
+            name.to_string(),
+            ExternalRelation {
+                modes,
+                call: Arc::new(call),
+            },
+        );
+
+        Ok(())
+    }
+    // A relation holding for the tuples `predicate` accepts, whose arguments all have to be bound.
+    pub fn register_filter(
+        &mut self,
+        name: &str,
+        arity: usize,
+        predicate: impl Fn(&[&TypedValue]) -> Result<bool, String> + Send + Sync + 'static,
+    ) {
+        let mode = "b".repeat(arity);
+        self.register(name, &[&mode], move |arguments| {
+            let arguments: Vec<_> = arguments.iter().map(|argument| argument.unwrap()).collect();
+
+            Ok(match predicate(&arguments)? {
+                true => vec![arguments.into_iter().cloned().collect()],
+                false => vec![],
+            })
+        })
+        .unwrap()
+    }
+    pub fn get(&self, name: &str) -> Option<&ExternalRelation> {
+        self.relations.get(name)
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.relations.contains_key(name)
+    }
+}
+
+fn variables(atom: &Atom) -> impl Iterator<Item = &String> {
+    atom.terms.iter().filter_map(|term| match term {
+        Term::Variable(name) => Some(name),
+        _ => None,
+    })
+}
+
+// Whether some mode of `relation` has its arguments bound, constants being bound from the start.
+fn computable(relation: &ExternalRelation, atom: &Atom, bound: &HashSet<&String>) -> bool {
+    let bound: Vec<_> = atom
+        .terms
+        .iter()
+        .map(|term| match term {
+            Term::Variable(name) => bound.contains(name),
+            _ => true,
+        })
+        .collect();
+
+    relation.mode(&bound).is_some()
+}
+
+// The body of `rule` in evaluation order: positive atoms as they come, each external one as soon
+// as one of its modes can be computed, and then negated atoms. Fails if an external atom never can
+// be, or if the rule misuses an external relation.
+pub(crate) fn order_body(rule: &Rule, externals: &ExternalRelations) -> Result<Vec<Atom>, String> {
+    if externals.contains(&rule.head.symbol) {
+        return Err(format!(
+            "external relation {} can't be derived, see rule {}",
+            rule.head.symbol, rule
+        ));
+    }
+    for atom in rule.body.iter() {
+        if let Some(relation) = externals.get(&atom.symbol) {
+            if relation.arity() != atom.terms.len() {
+                return Err(format!(
+                    "{} takes {} arguments, not {} as in rule {}",
+                    atom.symbol,
+                    relation.arity(),
+                    atom.terms.len(),
+                    rule
+                ));
+            }
+        }
+    }
+
+    let (mut pending, stored): (Vec<_>, Vec<_>) = rule
+        .body
+        .iter()
+        .filter(|atom| !atom.negated)
+        .partition(|atom| externals.contains(&atom.symbol));
+    if stored.is_empty() && !pending.is_empty() {
+        return Err(format!("rule {} has no positive atom besides external ones", rule));
+    }
+
+    let mut ordered = vec![];
+    let mut bound = HashSet::new();
+    for atom in stored {
+        ordered.push(atom.clone());
+        bound.extend(variables(atom));
+
+        while let Some(index) = pending
+            .iter()
+            .position(|atom| computable(&externals.relations[&atom.symbol], atom, &bound))
+        {
+            let atom = pending.remove(index);
+            ordered.push(atom.clone());
+            bound.extend(variables(atom));
+        }
+    }
+    for atom in rule.body.iter().filter(|atom| atom.negated) {
+        if let Some(relation) = externals.get(&atom.symbol) {
+            if !computable(relation, atom, &bound) {
+                pending.push(atom);
+            }
+        }
+        ordered.push(atom.clone());
+    }
+
+    match pending.first() {
+        Some(atom) => Err(format!(
+            "no mode of {} has its arguments bound in rule {}",
+            atom.symbol, rule
+        )),
+        None => Ok(ordered),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::engine::explanation::explain;
+    use crate::engine::external::ExternalRelations;
+    use crate::engine::fact_storage::StorageLayout;
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+
+    fn externals() -> ExternalRelations {
+        let mut externals = ExternalRelations::default();
+        externals.register_filter("ends_with", 2, |arguments| match arguments {
+            [TypedValue::Str(text), TypedValue::Str(suffix)] => Ok(text.ends_with(suffix.as_str())),
+            _ => Err("expected strings".to_string()),
+        });
+        // Every directory a path is in, e.g. "src" and "src/engine" for "src/engine/mod.rs"
+        externals
+            .register("directory", &["bf"], |arguments| {
+                let Some(TypedValue::Str(path)) = arguments[0] else {
+                    return Err("expected a string".to_string());
+                };
+
+                Ok(path
+                    .match_indices('/')
+                    .map(|(index, _)| vec![path.as_str().into(), path[..index].into()])
+                    .collect())
+            })
+            .unwrap();
+
+        externals
+    }
+
+    #[test]
+    fn test_external_relations() {
+        let program = program! {
+            rust(?p)        <- [ends_with(?p, ".rs"), changed(?p)],
+            documented(?d)  <- [changed(?p), ends_with(?p, ".md"), directory(?p, ?d)],
+            touched(?d)     <- [changed(?p), directory(?p, ?d), !ends_with(?p, ".md")],
+            owned(?d, ?o)   <- [touched(?d), owner(?d, ?o)]
+        };
+        let mut runtime =
+            MicroRuntime::try_with_externals(program, StorageLayout::default(), externals())
+                .unwrap();
+        runtime.insert("changed", vec!["src/engine/mod.rs".into()]);
+        runtime.insert("changed", vec!["docs/guide/intro.md".into()]);
+        runtime.insert("owner", vec!["src".into(), "core".into()]);
+        runtime.poll();
+        let query = |runtime: &MicroRuntime, relation: &str| {
+            let mut facts: Vec<_> = runtime
+                .query(&Query {
+                    matchers: vec![Matcher::Any],
+                    symbol: relation,
+                })
+                .unwrap()
+                .map(|fact| fact[0].to_string())
+                .collect();
+            facts.sort();
+            facts
+        };
+        assert_eq!(vec!["src/engine/mod.rs"], query(&runtime, "rust"));
+        assert_eq!(vec!["docs", "docs/guide"], query(&runtime, "documented"));
+        assert_eq!(vec!["src", "src/engine"], query(&runtime, "touched"));
+        assert_eq!(
+            vec![vec![TypedValue::from("src"), "core".into()]],
+            runtime.query(&build_query!(owned(_, _))).unwrap().collect::<Vec<_>>()
+        );
+
+        runtime.insert("changed", vec!["build.rs".into()]);
+        runtime.insert("changed", vec!["tests/cli.rs".into()]);
+        runtime.poll();
+        assert_eq!(vec!["build.rs", "src/engine/mod.rs", "tests/cli.rs"], query(&runtime, "rust"));
+        assert_eq!(vec!["src", "src/engine", "tests"], query(&runtime, "touched"));
+
+        let derivation = explain(&runtime, "touched", &vec!["tests".into()]).unwrap().unwrap();
+        assert_eq!(
+            "touched(\"tests\") by touched(d) <- [changed(p), directory(p, d), !ends_with(p, .md)]\n  changed(\"tests/cli.rs\")\n  directory(\"tests/cli.rs\", \"tests\")\n",
+            derivation.to_string()
+        );
+
+        // Like failing functions, failing external relations hold for nothing
+        let program = program! { rust(?p) <- [changed(?p), ends_with(?p, ".rs")] };
+        let mut runtime =
+            MicroRuntime::try_with_externals(program, StorageLayout::default(), externals())
+                .unwrap();
+        runtime.insert("changed", vec!["lib.rs".into()]);
+        runtime.insert("changed", vec![1usize.into()]);
+        assert_eq!(Err("ends_with: expected strings".to_string()), runtime.try_poll());
+        assert_eq!(1, runtime.query(&build_query!(rust(_))).unwrap().count());
+    }
+
+    #[test]
+    fn test_invalid_external_atoms() {
+        let invalid_programs = [
+            (
+                program! { p(?x) <- [changed(?p), directory(?x, ?p)] },
+                "no mode of directory has its arguments bound in rule p(x) <- [changed(p), directory(x, p)]",
+            ),
+            (
+                program! { directory(?p, ?p) <- [changed(?p)] },
+                "external relation directory can't be derived, see rule directory(p, p) <- [changed(p)]",
+            ),
+            (
+                program! { p(?p) <- [changed(?p), ends_with(?p)] },
+                "ends_with takes 2 arguments, not 1 as in rule p(p) <- [changed(p), ends_with(p)]",
+            ),
+            (
+                program! { p(?p) <- [ends_with(?p, ".rs")] },
+                "rule p(p) <- [ends_with(p, .rs)] has no positive atom besides external ones",
+            ),
+        ];
+
+        for (program, expected_error) in invalid_programs {
+            let runtime =
+                MicroRuntime::try_with_externals(program, StorageLayout::default(), externals());
+            assert_eq!(Some(expected_error.to_string()), runtime.err());
+        }
+
+        let mut externals = ExternalRelations::default();
+        assert!(externals.register("r", &["bx"], |_| Ok(vec![])).is_err());
+        assert!(externals.register("r", &["bf", "b"], |_| Ok(vec![])).is_err());
+    }
+}
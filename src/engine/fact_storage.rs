@@ -0,0 +1,249 @@
+use crate::engine::dictionary::{EncodedGroundAtom, ValueId};
+use hashbrown::HashTable;
+use indexmap::IndexSet;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageLayout {
+    // One heap allocation per fact, deduplicated by an IndexSet.
+    #[default]
+    Hashed,
+    // All facts of a relation share one flat buffer of ids, laid out row after row.
+    RowMajor,
+}
+
+#[derive(Clone)]
+pub enum FactStorage {
+    Hashed(IndexSet<EncodedGroundAtom, ahash::RandomState>),
+    RowMajor(RowMajorFactStorage),
+}
+
+impl Default for FactStorage {
+    fn default() -> Self {
+        FactStorage::new(StorageLayout::default())
+    }
+}
+
+impl FactStorage {
+    pub fn new(layout: StorageLayout) -> Self {
+        match layout {
+            StorageLayout::Hashed => FactStorage::Hashed(Default::default()),
+            StorageLayout::RowMajor => FactStorage::RowMajor(Default::default()),
+        }
+    }
+    pub fn layout(&self) -> StorageLayout {
+        match self {
+            FactStorage::Hashed(_) => StorageLayout::Hashed,
+            FactStorage::RowMajor(_) => StorageLayout::RowMajor,
+        }
+    }
+    // Panics if the fact does not have the arity of the facts already stored, see try_insert.
+    pub fn insert(&mut self, fact: EncodedGroundAtom) -> bool {
+        self.try_insert(fact).unwrap()
+    }
+    // Both layouts refuse a fact whose arity differs from that of the facts already stored.
+    pub fn try_insert(&mut self, fact: EncodedGroundAtom) -> Result<bool, String> {
+        match self {
+            FactStorage::Hashed(inner) => {
+                check_arity(inner.first().map(|first| first.len()), fact.len())?;
+
+                Ok(inner.insert(fact))
+            }
+            FactStorage::RowMajor(inner) => inner.insert(&fact),
+        }
+    }
+    // The arity of the facts stored, if there are any.
+    pub fn arity(&self) -> Option<usize> {
+        match self {
+            FactStorage::Hashed(inner) => inner.first().map(|first| first.len()),
+            FactStorage::RowMajor(inner) => (inner.len > 0).then_some(inner.arity),
+        }
+    }
+    pub fn contains(&self, fact: &[ValueId]) -> bool {
+        match self {
+            FactStorage::Hashed(inner) => inner.contains(fact),
+            FactStorage::RowMajor(inner) => inner.contains(fact),
+        }
+    }
+    pub fn iter(&self) -> FactIterator<'_> {
+        match self {
+            FactStorage::Hashed(inner) => FactIterator::Hashed(inner.iter()),
+            FactStorage::RowMajor(inner) => FactIterator::RowMajor(inner, 0),
+        }
+    }
+    pub fn drain(&mut self) -> Vec<EncodedGroundAtom> {
+        match self {
+            FactStorage::Hashed(inner) => inner.drain(..).collect(),
+            FactStorage::RowMajor(inner) => {
+                let facts = inner.iter().map(|fact| fact.to_vec()).collect();
+                inner.clear();
+
+                facts
+            }
+        }
+    }
+    pub fn clear(&mut self) {
+        match self {
+            FactStorage::Hashed(inner) => inner.clear(),
+            FactStorage::RowMajor(inner) => inner.clear(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            FactStorage::Hashed(inner) => inner.len(),
+            FactStorage::RowMajor(inner) => inner.len,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Extend<EncodedGroundAtom> for FactStorage {
+    fn extend<T: IntoIterator<Item = EncodedGroundAtom>>(&mut self, facts: T) {
+        facts.into_iter().for_each(|fact| {
+            self.insert(fact);
+        });
+    }
+}
+
+pub enum FactIterator<'a> {
+    Hashed(indexmap::set::Iter<'a, EncodedGroundAtom>),
+    RowMajor(&'a RowMajorFactStorage, usize),
+}
+
+impl<'a> Iterator for FactIterator<'a> {
+    type Item = &'a [ValueId];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            FactIterator::Hashed(inner) => inner.next().map(|fact| fact.as_slice()),
+            FactIterator::RowMajor(storage, row) => {
+                if *row == storage.len {
+                    return None;
+                }
+                *row += 1;
+
+                Some(storage.row(*row - 1))
+            }
+        }
+    }
+}
+
+pub fn check_arity(arity: Option<usize>, fact_arity: usize) -> Result<(), String> {
+    match arity {
+        Some(arity) if arity != fact_arity => Err(format!(
+            "relation of arity {} cannot hold a fact of arity {}",
+            arity, fact_arity
+        )),
+        _ => Ok(()),
+    }
+}
+
+// The hash table only holds row numbers, the ids themselves live once in `rows`.
+#[derive(Clone, Default)]
+pub struct RowMajorFactStorage {
+    arity: usize,
+    len: usize,
+    rows: Vec<ValueId>,
+    index: HashTable<usize>,
+    hasher: ahash::RandomState,
+}
+
+impl RowMajorFactStorage {
+    fn row(&self, row: usize) -> &[ValueId] {
+        &self.rows[row * self.arity..(row + 1) * self.arity]
+    }
+    fn find(&self, hash: u64, fact: &[ValueId]) -> Option<&usize> {
+        self.index.find(hash, |row| self.row(*row) == fact)
+    }
+    pub fn insert(&mut self, fact: &[ValueId]) -> Result<bool, String> {
+        if self.len == 0 {
+            self.arity = fact.len();
+        }
+        check_arity(Some(self.arity), fact.len())?;
+
+        let hash = self.hasher.hash_one(fact);
+        if self.find(hash, fact).is_some() {
+            return Ok(false);
+        }
+
+        self.rows.extend_from_slice(fact);
+        let (rows, arity, hasher) = (&self.rows, self.arity, &self.hasher);
+        self.index.insert_unique(hash, self.len, |row| {
+            hasher.hash_one(&rows[row * arity..(row + 1) * arity])
+        });
+        self.len += 1;
+
+        Ok(true)
+    }
+    pub fn contains(&self, fact: &[ValueId]) -> bool {
+        if self.len == 0 || fact.len() != self.arity {
+            return false;
+        }
+
+        self.find(self.hasher.hash_one(fact), fact).is_some()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &[ValueId]> {
+        (0..self.len).map(|row| self.row(row))
+    }
+    pub fn clear(&mut self) {
+        self.rows.clear();
+        self.index.clear();
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::fact_storage::{FactStorage, StorageLayout};
+
+    #[test]
+    fn test_layouts_agree() {
+        for layout in [StorageLayout::Hashed, StorageLayout::RowMajor] {
+            let mut fact_storage = FactStorage::new(layout);
+
+            assert!(fact_storage.insert(vec![1, 2]));
+            assert!(fact_storage.insert(vec![2, 3]));
+            assert!(!fact_storage.insert(vec![1, 2]));
+
+            assert_eq!(2, fact_storage.len());
+            assert!(fact_storage.contains(&[2, 3]));
+            assert!(!fact_storage.contains(&[3, 2]));
+            assert_eq!(
+                vec![&[1, 2][..], &[2, 3][..]],
+                fact_storage.iter().collect::<Vec<_>>()
+            );
+
+            assert_eq!(vec![vec![1, 2], vec![2, 3]], fact_storage.drain());
+            assert!(fact_storage.is_empty());
+            assert!(!fact_storage.contains(&[1, 2]));
+        }
+    }
+
+    #[test]
+    fn test_row_major_nullary_relation() {
+        let mut fact_storage = FactStorage::new(StorageLayout::RowMajor);
+
+        assert!(!fact_storage.contains(&[]));
+        assert!(fact_storage.insert(vec![]));
+        assert!(!fact_storage.insert(vec![]));
+        assert!(fact_storage.contains(&[]));
+        assert_eq!(1, fact_storage.iter().count());
+    }
+
+    #[test]
+    fn test_layouts_refuse_other_arities() {
+        for layout in [StorageLayout::Hashed, StorageLayout::RowMajor] {
+            let mut fact_storage = FactStorage::new(layout);
+
+            assert_eq!(None, fact_storage.arity());
+            assert_eq!(Ok(true), fact_storage.try_insert(vec![1, 2]));
+            assert_eq!(
+                Err("relation of arity 2 cannot hold a fact of arity 3".to_string()),
+                fact_storage.try_insert(vec![1, 2, 3])
+            );
+            assert_eq!(Some(2), fact_storage.arity());
+            assert_eq!(1, fact_storage.len());
+        }
+    }
+}
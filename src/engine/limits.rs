@@ -0,0 +1,74 @@
+use crate::engine::storage::RelationStorage;
+use crate::helpers::helpers::DELTA_PREFIX;
+use std::time::{Duration, Instant};
+
+// Bounds on a single poll, since rules calling functions in their heads can invent values forever,
+// e.g. `nat(succ(?x)) <- [nat(?x)]`. None means unbounded, which is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    // Rounds of semi-naive evaluation, over every stratum
+    pub max_iterations: Option<usize>,
+    pub max_facts_per_relation: Option<usize>,
+    // How many function calls a value may be nested in, e.g. 2 for succ(succ(0))
+    pub max_skolem_depth: Option<usize>,
+    // Only checked in between rounds, hence a single round may overrun it
+    pub time_budget: Option<Duration>,
+}
+
+impl Limits {
+    pub fn is_unbounded(&self) -> bool {
+        *self == Limits::default()
+    }
+}
+
+// Keeps track of a poll against its limits.
+pub(crate) struct Budget {
+    limits: Limits,
+    started: Instant,
+    iterations: usize,
+}
+
+impl Budget {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            started: Instant::now(),
+            iterations: 0,
+        }
+    }
+    // To be called after every round, with the delta relations it added to.
+    pub fn round<'a>(
+        &mut self,
+        relation_storage: &RelationStorage,
+        relation_symbols: impl Iterator<Item = &'a String>,
+    ) -> Result<(), String> {
+        self.iterations += 1;
+        if let Some(max_iterations) = self.limits.max_iterations {
+            if self.iterations > max_iterations {
+                return Err(format!("evaluation exceeded {} iterations", max_iterations));
+            }
+        }
+        if let Some(max_facts) = self.limits.max_facts_per_relation {
+            for relation_symbol in relation_symbols {
+                let relation_symbol = relation_symbol.strip_prefix(DELTA_PREFIX).unwrap();
+                let facts = relation_storage.get_relation(relation_symbol).len();
+                if facts > max_facts {
+                    return Err(format!(
+                        "relation {} exceeded {} facts",
+                        relation_symbol, max_facts
+                    ));
+                }
+            }
+        }
+        if let Some(time_budget) = self.limits.time_budget {
+            if self.started.elapsed() > time_budget {
+                return Err(format!("evaluation exceeded its time budget of {:?}", time_budget));
+            }
+        }
+
+        Ok(())
+    }
+    pub fn max_skolem_depth(&self) -> Option<usize> {
+        self.limits.max_skolem_depth
+    }
+}
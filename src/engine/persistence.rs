@@ -0,0 +1,630 @@
+use crate::engine::dictionary::{Dictionary, EncodedGroundAtom, ValueId};
+use crate::engine::external::ExternalRelations;
+use crate::engine::fact_storage::{FactStorage, StorageLayout};
+use crate::engine::storage::RelationStorage;
+use crate::helpers::helpers::DELTA_PREFIX;
+use datalog_syntax::{decode_custom, Program, Term, TypedValue};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"ACTLOGIC";
+// Bump whenever the layout below changes, old snapshots are then refused instead of misread.
+pub const FORMAT_VERSION: u32 = 2;
+
+pub struct Snapshot {
+    pub layout: StorageLayout,
+    pub fingerprint: u64,
+    pub dictionary: Dictionary,
+    // The flag tells whether the relation was intensional in the program that wrote the snapshot.
+    pub relations: Vec<(String, bool, Vec<EncodedGroundAtom>)>,
+    // Facts inserted straight into intensional relations, see MicroRuntime::prepare_reevaluation.
+    pub intensional_insertions: Vec<(String, Vec<EncodedGroundAtom>)>,
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+// FNV-1a, because the fingerprint has to be stable across processes and platforms.
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn canonical_term(term: &Term) -> String {
+    match term {
+        Term::Variable(name) => format!("?{}", name),
+        Term::Constant(value) => format!("{:?}", value),
+        Term::Skolemizer(skolem_function) => format!(
+            "#{}({})",
+            skolem_function.name,
+            skolem_function.deps.join(", ")
+        ),
+    }
+}
+
+// External atoms are marked with a &, so that a snapshot is not taken for one of the same program
+// with other external relations, whose stored facts would then stand for computed ones.
+pub fn fingerprint(program: &Program, externals: &ExternalRelations) -> u64 {
+    let mut rules: Vec<_> = program
+        .inner
+        .iter()
+        .map(|rule| {
+            std::iter::once(&rule.head)
+                .chain(rule.body.iter())
+                .map(|atom| {
+                    let terms: Vec<_> = atom.terms.iter().map(canonical_term).collect();
+
+                    let sign = if atom.negated { "!" } else { "" };
+                    let external = if externals.contains(&atom.symbol) { "&" } else { "" };
+
+                    format!("{}{}{}({})", sign, external, atom.symbol, terms.join(", "))
+                })
+                .collect::<Vec<_>>()
+                .join(" <- ")
+        })
+        .collect();
+    rules.sort();
+
+    rules
+        .iter()
+        .fold(FNV_OFFSET_BASIS, |hash, rule| fnv1a(hash, rule.as_bytes()))
+}
+
+pub(crate) fn write_u32(writer: &mut impl Write, value: u32) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_u64(writer: &mut impl Write, value: u64) -> std::io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> std::io::Result<()> {
+    write_u64(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+pub(crate) fn write_value(writer: &mut impl Write, value: &TypedValue) -> std::io::Result<()> {
+    match value {
+        TypedValue::Str(inner) => {
+            writer.write_all(&[0])?;
+            write_bytes(writer, inner.as_bytes())
+        }
+        TypedValue::Int(inner) => {
+            writer.write_all(&[1])?;
+            write_u64(writer, *inner as u64)
+        }
+        TypedValue::Bool(inner) => writer.write_all(&[2, *inner as u8]),
+        TypedValue::I64(inner) => {
+            writer.write_all(&[3])?;
+            write_u64(writer, *inner as u64)
+        }
+        TypedValue::Float(inner) => {
+            writer.write_all(&[4])?;
+            write_u64(writer, inner.get().to_bits())
+        }
+        TypedValue::Bytes(inner) => {
+            writer.write_all(&[5])?;
+            write_bytes(writer, inner)
+        }
+        TypedValue::List(items) => {
+            writer.write_all(&[6])?;
+            write_u64(writer, items.len() as u64)?;
+            items.iter().try_for_each(|item| write_value(writer, item))
+        }
+        TypedValue::Custom(inner) => {
+            writer.write_all(&[7])?;
+            write_bytes(writer, inner.kind().as_bytes())?;
+            write_bytes(writer, &inner.encode())
+        }
+    }
+}
+
+pub(crate) fn read_u8(reader: &mut impl Read) -> std::io::Result<u8> {
+    let mut buffer = [0; 1];
+    reader.read_exact(&mut buffer)?;
+
+    Ok(buffer[0])
+}
+
+pub(crate) fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+
+    Ok(u32::from_le_bytes(buffer))
+}
+
+pub(crate) fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+
+    Ok(u64::from_le_bytes(buffer))
+}
+
+pub(crate) fn read_bytes(reader: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let len = read_u64(reader)?;
+    let mut buffer = vec![];
+    reader.take(len).read_to_end(&mut buffer)?;
+
+    if buffer.len() as u64 != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(buffer)
+}
+
+pub(crate) fn read_string(reader: &mut impl Read) -> Result<String, String> {
+    let bytes = read_bytes(reader).map_err(|err| err.to_string())?;
+
+    String::from_utf8(bytes).map_err(|err| err.to_string())
+}
+
+pub(crate) fn read_value(reader: &mut impl Read) -> Result<TypedValue, String> {
+    let io = |err: std::io::Error| err.to_string();
+
+    match read_u8(reader).map_err(io)? {
+        0 => Ok(TypedValue::Str(read_string(reader)?)),
+        1 => Ok(TypedValue::Int(read_u64(reader).map_err(io)? as usize)),
+        2 => Ok(TypedValue::Bool(read_u8(reader).map_err(io)? != 0)),
+        3 => Ok(TypedValue::I64(read_u64(reader).map_err(io)? as i64)),
+        4 => Ok(f64::from_bits(read_u64(reader).map_err(io)?).into()),
+        5 => Ok(TypedValue::Bytes(read_bytes(reader).map_err(io)?)),
+        6 => {
+            // Pushed one by one, so that a corrupted length fails on the missing items rather
+            // than on allocating them
+            let len = read_u64(reader).map_err(io)?;
+            let mut items = vec![];
+            for _ in 0..len {
+                items.push(read_value(reader)?);
+            }
+            Ok(TypedValue::List(items))
+        }
+        // Custom kinds have to be registered before reading them
+        7 => {
+            let kind = read_string(reader)?;
+            let bytes = read_bytes(reader).map_err(io)?;
+            decode_custom(&kind, &bytes).map(TypedValue::Custom)
+        }
+        tag => Err(format!("unknown value tag {}", tag)),
+    }
+}
+
+fn write_facts(writer: &mut impl Write, facts: &FactStorage) -> std::io::Result<()> {
+    write_u64(writer, facts.len() as u64)?;
+    for fact in facts.iter() {
+        write_u32(writer, fact.len() as u32)?;
+        for id in fact {
+            write_u32(writer, *id)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_facts(
+    reader: &mut impl Read,
+    dictionary: &Dictionary,
+    symbol: &str,
+) -> Result<Vec<EncodedGroundAtom>, String> {
+    let io = |err: std::io::Error| err.to_string();
+    let fact_count = read_u64(reader).map_err(io)?;
+    let mut facts = vec![];
+    for _ in 0..fact_count {
+        let arity = read_u32(reader).map_err(io)?;
+        let fact = (0..arity)
+            .map(|_| read_u32(reader))
+            .collect::<std::io::Result<Vec<ValueId>>>()
+            .map_err(io)?;
+
+        if fact.iter().any(|id| !dictionary.has_id(*id)) {
+            return Err(format!("dangling value id in relation {}", symbol));
+        }
+
+        facts.push(fact);
+    }
+
+    Ok(facts)
+}
+
+fn write_snapshot(
+    writer: &mut impl Write,
+    layout: StorageLayout,
+    fingerprint: u64,
+    dictionary: &Dictionary,
+    processed: &RelationStorage,
+    intensional_relations: &HashSet<String>,
+    intensional_insertions: &RelationStorage,
+) -> std::io::Result<()> {
+    writer.write_all(MAGIC)?;
+    write_u32(writer, FORMAT_VERSION)?;
+    writer.write_all(&[layout as u8])?;
+    write_u64(writer, fingerprint)?;
+
+    write_u64(writer, dictionary.iter().len() as u64)?;
+    for value in dictionary.iter() {
+        write_value(writer, value)?;
+    }
+
+    // Delta relations are always empty in between polls.
+    let relations: Vec<_> = processed
+        .inner
+        .iter()
+        .filter(|(symbol, _)| !symbol.starts_with(DELTA_PREFIX))
+        .collect();
+    write_u64(writer, relations.len() as u64)?;
+    for (symbol, facts) in relations {
+        write_bytes(writer, symbol.as_bytes())?;
+        writer.write_all(&[intensional_relations.contains(symbol) as u8])?;
+        write_facts(writer, facts)?;
+    }
+
+    write_u64(writer, intensional_insertions.inner.len() as u64)?;
+    for (symbol, facts) in &intensional_insertions.inner {
+        write_bytes(writer, symbol.as_bytes())?;
+        write_facts(writer, facts)?;
+    }
+
+    writer.flush()
+}
+
+pub fn save(
+    path: &Path,
+    layout: StorageLayout,
+    fingerprint: u64,
+    dictionary: &Dictionary,
+    processed: &RelationStorage,
+    intensional_relations: &HashSet<String>,
+    intensional_insertions: &RelationStorage,
+) -> Result<(), String> {
+    // Written next to the target and then renamed, so a crash never leaves a torn snapshot.
+    let temporary_path = path.with_extension("tmp");
+    let file = File::create(&temporary_path).map_err(|err| err.to_string())?;
+    let mut writer = BufWriter::new(file);
+
+    write_snapshot(
+        &mut writer,
+        layout,
+        fingerprint,
+        dictionary,
+        processed,
+        intensional_relations,
+        intensional_insertions,
+    )
+    .and_then(|_| writer.get_ref().sync_all())
+    .and_then(|_| std::fs::rename(&temporary_path, path))
+    .map_err(|err| err.to_string())
+}
+
+pub fn load(path: &Path) -> Result<Snapshot, String> {
+    let io = |err: std::io::Error| err.to_string();
+    let file = File::open(path).map_err(io)?;
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic).map_err(io)?;
+    if &magic != MAGIC {
+        return Err(format!("{} is not a snapshot", path.display()));
+    }
+
+    let version = read_u32(&mut reader).map_err(io)?;
+    if version != FORMAT_VERSION {
+        return Err(format!(
+            "unsupported snapshot version {}, expected {}",
+            version, FORMAT_VERSION
+        ));
+    }
+
+    let layout = match read_u8(&mut reader).map_err(io)? {
+        0 => StorageLayout::Hashed,
+        1 => StorageLayout::RowMajor,
+        tag => return Err(format!("unknown storage layout {}", tag)),
+    };
+    let fingerprint = read_u64(&mut reader).map_err(io)?;
+
+    let mut dictionary = Dictionary::default();
+    let value_count = read_u64(&mut reader).map_err(io)?;
+    for _ in 0..value_count {
+        dictionary.intern_owned(read_value(&mut reader)?);
+    }
+
+    let relation_count = read_u64(&mut reader).map_err(io)?;
+    let mut relations = vec![];
+    for _ in 0..relation_count {
+        let symbol = read_string(&mut reader)?;
+        let intensional = read_u8(&mut reader).map_err(io)? != 0;
+        let facts = read_facts(&mut reader, &dictionary, &symbol)?;
+
+        relations.push((symbol, intensional, facts));
+    }
+
+    let insertion_count = read_u64(&mut reader).map_err(io)?;
+    let mut intensional_insertions = vec![];
+    for _ in 0..insertion_count {
+        let symbol = read_string(&mut reader)?;
+        let facts = read_facts(&mut reader, &dictionary, &symbol)?;
+
+        intensional_insertions.push((symbol, facts));
+    }
+
+    Ok(Snapshot {
+        layout,
+        fingerprint,
+        dictionary,
+        relations,
+        intensional_insertions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::engine::external::ExternalRelations;
+    use crate::engine::fact_storage::StorageLayout;
+    use crate::engine::persistence::{fingerprint, read_value, write_value};
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    // A git ref, e.g. refs/heads/main
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct GitRef(String);
+
+    impl std::fmt::Display for GitRef {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Custom for GitRef {
+        const KIND: &'static str = "git-ref";
+
+        fn encode(&self) -> Vec<u8> {
+            self.0.as_bytes().to_vec()
+        }
+        fn decode(bytes: &[u8]) -> Result<Self, String> {
+            match std::str::from_utf8(bytes) {
+                Ok(name) if name.starts_with("refs/") => Ok(GitRef(name.to_string())),
+                _ => Err("not a ref".to_string()),
+            }
+        }
+    }
+
+    // Never registered
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct Unknown;
+
+    impl std::fmt::Display for Unknown {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "unknown")
+        }
+    }
+
+    impl Custom for Unknown {
+        const KIND: &'static str = "unknown";
+
+        fn encode(&self) -> Vec<u8> {
+            vec![]
+        }
+        fn decode(_: &[u8]) -> Result<Self, String> {
+            Ok(Unknown)
+        }
+    }
+
+    fn snapshot_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("act-logically-{}-{}.snapshot", name, std::process::id()))
+    }
+
+    fn tc_runtime(layout: StorageLayout) -> MicroRuntime {
+        let tc_program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z)],
+        };
+
+        let mut runtime = MicroRuntime::with_layout(tc_program, layout);
+        runtime.insert("e", vec!["a".into(), "b".into()]);
+        runtime.insert("e", vec!["b".into(), "c".into()]);
+        runtime.poll();
+
+        runtime
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_rule_order() {
+        let left = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z)],
+        };
+        let right = program! {
+            tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z)],
+            tc(?x, ?y) <- [e(?x, ?y)],
+        };
+        let other = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+        };
+
+        let none = ExternalRelations::default();
+        assert_eq!(fingerprint(&left, &none), fingerprint(&right, &none));
+        assert_ne!(fingerprint(&left, &none), fingerprint(&other, &none));
+
+        let concat = program! {
+            path(?p) <- [dir(?d), concat(?d, "/a", ?p)],
+        };
+        assert_ne!(
+            fingerprint(&concat, &none),
+            fingerprint(&concat, &ExternalRelations::builtins())
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_warm() {
+        let path = snapshot_path("warm");
+        let mut runtime = tc_runtime(StorageLayout::RowMajor);
+        runtime.save(&path).unwrap();
+
+        let tc_program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z)],
+        };
+        let mut loaded = MicroRuntime::load(&path, tc_program).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let all = build_query!(tc(_, _));
+        let expected: HashSet<_> = runtime.query(&all).unwrap().collect();
+        let actual: HashSet<_> = loaded.query(&all).unwrap().collect();
+        assert_eq!(expected, actual);
+
+        // The loaded runtime keeps on being incremental
+        loaded.insert("e", vec!["c".into(), "d".into()]);
+        loaded.poll();
+        assert!(loaded.contains("tc", &vec!["a".into(), "d".into()]).unwrap());
+    }
+
+    #[test]
+    fn test_load_rematerialises_changed_program() {
+        let path = snapshot_path("changed");
+        tc_runtime(StorageLayout::Hashed).save(&path).unwrap();
+
+        let reversed_program = program! {
+            rev(?y, ?x) <- [e(?x, ?y)],
+        };
+        let loaded = MicroRuntime::load(&path, reversed_program).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let actual: HashSet<_> = loaded.query(&build_query!(rev(_, _))).unwrap().collect();
+        let expected: HashSet<AnonymousGroundAtom> = vec![
+            vec!["b".into(), "a".into()],
+            vec!["c".into(), "b".into()],
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(expected, actual);
+        // Facts derived by the old program do not survive
+        assert!(!loaded.contains("tc", &vec!["a".into(), "b".into()]).unwrap());
+    }
+
+    #[test]
+    fn test_save_and_load_with_externals() {
+        let path = snapshot_path("externals");
+        let program = program! {
+            path(?p) <- [dir(?d), concat(?d, "/a", ?p)],
+        };
+        let mut runtime = MicroRuntime::try_with_externals(
+            program.clone(),
+            StorageLayout::Hashed,
+            ExternalRelations::builtins(),
+        )
+        .unwrap();
+        runtime.insert("dir", vec!["src".into()]);
+        runtime.poll();
+        runtime.save(&path).unwrap();
+
+        let mut loaded =
+            MicroRuntime::load_with_externals(&path, program.clone(), ExternalRelations::builtins())
+                .unwrap();
+        assert!(loaded.contains("path", &vec!["src/a".into()]).unwrap());
+        loaded.insert("dir", vec!["doc".into()]);
+        loaded.poll();
+        assert!(loaded.contains("path", &vec!["doc/a".into()]).unwrap());
+
+        // Without them, concat is a relation of its own, and nothing derived is kept
+        let loaded = MicroRuntime::load(&path, program).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.contains("dir", &vec!["src".into()]).unwrap());
+        assert!(!loaded.contains("path", &vec!["src/a".into()]).unwrap());
+    }
+
+    #[test]
+    fn test_save_and_load_intensional_insertions() {
+        let path = snapshot_path("intensional");
+        let negated_program = program! {
+            reachable(?y)   <- [reachable(?x), e(?x, ?y)],
+            unreachable(?x) <- [node(?x), !reachable(?x)],
+        };
+        let mut runtime = MicroRuntime::new(negated_program.clone());
+        runtime.insert("reachable", vec!["a".into()]);
+        runtime.insert("node", vec!["b".into()]);
+        runtime.poll();
+        runtime.save(&path).unwrap();
+
+        // Programs with negation are evaluated again from the facts inserted into reachable
+        let mut loaded = MicroRuntime::load(&path, negated_program).unwrap();
+        loaded.insert("e", vec!["a".into(), "b".into()]);
+        loaded.poll();
+        assert!(loaded.contains("reachable", &vec!["a".into()]).unwrap());
+        assert!(!loaded.contains("unreachable", &vec!["b".into()]).unwrap());
+
+        let changed_program = program! {
+            reachable(?y)   <- [reachable(?x), e(?x, ?y)],
+            isolated(?x)    <- [node(?x), !reachable(?x)],
+        };
+        let loaded = MicroRuntime::load(&path, changed_program).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.contains("reachable", &vec!["a".into()]).unwrap());
+        assert!(loaded.contains("isolated", &vec!["b".into()]).unwrap());
+    }
+
+    #[test]
+    fn test_value_round_trip() {
+        let values: Vec<TypedValue> = vec![
+            "a".into(),
+            3usize.into(),
+            false.into(),
+            (-3i64).into(),
+            f64::NAN.into(),
+            (-2.5).into(),
+            b"\x00\xff".as_slice().into(),
+            TypedValue::List(vec![1usize.into(), TypedValue::List(vec!["b".into()])]),
+        ];
+        let mut buffer = vec![];
+        values
+            .iter()
+            .for_each(|value| write_value(&mut buffer, value).unwrap());
+
+        let mut reader = buffer.as_slice();
+        let read: Vec<_> = values.iter().map(|_| read_value(&mut reader).unwrap()).collect();
+        assert_eq!(values, read);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_custom_value_round_trip() {
+        let value = TypedValue::custom(GitRef("refs/heads/main".to_string()));
+        let mut buffer = vec![];
+        write_value(&mut buffer, &value).unwrap();
+
+        register_custom::<GitRef>();
+        let read = read_value(&mut buffer.as_slice()).unwrap();
+        assert_eq!(value, read);
+        let TypedValue::Custom(custom) = read else {
+            panic!("expected a custom value");
+        };
+        assert_eq!("refs/heads/main", custom.downcast_ref::<GitRef>().unwrap().0);
+
+        let mut invalid = vec![];
+        write_value(&mut invalid, &TypedValue::custom(GitRef("main".to_string()))).unwrap();
+        assert_eq!(
+            Err("invalid git-ref value: not a ref".to_string()),
+            read_value(&mut invalid.as_slice())
+        );
+
+        let mut unknown = vec![];
+        write_value(&mut unknown, &TypedValue::custom(Unknown)).unwrap();
+        assert_eq!(
+            Err("unregistered custom value kind unknown".to_string()),
+            read_value(&mut unknown.as_slice())
+        );
+    }
+
+    #[test]
+    fn test_load_refuses_garbage() {
+        let path = snapshot_path("garbage");
+        std::fs::write(&path, b"definitely not a snapshot").unwrap();
+
+        let outcome = MicroRuntime::load(&path, program! { tc(?x, ?y) <- [e(?x, ?y)] });
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(outcome.is_err());
+    }
+}
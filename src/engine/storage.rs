@@ -1,81 +1,84 @@
-use ahash::HashMap;
-use crate::helpers::helpers::{DELTA_PREFIX};
-use datalog_syntax::{AnonymousGroundAtom, Program};
-use indexmap::IndexSet;
+use ahash::{HashMap, HashSet};
+use crate::engine::dictionary::{Dictionary, EncodedGroundAtom};
+use crate::engine::external::ExternalRelations;
+use crate::engine::fact_storage::{FactStorage, StorageLayout};
+use crate::helpers::helpers::DELTA_PREFIX;
+use datalog_syntax::Rule;
 use crate::evaluation::spj_processor::RuleEvaluator;
 
-pub type FactStorage = IndexSet<AnonymousGroundAtom, ahash::RandomState>;
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct RelationStorage {
     pub(crate) inner: HashMap<String, FactStorage>,
+    pub(crate) layout: StorageLayout,
 }
 
 impl RelationStorage {
+    pub fn new(layout: StorageLayout) -> Self {
+        Self {
+            inner: Default::default(),
+            layout,
+        }
+    }
+    pub fn register(&mut self, relation_symbol: &str) {
+        if !self.inner.contains_key(relation_symbol) {
+            self.inner
+                .insert(relation_symbol.to_string(), FactStorage::new(self.layout));
+        }
+    }
     pub fn get_relation(&self, relation_symbol: &str) -> &FactStorage {
-        return self.inner.get(relation_symbol).unwrap()
+        self.inner.get(relation_symbol).unwrap()
     }
-    pub fn drain_relation(&mut self, relation_symbol: &str) -> Vec<AnonymousGroundAtom> {
+    pub fn drain_relation(&mut self, relation_symbol: &str) -> Vec<EncodedGroundAtom> {
         let rel = self.inner.get_mut(relation_symbol).unwrap();
 
-        return rel.drain(..).collect();
+        rel.drain()
     }
     pub fn drain_all_relations(
         &mut self,
-    ) -> impl Iterator<Item = (String, Vec<AnonymousGroundAtom>)> + '_ {
-        let relations_to_be_drained: Vec<_> =
-            self.inner.iter().map(|(symbol, _)| symbol.clone()).collect();
+    ) -> impl Iterator<Item = (String, Vec<EncodedGroundAtom>)> + '_ {
+        let relations_to_be_drained: Vec<_> = self.inner.keys().cloned().collect();
 
         relations_to_be_drained.into_iter().map(|relation_symbol| {
             (
                 relation_symbol.clone(),
-                self.inner
-                    .get_mut(&relation_symbol)
-                    .unwrap()
-                    .drain(..)
-                    .collect::<Vec<_>>(),
+                self.inner.get_mut(&relation_symbol).unwrap().drain(),
             )
         })
     }
     pub fn drain_deltas(&mut self) {
         let delta_relation_symbols: Vec<_> = self
             .inner
-            .iter()
-            .map(|(symbol, _)| symbol.clone())
+            .keys()
             .filter(|relation_symbol| relation_symbol.starts_with(DELTA_PREFIX))
+            .cloned()
             .collect();
 
         delta_relation_symbols
             .into_iter()
             .for_each(|relation_symbol| {
-                if relation_symbol.starts_with(DELTA_PREFIX) {
-                    let delta_facts: Vec<_> = self.drain_relation(&relation_symbol);
-
-                    let current_non_delta_relation = self
-                        .inner
-                        .get_mut(relation_symbol.strip_prefix(DELTA_PREFIX).unwrap())
-                        .unwrap();
-
-                    delta_facts.into_iter().for_each(|fact| {
-                        current_non_delta_relation.insert(fact);
-                    });
-                }
+                let delta_facts: Vec<_> = self.drain_relation(&relation_symbol);
+
+                let current_non_delta_relation = self
+                    .inner
+                    .get_mut(relation_symbol.strip_prefix(DELTA_PREFIX).unwrap())
+                    .unwrap();
+
+                delta_facts.into_iter().for_each(|fact| {
+                    current_non_delta_relation.insert(fact);
+                });
             });
     }
     pub fn insert_registered(
         &mut self,
         relation_symbol: &str,
-        registrations: impl Iterator<Item = AnonymousGroundAtom>,
+        registrations: impl Iterator<Item = EncodedGroundAtom>,
     ) {
-        let mut hashes = vec![];
-
-        registrations.into_iter().for_each(|fact| {
-            hashes.push(fact);
-        });
+        let hashes: Vec<_> = registrations.collect();
 
         if let Some(relation) = self.inner.get_mut(relation_symbol) {
             relation.extend(hashes)
         } else {
-            let mut fresh_fact_storage = FactStorage::default();
+            let mut fresh_fact_storage = FactStorage::new(self.layout);
             fresh_fact_storage.extend(hashes);
 
             self.inner
@@ -86,24 +89,35 @@ impl RelationStorage {
     pub fn insert_all(
         &mut self,
         relation_symbol: &str,
-        facts: impl Iterator<Item = AnonymousGroundAtom>,
+        facts: impl Iterator<Item = EncodedGroundAtom>,
     ) {
         if let Some(relation) = self.inner.get_mut(relation_symbol) {
-            relation.extend(facts.into_iter())
+            relation.extend(facts)
         } else {
-            let mut fresh_fact_storage = FactStorage::default();
-            fresh_fact_storage.extend(facts.into_iter());
+            let mut fresh_fact_storage = FactStorage::new(self.layout);
+            fresh_fact_storage.extend(facts);
 
             self.inner
                 .insert(relation_symbol.to_string(), fresh_fact_storage);
         }
     }
-    pub fn insert(&mut self, relation_symbol: &str, ground_atom: AnonymousGroundAtom) -> bool {
+    pub fn replace_relation(
+        &mut self,
+        relation_symbol: &str,
+        facts: impl Iterator<Item = EncodedGroundAtom>,
+    ) {
+        if let Some(relation) = self.inner.get_mut(relation_symbol) {
+            relation.clear();
+        }
+
+        self.insert_all(relation_symbol, facts)
+    }
+    pub fn insert(&mut self, relation_symbol: &str, ground_atom: EncodedGroundAtom) -> bool {
         if let Some(relation) = self.inner.get_mut(relation_symbol) {
             return relation.insert(ground_atom);
         }
 
-        let mut fresh_fact_storage = FactStorage::default();
+        let mut fresh_fact_storage = FactStorage::new(self.layout);
         fresh_fact_storage.insert(ground_atom);
 
         self.inner
@@ -111,7 +125,11 @@ impl RelationStorage {
 
         true
     }
-    pub fn contains(&self, relation_symbol: &str, ground_atom: &AnonymousGroundAtom) -> bool {
+    // The arity of the facts stored in the relation, if there are any.
+    pub fn arity(&self, relation_symbol: &str) -> Option<usize> {
+        self.inner.get(relation_symbol).and_then(FactStorage::arity)
+    }
+    pub fn contains(&self, relation_symbol: &str, ground_atom: &EncodedGroundAtom) -> bool {
         if let Some(relation) = self.inner.get(relation_symbol) {
             return relation.contains(ground_atom);
         }
@@ -119,86 +137,77 @@ impl RelationStorage {
         false
     }
 
-    // Nonrecursive materialisation can be done sequentially in one pass.
-    pub fn materialize_nonrecursive_delta_program(&mut self, nonrecursive_program: &Program) {
-        for (idx, rule) in nonrecursive_program.inner.iter().enumerate() {
-            let evaluator = RuleEvaluator::new(self, rule);
-
-            let evaluation = evaluator.step();
-
-            let delta_relation_symbol = rule.head.symbol.clone();
-
-            let current_delta_relation = self.get_relation(&delta_relation_symbol);
-
-            let diff: FactStorage = evaluation
-                .into_iter()
-                .filter(|fact| !current_delta_relation.contains(fact))
-                .collect();
-
-            if idx == 0 {
-                (*self.inner.get_mut(&delta_relation_symbol).unwrap()) = diff.clone();
-            } else {
-                self.insert_all(&delta_relation_symbol, diff.clone().into_iter());
-            }
-
-            let relation_symbol = delta_relation_symbol.clone();
-            relation_symbol.strip_prefix(DELTA_PREFIX).unwrap();
-            self.insert_all(
-                delta_relation_symbol.strip_prefix(DELTA_PREFIX).unwrap(),
-                diff.into_iter(),
-            );
-        }
-    }
-    pub fn materialize_recursive_delta_program(&mut self, recursive_program: &Program) {
-        let evaluation_setup: Vec<_> = recursive_program
-            .inner
+    // Evaluates every rule once. Whatever they derive that is not known yet replaces the contents
+    // of the delta relations in `delta_relation_symbols`, is added to the full relations, and is
+    // returned per delta relation. Failures of skolem functions are added to `errors`, unless they
+    // exceed `max_skolem_depth`, in which case nothing is changed.
+    pub fn materialize_delta_rules(
+        &mut self,
+        dictionary: &mut Dictionary,
+        rules: &[&Rule],
+        delta_relation_symbols: &HashSet<String>,
+        externals: &ExternalRelations,
+        max_skolem_depth: Option<usize>,
+        errors: &mut Vec<String>,
+    ) -> Result<Vec<(String, Vec<EncodedGroundAtom>)>, String> {
+        let evaluation = rules
             .iter()
-            .map(|rule| (&rule.head.symbol, RuleEvaluator::new(self, rule)))
-            .collect();
-
-        let evaluation = evaluation_setup
-            .into_iter()
-            .map(|(delta_relation_symbol, rule)| {
-                let out = rule.step().collect::<Vec<_>>();
-                (delta_relation_symbol, out)
+            .map(|rule| {
+                let out = RuleEvaluator::new(self, rule, externals).step(
+                    dictionary,
+                    max_skolem_depth,
+                    errors,
+                )?;
+                Ok((&rule.head.symbol, out))
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, String>>()?;
 
-        evaluation.into_iter().enumerate().for_each(
-            |(idx, (delta_relation_symbol, current_delta_evaluation))| {
-                let curr = self.get_relation(delta_relation_symbol);
+        let mut diffs: HashMap<&String, FactStorage> = Default::default();
+        evaluation
+            .into_iter()
+            .for_each(|(delta_relation_symbol, current_delta_evaluation)| {
+                let curr = self.get_relation(delta_relation_symbol.strip_prefix(DELTA_PREFIX).unwrap());
+
+                diffs
+                    .entry(delta_relation_symbol)
+                    .or_insert_with(|| FactStorage::new(self.layout))
+                    .extend(
+                        current_delta_evaluation
+                            .into_iter()
+                            .filter(|fact| !curr.contains(fact)),
+                    );
+            });
 
-                let diff: FactStorage = current_delta_evaluation
-                    .into_iter()
-                    .filter(|fact| !curr.contains(fact))
-                    .collect();
+        delta_relation_symbols.iter().for_each(|delta_relation_symbol| {
+            self.inner.get_mut(delta_relation_symbol).unwrap().clear();
+        });
 
-                if idx == 0 {
-                    (*self.inner.get_mut(delta_relation_symbol).unwrap()) = diff.clone();
-                } else {
-                    self.insert_all(delta_relation_symbol, diff.clone().into_iter());
-                }
+        Ok(diffs
+            .into_iter()
+            .map(|(delta_relation_symbol, mut diff)| {
+                let diff = diff.drain();
 
-                let relation_symbol = delta_relation_symbol.clone();
-                relation_symbol.strip_prefix(DELTA_PREFIX).unwrap();
+                self.insert_all(delta_relation_symbol, diff.clone().into_iter());
                 self.insert_all(
                     delta_relation_symbol.strip_prefix(DELTA_PREFIX).unwrap(),
-                    diff.into_iter(),
+                    diff.clone().into_iter(),
                 );
-            },
-        );
+
+                (delta_relation_symbol.clone(), diff)
+            })
+            .collect())
     }
 
     pub fn len(&self) -> usize {
-        return self
+        self
             .inner
             .iter()
             .filter(|(symbol, _facts)| !symbol.starts_with(DELTA_PREFIX))
             .map(|(_symbol, facts)| facts.len())
-            .sum();
+            .sum()
     }
 
     pub fn is_empty(&self) -> bool {
-        return self.len() == 0;
+        self.len() == 0
     }
 }
@@ -0,0 +1,280 @@
+use crate::engine::persistence::{
+    fnv1a, read_string, read_u32, read_u64, read_u8, read_value, write_bytes, write_u32,
+    write_u64, write_value, FNV_OFFSET_BASIS,
+};
+use datalog_syntax::AnonymousGroundAtom;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"ACTLGWAL";
+pub const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: u64 = 12;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogRecord {
+    Insertion(String, AnonymousGroundAtom),
+}
+
+// Every record is framed as [payload length][payload][checksum], so that a record torn by a crash
+// is detected on replay and cut off, instead of being misread.
+pub struct WriteAheadLog {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+fn encode_record(record: &LogRecord) -> Vec<u8> {
+    let mut payload = vec![];
+    match record {
+        LogRecord::Insertion(relation_symbol, ground_atom) => {
+            payload.push(0);
+            write_bytes(&mut payload, relation_symbol.as_bytes()).unwrap();
+            write_u32(&mut payload, ground_atom.len() as u32).unwrap();
+            ground_atom
+                .iter()
+                .for_each(|value| write_value(&mut payload, value).unwrap());
+        }
+    }
+
+    payload
+}
+
+fn decode_record(payload: &[u8]) -> Result<LogRecord, String> {
+    let io = |err: std::io::Error| err.to_string();
+    let mut reader = Cursor::new(payload);
+
+    match read_u8(&mut reader).map_err(io)? {
+        0 => {
+            let relation_symbol = read_string(&mut reader)?;
+            let arity = read_u32(&mut reader).map_err(io)?;
+            let ground_atom = (0..arity)
+                .map(|_| read_value(&mut reader))
+                .collect::<Result<_, _>>()?;
+
+            Ok(LogRecord::Insertion(relation_symbol, ground_atom))
+        }
+        tag => Err(format!("unknown log record tag {}", tag)),
+    }
+}
+
+// Reads records until the end of the log or the first torn one, returning them along with the
+// offset right after the last intact record.
+fn read_records(reader: &mut impl Read) -> (Vec<LogRecord>, u64) {
+    let mut records = vec![];
+    let mut offset = HEADER_LEN;
+
+    while let Ok(len) = read_u32(reader) {
+        let mut payload = vec![];
+        let torn = reader.take(len as u64).read_to_end(&mut payload).is_err();
+        if torn || payload.len() != len as usize {
+            break;
+        }
+        let Ok(checksum) = read_u64(reader) else {
+            break;
+        };
+        if checksum != fnv1a(FNV_OFFSET_BASIS, &payload) {
+            break;
+        }
+        let Ok(record) = decode_record(&payload) else {
+            break;
+        };
+
+        records.push(record);
+        offset += 4 + len as u64 + 8;
+    }
+
+    (records, offset)
+}
+
+impl WriteAheadLog {
+    // Opens the log at `path`, creating it if needed, and returns the records that have to be
+    // replayed.
+    pub fn open(path: impl AsRef<Path>) -> Result<(Self, Vec<LogRecord>), String> {
+        let io = |err: std::io::Error| err.to_string();
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(io)?;
+
+        let records = if file.metadata().map_err(io)?.len() == 0 {
+            file.write_all(MAGIC).map_err(io)?;
+            write_u32(&mut file, FORMAT_VERSION).map_err(io)?;
+            file.sync_all().map_err(io)?;
+
+            vec![]
+        } else {
+            let mut contents = vec![];
+            file.read_to_end(&mut contents).map_err(io)?;
+            let mut reader = Cursor::new(contents);
+
+            let mut magic = [0; 8];
+            reader.read_exact(&mut magic).map_err(io)?;
+            if &magic != MAGIC {
+                return Err(format!("{} is not a write-ahead log", path.display()));
+            }
+            let version = read_u32(&mut reader).map_err(io)?;
+            if version != FORMAT_VERSION {
+                return Err(format!(
+                    "unsupported write-ahead log version {}, expected {}",
+                    version, FORMAT_VERSION
+                ));
+            }
+
+            let (records, valid_len) = read_records(&mut reader);
+            // Whatever follows the last intact record is the leftover of a crash.
+            file.set_len(valid_len).map_err(io)?;
+
+            records
+        };
+
+        file.seek(SeekFrom::End(0)).map_err(io)?;
+
+        Ok((
+            Self {
+                path,
+                writer: BufWriter::new(file),
+            },
+            records,
+        ))
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn append(&mut self, record: &LogRecord) -> Result<(), String> {
+        let payload = encode_record(record);
+
+        write_u32(&mut self.writer, payload.len() as u32)
+            .and_then(|_| self.writer.write_all(&payload))
+            .and_then(|_| write_u64(&mut self.writer, fnv1a(FNV_OFFSET_BASIS, &payload)))
+            .and_then(|_| self.writer.flush())
+            .map_err(|err| err.to_string())
+    }
+    // Flushing hands records over to the OS, syncing makes them survive a power loss as well.
+    pub fn sync(&mut self) -> Result<(), String> {
+        self.writer
+            .flush()
+            .and_then(|_| self.writer.get_ref().sync_data())
+            .map_err(|err| err.to_string())
+    }
+    // Called once a snapshot holds everything that the log did.
+    pub fn compact(&mut self) -> Result<(), String> {
+        let io = |err: std::io::Error| err.to_string();
+
+        self.writer.flush().map_err(io)?;
+        let file = self.writer.get_mut();
+        file.set_len(HEADER_LEN).map_err(io)?;
+        file.seek(SeekFrom::End(0)).map_err(io)?;
+        file.sync_all().map_err(io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::engine::write_ahead_log::{LogRecord, WriteAheadLog};
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("act-logically-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_replay_skips_torn_tail() {
+        let path = temporary_path("torn.wal");
+        let _ = std::fs::remove_file(&path);
+
+        let first = LogRecord::Insertion("e".to_string(), vec!["a".into(), 1usize.into()]);
+        let second = LogRecord::Insertion("e".to_string(), vec!["b".into(), true.into()]);
+        {
+            let (mut log, records) = WriteAheadLog::open(&path).unwrap();
+            assert!(records.is_empty());
+            log.append(&first).unwrap();
+            log.append(&second).unwrap();
+        }
+        // Simulates a crash in the middle of an append
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[42, 0, 0, 0, 1, 2])
+            .unwrap();
+
+        let (mut log, records) = WriteAheadLog::open(&path).unwrap();
+        assert_eq!(vec![first.clone(), second.clone()], records);
+
+        // The torn tail is gone, hence new records are readable again
+        log.append(&first).unwrap();
+        drop(log);
+        let (_, records) = WriteAheadLog::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vec![first.clone(), second, first], records);
+    }
+
+    #[test]
+    fn test_runtime_recovers_from_log_and_compacts_on_save() {
+        let log_path = temporary_path("runtime.wal");
+        let snapshot_path = temporary_path("runtime.snapshot");
+        let _ = std::fs::remove_file(&log_path);
+        let tc_program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z)],
+        };
+
+        {
+            let mut runtime = MicroRuntime::new(tc_program.clone());
+            runtime.attach_log(&log_path).unwrap();
+            runtime.insert("e", vec!["a".into(), "b".into()]);
+            runtime.poll();
+            runtime.save(&snapshot_path).unwrap();
+            // Only this one is in the log after the snapshot
+            runtime.insert("e", vec!["b".into(), "c".into()]);
+        }
+
+        let (_, records) = WriteAheadLog::open(&log_path).unwrap();
+        assert_eq!(1, records.len());
+
+        let mut recovered = MicroRuntime::load(&snapshot_path, tc_program).unwrap();
+        recovered.attach_log(&log_path).unwrap();
+        recovered.poll();
+        std::fs::remove_file(&log_path).unwrap();
+        std::fs::remove_file(&snapshot_path).unwrap();
+
+        assert!(recovered.contains("tc", &vec!["a".into(), "c".into()]).unwrap());
+    }
+
+    #[test]
+    fn test_replay_refuses_other_arities() {
+        let path = temporary_path("arities.wal");
+        let _ = std::fs::remove_file(&path);
+        {
+            let (mut log, _) = WriteAheadLog::open(&path).unwrap();
+            log.append(&LogRecord::Insertion("e".to_string(), vec!["a".into(), "b".into()]))
+                .unwrap();
+            log.append(&LogRecord::Insertion("e".to_string(), vec!["c".into()])).unwrap();
+        }
+
+        // Unknown to the program, e takes the arity of its first record
+        let mut runtime = MicroRuntime::new(program! { tc(?x) <- [node(?x)] });
+        assert_eq!(
+            Err("e: relation of arity 2 cannot hold a fact of arity 1".to_string()),
+            runtime.attach_log(&path)
+        );
+        // Written for a program where e has another arity
+        let mut runtime = MicroRuntime::new(program! { tc(?x, ?y, ?z) <- [e(?x, ?y, ?z)] });
+        assert_eq!(
+            Err("e: relation of arity 3 cannot hold a fact of arity 2".to_string()),
+            runtime.attach_log(&path)
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        runtime.try_poll().unwrap();
+        assert!(runtime.query(&build_query!(e(_, _, _))).unwrap().next().is_none());
+    }
+}
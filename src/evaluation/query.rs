@@ -1,14 +1,32 @@
-use datalog_syntax::{AnonymousGroundAtom, Matcher, Query};
+use crate::engine::dictionary::{Dictionary, ValueId};
+use datalog_syntax::{Matcher, Query};
 
-pub fn pattern_match(query: &Query, fact: &AnonymousGroundAtom) -> bool {
-    return fact.iter().enumerate().all(|(index, term)| {
-        if let Some(matcher) = query.matchers.get(index) {
+pub enum EncodedMatcher {
+    Any,
+    Constant(ValueId),
+}
+
+// A constant that has never been interned cannot match any stored fact, hence None.
+pub fn encode_query(query: &Query, dictionary: &Dictionary) -> Option<Vec<EncodedMatcher>> {
+    query
+        .matchers
+        .iter()
+        .map(|matcher| match matcher {
+            Matcher::Any => Some(EncodedMatcher::Any),
+            Matcher::Constant(value) => dictionary.get_id(value).map(EncodedMatcher::Constant),
+        })
+        .collect()
+}
+
+pub fn pattern_match(matchers: &[EncodedMatcher], fact: &[ValueId]) -> bool {
+    fact.iter().enumerate().all(|(index, term)| {
+        if let Some(matcher) = matchers.get(index) {
             return match (matcher, term) {
-                (Matcher::Any, _) => true,
-                (Matcher::Constant(target), term) => target == term,
+                (EncodedMatcher::Any, _) => true,
+                (EncodedMatcher::Constant(target), term) => target == term,
             };
         }
 
         true
-    });
+    })
 }
@@ -1,24 +1,85 @@
+use crate::engine::dictionary::{Dictionary, EncodedGroundAtom};
+use crate::engine::external::ExternalRelations;
+use crate::engine::limits::Budget;
 use crate::engine::storage::RelationStorage;
-use datalog_syntax::Program;
+use ahash::{HashMap, HashSet};
+use datalog_syntax::{Program, Rule};
 
+// Strata are evaluated one after the other, each up to its fixpoint. Once a stratum is done its
+// delta relations hold everything it derived, which is what the following strata start from.
+// Fails as soon as the budget is exceeded, leaving the relations half evaluated.
 pub fn semi_naive_evaluation(
     relation_storage: &mut RelationStorage,
-    nonrecursive_delta_program: &Program,
-    recursive_delta_program: &Program,
-) {
-    relation_storage.materialize_nonrecursive_delta_program(&nonrecursive_delta_program);
+    dictionary: &mut Dictionary,
+    delta_strata: &[Program],
+    externals: &ExternalRelations,
+    budget: &mut Budget,
+    errors: &mut Vec<String>,
+) -> Result<(), String> {
+    for delta_program in delta_strata {
+        let delta_relation_symbols: HashSet<String> = delta_program
+            .inner
+            .iter()
+            .map(|rule| rule.head.symbol.clone())
+            .collect();
 
-    loop {
-        let previous_non_delta_fact_count = relation_storage.len();
+        // Facts inserted straight into the relations of this stratum are new as well.
+        let mut stratum_deltas: HashMap<String, Vec<EncodedGroundAtom>> = delta_relation_symbols
+            .iter()
+            .map(|delta_relation_symbol| {
+                let inserted_facts = relation_storage
+                    .get_relation(delta_relation_symbol)
+                    .iter()
+                    .map(|fact| fact.to_vec())
+                    .collect();
 
-        relation_storage.materialize_recursive_delta_program(&recursive_delta_program);
+                (delta_relation_symbol.clone(), inserted_facts)
+            })
+            .collect();
 
-        let new_non_delta_fact_count = relation_storage.len();
+        let mut rules: Vec<&Rule> = delta_program.inner.iter().collect();
+        loop {
+            let diffs = relation_storage.materialize_delta_rules(
+                dictionary,
+                &rules,
+                &delta_relation_symbols,
+                externals,
+                budget.max_skolem_depth(),
+                errors,
+            )?;
 
-        let new_fact_count = new_non_delta_fact_count - previous_non_delta_fact_count;
+            if diffs.iter().all(|(_, diff)| diff.is_empty()) {
+                break;
+            }
+            budget.round(
+                relation_storage,
+                diffs
+                    .iter()
+                    .filter(|(_, diff)| !diff.is_empty())
+                    .map(|(delta_relation_symbol, _)| delta_relation_symbol),
+            )?;
 
-        if new_fact_count == 0 {
-            return;
+            diffs.into_iter().for_each(|(delta_relation_symbol, diff)| {
+                stratum_deltas
+                    .get_mut(&delta_relation_symbol)
+                    .unwrap()
+                    .extend(diff)
+            });
+
+            // Deltas of earlier strata have been fully consumed by the first round.
+            rules.retain(|rule| {
+                rule.body
+                    .iter()
+                    .any(|body_atom| delta_relation_symbols.contains(&body_atom.symbol))
+            });
         }
+
+        stratum_deltas
+            .into_iter()
+            .for_each(|(delta_relation_symbol, facts)| {
+                relation_storage.replace_relation(&delta_relation_symbol, facts.into_iter())
+            });
     }
-}
\ No newline at end of file
+
+    Ok(())
+}
@@ -1,8 +1,9 @@
-use std::any::type_name;
+use crate::engine::dictionary::{Dictionary, EncodedGroundAtom, ValueId};
 use crate::engine::ephemeral_storage::{EphemeralStorage, EphemeralValue};
+use crate::engine::external::{order_body, ExternalRelations};
 use crate::engine::storage::RelationStorage;
 use ahash::{HashMap, HashSet};
-use datalog_syntax::{AnonymousGroundAtom, Rule, SkolemFunction, Term, TypedValue, Variable};
+use datalog_syntax::{Rule, SkolemFunction, Term, TypedValue, Variable};
 use crate::evaluation::spj_processor::Instruction::{Join, Project};
 
 // This implements a minimal SPJ (Select, Project, Join) processor
@@ -18,12 +19,25 @@ enum ProjectionInput {
     Skolemizer(SkolemFunction, HashMap<String, usize>)
 }
 
+#[derive(PartialEq, Debug, Clone)]
+enum ExternalArgument {
+    Column(Column),
+    Value(Value),
+    // Generated by the external relation
+    Free(Variable),
+}
+
 #[derive(PartialEq, Debug, Clone)]
 enum Instruction {
     Move(Symbol),
     Select(Symbol, Column, Value),
     Project(Symbol, Vec<ProjectionInput>),
     Join(Symbol, Symbol, Vec<(usize, usize)>),
+    // Keeps the left facts that have no match on the right, which is how negation is evaluated
+    AntiJoin(Symbol, Symbol, Vec<(usize, usize)>),
+    // Extends the left facts with the tuples of an external relation matching them, or when
+    // negated keeps those with none
+    External(Symbol, Symbol, Vec<ExternalArgument>, bool),
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -32,57 +46,100 @@ struct Stack {
 }
 
 fn stringify_selection(selection: &Instruction) -> String {
-    return match selection {
+    match selection {
         Instruction::Select(symbol, column, value) => format!("{}_{}={:?}", symbol, column, value),
         _ => unreachable!(),
-    };
+    }
 }
 
 fn stringify_join(join: &Instruction) -> String {
-    return match join {
+    let (left_symbol, right_symbol, join_keys, separator) = match join {
         Instruction::Join(left_symbol, right_symbol, join_keys) => {
-            let join_keys_format = join_keys
+            (left_symbol, right_symbol, join_keys, "_")
+        }
+        Instruction::AntiJoin(left_symbol, right_symbol, join_keys) => {
+            (left_symbol, right_symbol, join_keys, "_not_")
+        }
+        _ => unreachable!(),
+    };
+    let join_keys_format = join_keys
+        .iter()
+        .map(|(left_column, right_column)| format!("{}={}", left_column, right_column))
+        .collect::<Vec<_>>()
+        .join("_");
+
+    format!("{}{}{}_{}", left_symbol, separator, right_symbol, join_keys_format)
+}
+
+fn stringify_external(external: &Instruction) -> String {
+    match external {
+        Instruction::External(left_symbol, symbol, arguments, negated) => {
+            let arguments_format = arguments
                 .iter()
-                .map(|(left_column, right_column)| format!("{}={}", left_column, right_column))
+                .map(|argument| match argument {
+                    ExternalArgument::Column(column) => column.to_string(),
+                    ExternalArgument::Value(value) => format!("{:?}", value),
+                    ExternalArgument::Free(name) => format!("?{}", name),
+                })
                 .collect::<Vec<_>>()
                 .join("_");
+            let separator = if *negated { "_not_" } else { "_" };
 
-            format!("{}_{}_{}", left_symbol, right_symbol, join_keys_format)
-        },
+            format!("{}{}{}({})", left_symbol, separator, symbol, arguments_format)
+        }
         _ => unreachable!(),
-    };
+    }
 }
 
-fn get_selection(symbol: &str, terms: &Vec<Term>) -> Option<Instruction> {
+fn get_external_arguments(left_terms: &[Term], terms: &[Term]) -> Vec<ExternalArgument> {
+    let left_variable_map = get_variables(left_terms);
+
+    terms
+        .iter()
+        .map(|term| match term {
+            Term::Variable(name) => match left_variable_map.get(name) {
+                Some(column) => ExternalArgument::Column(*column),
+                None => ExternalArgument::Free(name.clone()),
+            },
+            Term::Constant(value) => ExternalArgument::Value(value.clone()),
+            Term::Skolemizer(_) => unreachable!(),
+        })
+        .collect()
+}
+
+// A variable generated twice has to be generated the same.
+fn generated_consistently(arguments: &[ExternalArgument], tuple: &[Value]) -> bool {
+    arguments.iter().zip(tuple).all(|(argument, value)| {
+        let first = arguments.iter().position(|other| other == argument).unwrap();
+
+        !matches!(argument, ExternalArgument::Free(_)) || tuple[first] == *value
+    })
+}
+
+fn get_selection(symbol: &str, terms: &[Term]) -> Option<Instruction> {
     let selection: Vec<Instruction> = terms
         .iter()
         .enumerate()
-        .filter(|(_, term)| match term {
-            Term::Constant(_) => true,
-            _ => false,
-        })
+        .filter(|(_, term)| matches!(term, Term::Constant(_)))
         .map(|(idx, constant)| {
             let constant_value = match constant {
                 Term::Constant(inner) => inner,
                 _ => unreachable!(),
             };
 
-            return Instruction::Select(symbol.to_string(), idx, constant_value.clone());
+            Instruction::Select(symbol.to_string(), idx, constant_value.clone())
         })
         .collect();
 
-    return selection.get(0).cloned();
+    selection.first().cloned()
 }
 
-fn get_variables(terms: &Vec<Term>) -> HashMap<Variable, usize> {
+fn get_variables(terms: &[Term]) -> HashMap<Variable, usize> {
     terms
-        .clone()
-        .into_iter()
+        .iter()
+        .cloned()
         .enumerate()
-        .filter(|(_, term)| match term {
-            Term::Variable(_) => true,
-            _ => false,
-        })
+        .filter(|(_, term)| matches!(term, Term::Variable(_)))
         .map(|(idx, term)| match term {
             Term::Variable(name) => (name, idx),
             _ => unreachable!()
@@ -90,12 +147,13 @@ fn get_variables(terms: &Vec<Term>) -> HashMap<Variable, usize> {
         .collect()
 }
 
+// Without shared variables there are no join keys, and the join is a cartesian product.
 fn get_join(
-    left_terms: &Vec<Term>,
-    right_terms: &Vec<Term>,
+    left_terms: &[Term],
+    right_terms: &[Term],
     left_symbol: &str,
     right_symbol: &str,
-) -> Option<Instruction> {
+) -> Instruction {
     let left_variable_map = get_variables(left_terms);
     let right_variable_map = get_variables(right_terms);
 
@@ -107,57 +165,36 @@ fn get_join(
         }
     }
 
-    if !join_keys.is_empty() {
-        return Some(Join(
-            left_symbol.to_string(),
-            right_symbol.to_string(),
-            join_keys
-        ))
-    }
-
-    return None;
+    Join(
+        left_symbol.to_string(),
+        right_symbol.to_string(),
+        join_keys
+    )
 }
 
 fn get_projection(rule: &Rule) -> Instruction {
-    let projection_variable_targets: HashSet<String> = rule
-        .head
-        .terms
-        .iter()
-        .filter(|term| match term {
-            Term::Variable(_) => true,
-            _ => false
-        })
-        .map(|term| match term {
-            Term::Variable(name) => name.clone(),
-            _ => unreachable!()
-        })
-        .collect();
-
     let mut seen: HashSet<_> = Default::default();
     let mut variable_location_assuming_joins_are_natural: HashMap<Variable, usize> =
         Default::default();
 
     let mut position_assuming_joins_are_natural = 0;
 
-    rule.body.iter().for_each(|body_atom| {
-        body_atom.terms.iter().for_each(|term| {
-            match term {
-                Term::Variable(name) => {
-                    if !seen.contains(name) {
-                        seen.insert(name.clone());
-
-                        //if projection_variable_targets.contains(name) {
+    // Negated atoms are anti-joined, hence they contribute no columns.
+    rule.body
+        .iter()
+        .filter(|body_atom| !body_atom.negated)
+        .for_each(|body_atom| {
+            body_atom.terms.iter().for_each(|term| {
+                if let Term::Variable(name) = term {
+                    if seen.insert(name.clone()) {
                         variable_location_assuming_joins_are_natural
                             .insert(name.clone(), position_assuming_joins_are_natural);
-                        //}
                     }
                 }
-                _ => {},
-            }
 
-            position_assuming_joins_are_natural += 1;
+                position_assuming_joins_are_natural += 1;
+            });
         });
-    });
 
     let projection = rule
         .head
@@ -192,9 +229,13 @@ fn get_projection(rule: &Rule) -> Instruction {
     Project(rule.head.symbol.clone(), projection)
 }
 
-impl From<Rule> for Stack {
-    fn from(rule: Rule) -> Self {
+impl Stack {
+    // Expects the rule to have been checked by order_body.
+    fn new(mut rule: Rule, externals: &ExternalRelations) -> Self {
         let mut operations = vec![];
+        // Negated atoms can only filter once every variable they share has been bound, and
+        // external atoms once their inputs have been.
+        rule.body = order_body(&rule, externals).unwrap();
 
         let mut body_iter = rule.body.iter().peekable();
         let mut last_join_result_name = None;
@@ -216,6 +257,24 @@ impl From<Rule> for Stack {
             }
 
             if let Some(next_atom) = body_iter.peek() {
+                if externals.contains(&next_atom.symbol) {
+                    let external = Instruction::External(
+                        left_symbol,
+                        next_atom.symbol.clone(),
+                        get_external_arguments(left_terms, &next_atom.terms),
+                        next_atom.negated,
+                    );
+
+                    last_join_terms = left_terms.clone();
+                    if !next_atom.negated {
+                        last_join_terms.extend(next_atom.terms.clone());
+                    }
+                    last_join_result_name = Some(stringify_external(&external));
+
+                    operations.push(external);
+                    continue;
+                }
+
                 let mut right_symbol = next_atom.symbol.clone();
                 let right_terms = &next_atom.terms;
 
@@ -227,15 +286,20 @@ impl From<Rule> for Stack {
                     operations.push(Instruction::Move(right_symbol.clone()));
                 }
 
-                if let Some(binary_join) =
-                    get_join(&left_terms, right_terms, &left_symbol, &right_symbol)
-                {
-                    last_join_result_name = Some(stringify_join(&binary_join));
-                    last_join_terms = left_terms.clone();
-                    last_join_terms.extend(right_terms.clone());
+                let mut binary_join =
+                    get_join(left_terms, right_terms, &left_symbol, &right_symbol);
 
-                    operations.push(binary_join);
+                last_join_terms = left_terms.clone();
+                if next_atom.negated {
+                    if let Join(left_symbol, right_symbol, join_keys) = binary_join {
+                        binary_join = Instruction::AntiJoin(left_symbol, right_symbol, join_keys);
+                    }
+                } else {
+                    last_join_terms.extend(right_terms.clone());
                 }
+                last_join_result_name = Some(stringify_join(&binary_join));
+
+                operations.push(binary_join);
             } else {
                 if operations.is_empty() {
                     operations.push(Instruction::Move(current_atom.symbol.clone()));
@@ -261,27 +325,40 @@ impl From<Rule> for Stack {
 pub struct RuleEvaluator<'a> {
     rule: &'a Rule,
     facts_storage: &'a RelationStorage,
+    externals: &'a ExternalRelations,
 }
 
 impl<'a> RuleEvaluator<'a> {
-    pub(crate) fn new(facts_storage: &'a RelationStorage, rule: &'a Rule) -> Self {
+    pub(crate) fn new(
+        facts_storage: &'a RelationStorage,
+        rule: &'a Rule,
+        externals: &'a ExternalRelations,
+    ) -> Self {
         Self {
             rule,
             facts_storage,
+            externals,
         }
     }
 }
 
 impl<'a> RuleEvaluator<'a> {
-    pub fn step(&self) -> impl Iterator<Item = AnonymousGroundAtom> + 'a {
-        let stack = Stack::from(self.rule.clone());
+    // Fails if a function would exceed `max_skolem_depth`. Facts other functions fail on are
+    // skipped, and their failures added to `errors`.
+    pub fn step(
+        &self,
+        dictionary: &mut Dictionary,
+        max_skolem_depth: Option<usize>,
+        errors: &mut Vec<String>,
+    ) -> Result<Vec<EncodedGroundAtom>, String> {
+        let stack = Stack::new(self.rule.clone(), self.externals);
 
         let mut out = EphemeralStorage::default();
 
         // There will always be at least two elements in the stack. Move or Select and then Projection.
         let penultimate_operation = stack.inner.len() - 2;
         let mut relation_symbol_to_be_projected = self.rule.head.symbol.clone();
-        let mut grounded_facts: Vec<AnonymousGroundAtom> = vec![];
+        let mut grounded_facts: Vec<EncodedGroundAtom> = vec![];
 
         for (idx, operation) in stack.inner.iter().enumerate() {
             match operation {
@@ -289,119 +366,174 @@ impl<'a> RuleEvaluator<'a> {
                     if idx == penultimate_operation {
                         relation_symbol_to_be_projected = symbol.clone();
                     }
-                    let moved = out.inner.get(symbol).is_some();
+                    let moved = out.inner.contains_key(symbol);
                     // If it has already been moved, then this is a NOOP
                     if !moved {
                         let fact_refs = self.facts_storage.get_relation(symbol);
 
-                        out.borrow_all(
-                            symbol,
-                            fact_refs
-                                .into_iter()
-                                .map(|fact| EphemeralValue::FactRef(fact)),
-                        )
+                        out.borrow_all(symbol, fact_refs.iter().map(EphemeralValue::FactRef))
                     }
                 }
                 Instruction::Select(symbol, column, value) => {
-                    let index_name = stringify_selection(&operation);
+                    let index_name = stringify_selection(operation);
                     if idx == penultimate_operation {
                         relation_symbol_to_be_projected = index_name.clone();
                     }
                     // If the index already exists, then this is a NOOP.
-                    if out.inner.get(&index_name).is_none() {
+                    if !out.inner.contains_key(&index_name) {
                         let target_relation = self.facts_storage.get_relation(symbol);
+                        // A value that has never been interned cannot be in any fact.
+                        let value_id = dictionary.get_id(value);
 
                         let selection = target_relation
                             .iter()
-                            .filter(|fact| fact[*column] == *value)
-                            .map(|fact| EphemeralValue::FactRef(fact));
+                            .filter(|fact| Some(fact[*column]) == value_id)
+                            .map(EphemeralValue::FactRef);
 
                         out.borrow_all(&index_name, selection)
                     }
                 }
                 Instruction::Join(left_symbol, right_symbol, join_keys) => {
-                    let left_relation = out.get_relation(&left_symbol);
-                    let right_relation = out.get_relation(&right_symbol);
+                    let left_relation = out.get_relation(left_symbol);
+                    let right_relation = out.get_relation(right_symbol);
                     let join_result_name = stringify_join(operation);
                     if idx == penultimate_operation {
                         relation_symbol_to_be_projected = join_result_name.clone();
                     }
 
+                    // Hash join. The right side is always a plain relation, hence it is the one
+                    // indexed on its join columns, and the left side probes it.
+                    let mut right_index: HashMap<Vec<ValueId>, Vec<&[ValueId]>> =
+                        Default::default();
+                    right_relation.iter().for_each(|right_allocation| {
+                        let right_fact = match right_allocation {
+                            EphemeralValue::FactRef(fact) => *fact,
+                            _ => unreachable!(),
+                        };
+                        let join_key = join_keys
+                            .iter()
+                            .map(|(_, right_column)| right_fact[*right_column])
+                            .collect();
+
+                        right_index.entry(join_key).or_default().push(right_fact);
+                    });
+
                     let mut join_result = vec![];
 
                     left_relation.iter().for_each(|left_allocation| {
-                        let is_left_product = match left_allocation {
-                            EphemeralValue::FactRef(_) => false,
-                            EphemeralValue::JoinResult(_) => true
-                        };
-                        let mut join_key_positions = None;
-                        if is_left_product {
-                            join_key_positions = Some(join_keys
-                                .iter()
-                                .map(|(left_column, right_column)| {
-                                    let mut cumsum = 0;
-
-                                    let arities = {
-                                        match left_allocation {
-                                            EphemeralValue::JoinResult(product) => product,
-                                            _ => unreachable!()
-                                        }
+                        let join_key: Vec<ValueId> = join_keys
+                            .iter()
+                            .map(|(left_column, _)| left_allocation.column(*left_column))
+                            .collect();
+
+                        if let Some(right_facts) = right_index.get(&join_key) {
+                            right_facts.iter().for_each(|right_fact| {
+                                let new_product = match left_allocation {
+                                    EphemeralValue::FactRef(left_fact) => {
+                                        vec![*left_fact, *right_fact]
                                     }
-                                        .iter()
-                                        .map(|fact| fact.len());
-
-                                    let mut left_idx = 0;
+                                    EphemeralValue::JoinResult(product) => {
+                                        let mut new_product = product.clone();
+                                        new_product.push(right_fact);
 
-                                    for (idx, arity) in arities.enumerate() {
-                                        cumsum += arity;
+                                        new_product
+                                    }
+                                    EphemeralValue::Owned(values) => {
+                                        let mut row = values.clone();
+                                        row.extend_from_slice(right_fact);
 
-                                        if *left_column < cumsum {
-                                            left_idx = idx;
-                                            break;
-                                        }
+                                        join_result.push(EphemeralValue::Owned(row));
+                                        return;
                                     }
+                                };
 
-                                    ((left_idx, cumsum - left_column), right_column)
-                                })
-                            );
+                                join_result.push(EphemeralValue::JoinResult(new_product));
+                            })
                         }
+                    });
 
-                        right_relation.into_iter().for_each(|right_allocation| {
-                            let right_fact = match right_allocation {
-                                EphemeralValue::FactRef(fact) => fact,
-                                EphemeralValue::JoinResult(_) => unreachable!(),
-                            };
+                    out.borrow_all(&join_result_name, join_result.into_iter());
+                }
+                Instruction::AntiJoin(left_symbol, right_symbol, join_keys) => {
+                    let left_relation = out.get_relation(left_symbol);
+                    let right_relation = out.get_relation(right_symbol);
+                    let anti_join_result_name = stringify_join(operation);
+                    if idx == penultimate_operation {
+                        relation_symbol_to_be_projected = anti_join_result_name.clone();
+                    }
 
-                            match left_allocation {
-                                EphemeralValue::FactRef(left_fact) => {
-                                    if join_keys
-                                        .iter()
-                                        .all(|(left_column, right_column)| {
-                                            left_fact[*left_column] == right_fact[*right_column]
-                                        }) {
-                                        {
-                                            join_result.push(EphemeralValue::JoinResult(vec![
-                                                left_fact,
-                                                right_fact,
-                                            ]));
-                                        }
-                                    }
-                                }
-                                EphemeralValue::JoinResult(product) => {
-                                    if join_key_positions.clone().unwrap().into_iter().all(|((left_fact_idx, left_column), right_column)| {
-                                        product[left_fact_idx][left_column] == right_fact[*right_column]
-                                    }) {
-                                        let mut new_product = product.clone();
-                                        new_product.push(right_fact);
+                    let right_keys: HashSet<Vec<ValueId>> = right_relation
+                        .iter()
+                        .map(|right_allocation| match right_allocation {
+                            EphemeralValue::FactRef(right_fact) => join_keys
+                                .iter()
+                                .map(|(_, right_column)| right_fact[*right_column])
+                                .collect(),
+                            _ => unreachable!(),
+                        })
+                        .collect();
+
+                    let anti_join_result: Vec<_> = left_relation
+                        .iter()
+                        .filter(|left_allocation| {
+                            let join_key: Vec<ValueId> = join_keys
+                                .iter()
+                                .map(|(left_column, _)| left_allocation.column(*left_column))
+                                .collect();
+
+                            !right_keys.contains(&join_key)
+                        })
+                        .cloned()
+                        .collect();
 
-                                        join_result.push(EphemeralValue::JoinResult(new_product));
-                                    };
+                    out.borrow_all(&anti_join_result_name, anti_join_result.into_iter());
+                }
+                Instruction::External(left_symbol, symbol, arguments, negated) => {
+                    let external_result_name = stringify_external(operation);
+                    if idx == penultimate_operation {
+                        relation_symbol_to_be_projected = external_result_name.clone();
+                    }
+                    let relation = self.externals.get(symbol).unwrap();
+
+                    let mut external_result = vec![];
+                    for left_allocation in out.get_relation(left_symbol) {
+                        let values: Vec<_> = arguments
+                            .iter()
+                            .map(|argument| match argument {
+                                ExternalArgument::Column(column) => {
+                                    Some(dictionary.resolve(left_allocation.column(*column)))
                                 }
+                                ExternalArgument::Value(value) => Some(value),
+                                ExternalArgument::Free(_) => None,
+                            })
+                            .collect();
+                        let mut tuples = match relation.matching(&values) {
+                            Ok(tuples) => tuples,
+                            // Like failing functions, failing relations hold for nothing
+                            Err(err) => {
+                                errors.push(format!("{}: {}", symbol, err));
+                                continue;
                             }
-                        })
-                    });
+                        };
+                        tuples.retain(|tuple| generated_consistently(arguments, tuple));
 
-                    out.borrow_all(&join_result_name, join_result.into_iter());
+                        if *negated {
+                            if tuples.is_empty() {
+                                external_result.push(left_allocation.clone());
+                            }
+                            continue;
+                        }
+                        for tuple in tuples {
+                            let mut row = left_allocation.to_row();
+                            row.extend(
+                                tuple.into_iter().map(|value| dictionary.intern_owned(value)),
+                            );
+
+                            external_result.push(EphemeralValue::Owned(row));
+                        }
+                    }
+
+                    out.borrow_all(&external_result_name, external_result.into_iter());
                 }
                 Instruction::Project(_symbol, projection_inputs) => {
                     let ephemeral_relation_to_be_projected = out
@@ -409,48 +541,69 @@ impl<'a> RuleEvaluator<'a> {
                         .remove(relation_symbol_to_be_projected.as_str())
                         .unwrap();
 
-                    ephemeral_relation_to_be_projected
-                        .into_iter()
-                        .for_each(|allocation| {
-                            let fact = match allocation {
-                                EphemeralValue::FactRef(fact) => fact.clone(),
-                                EphemeralValue::JoinResult(facts) => {
-                                    facts.into_iter().flat_map(|fact| fact).cloned().collect()
-                                }
-                            };
-
-                            let mut projection = vec![];
+                    for allocation in ephemeral_relation_to_be_projected {
+                        let fact: EncodedGroundAtom = allocation.to_row();
 
-                            projection_inputs.iter().for_each(|projection_input| {
-                                match projection_input {
-                                    ProjectionInput::Column(column, name) => {
-                                        projection.push(fact[*column].clone())
+                        let mut projection = vec![];
+                        let mut failure = None;
+                        for projection_input in projection_inputs {
+                            match projection_input {
+                                ProjectionInput::Column(column, _name) => {
+                                    projection.push(fact[*column])
+                                }
+                                ProjectionInput::Value(value) => {
+                                    projection.push(dictionary.intern(value))
+                                }
+                                ProjectionInput::Skolemizer(skolemization_function, vars) => {
+                                    let input_depth = vars
+                                        .values()
+                                        .map(|column| dictionary.skolem_depth(fact[*column]))
+                                        .max()
+                                        .unwrap_or(0);
+                                    if let Some(max_skolem_depth) = max_skolem_depth {
+                                        if input_depth >= max_skolem_depth {
+                                            return Err(format!(
+                                                "{} exceeded a nesting depth of {}",
+                                                skolemization_function, max_skolem_depth
+                                            ));
+                                        }
                                     }
-                                    ProjectionInput::Value(value) => {
-                                        projection.push(value.clone())
-                                    },
-                                    ProjectionInput::Skolemizer(skolemization_function, vars) => {
-                                        let inputs = vars
-                                            .iter()
-                                            .map(|(name, column)| {
-                                                (name.as_str(), fact.get(*column).unwrap())
-                                            })
-                                            .collect();
-
-                                        projection.push((skolemization_function.func)(inputs))
+
+                                    // Skolem functions are the only place where values are
+                                    // decoded during evaluation.
+                                    let inputs = vars
+                                        .iter()
+                                        .map(|(name, column)| {
+                                            (name.as_str(), dictionary.resolve(fact[*column]))
+                                        })
+                                        .collect();
+                                    match skolemization_function.call(inputs) {
+                                        Ok(output) => projection
+                                            .push(dictionary.intern_invented(output, input_depth)),
+                                        Err(err) => {
+                                            failure = Some(format!(
+                                                "{}: {}",
+                                                skolemization_function, err
+                                            ));
+                                            break;
+                                        }
                                     }
                                 }
-                            });
-
-                            //println!("{}{:?}", relation_symbol_to_be_projected, projection);
+                            }
+                        }
 
-                            grounded_facts.push(projection)
-                        });
+                        // Facts a function fails on are not derived, and the failure is reported
+                        // instead.
+                        match failure {
+                            None => grounded_facts.push(projection),
+                            Some(err) => errors.push(err),
+                        }
+                    }
                 }
             }
         }
 
-        grounded_facts.into_iter()
+        Ok(grounded_facts)
     }
 }
 
@@ -458,7 +611,8 @@ impl<'a> RuleEvaluator<'a> {
 mod test {
     use datalog_rule_macro::rule;
     use datalog_syntax::*;
-    use crate::evaluation::spj_processor::{Instruction, ProjectionInput, Stack};
+    use crate::engine::external::ExternalRelations;
+    use crate::evaluation::spj_processor::{ExternalArgument, Instruction, ProjectionInput, Stack};
 
     #[test]
     fn from_unary_rule_into_stack() {
@@ -474,7 +628,7 @@ mod test {
             ],
         };
 
-        assert_eq!(expected_stack, Stack::from(rule))
+        assert_eq!(expected_stack, Stack::new(rule, &ExternalRelations::default()))
     }
 
     #[test]
@@ -497,7 +651,7 @@ mod test {
             ],
         };
 
-        assert_eq!(expected_stack, Stack::from(rule))
+        assert_eq!(expected_stack, Stack::new(rule, &ExternalRelations::default()))
     }
 
     #[test]
@@ -516,7 +670,23 @@ mod test {
             ],
         };
 
-        assert_eq!(expected_stack, Stack::from(rule))
+        assert_eq!(expected_stack, Stack::new(rule, &ExternalRelations::default()))
+    }
+
+    #[test]
+    fn from_negated_rule_into_stack() {
+        let rule = rule! { S(?x) <- [!E(?x, ?y), N(?x)] };
+
+        let expected_stack = Stack {
+            inner: vec![
+                Instruction::Move("N".to_string()),
+                Instruction::Move("E".to_string()),
+                Instruction::AntiJoin("N".to_string(), "E".to_string(), vec![(0, 0)]),
+                Instruction::Project("S".to_string(), vec![ProjectionInput::Column(0, "x".into())]),
+            ],
+        };
+
+        assert_eq!(expected_stack, Stack::new(rule, &ExternalRelations::default()))
     }
 
     #[test]
@@ -541,6 +711,41 @@ mod test {
             ],
         };
 
-        assert_eq!(expected_stack, Stack::from(rule))
+        assert_eq!(expected_stack, Stack::new(rule, &ExternalRelations::default()))
+    }
+
+    #[test]
+    fn from_external_rule_into_stack() {
+        let rule = rule! { P(?x, ?d) <- [D(?x, ?d), E(?x), !M(?x, "a")] };
+        let mut externals = ExternalRelations::default();
+        externals.register("D", &["bf"], |_| Ok(vec![])).unwrap();
+        externals.register_filter("M", 2, |_| Ok(false));
+
+        let expected_stack = Stack {
+            inner: vec![
+                Instruction::Move("E".to_string()),
+                Instruction::External(
+                    "E".to_string(),
+                    "D".to_string(),
+                    vec![ExternalArgument::Column(0), ExternalArgument::Free("d".into())],
+                    false,
+                ),
+                Instruction::External(
+                    "E_D(0_?d)".to_string(),
+                    "M".to_string(),
+                    vec![
+                        ExternalArgument::Column(1),
+                        ExternalArgument::Value(TypedValue::from("a")),
+                    ],
+                    true,
+                ),
+                Instruction::Project(
+                    "P".to_string(),
+                    vec![ProjectionInput::Column(0, "x".into()), ProjectionInput::Column(2, "d".into())],
+                ),
+            ],
+        };
+
+        assert_eq!(expected_stack, Stack::new(rule, &externals))
     }
 }
@@ -1,50 +1,119 @@
-use datalog_syntax::Program;
+use crate::engine::external::ExternalRelations;
+use datalog_syntax::{Program, Term};
+use std::collections::{HashMap, HashSet};
 
 pub const DELTA_PREFIX: &str = "Δ";
 pub fn add_prefix(symbol: &mut String, prefix: &str) {
     *symbol = format!("{}{}", prefix, symbol);
 }
 
-pub fn split_program(program: Program) -> (Program, Program) {
-    let mut nonrecursive = vec![];
-    let mut recursive = vec![];
-
-    program.inner.into_iter().for_each(|rule| {
-        let head_symbol = rule.head.symbol.as_str();
-
-        if rule
+// Every variable of a head has to be bound by a positive body atom, since negated atoms only ever
+// filter. Variables appearing solely in negated atoms mean "for any value". Functions have to be
+// resolved, see FunctionRegistry.
+pub fn check_safety(program: &Program) -> Result<(), String> {
+    for rule in &program.inner {
+        let bound_variables: HashSet<_> = rule
             .body
             .iter()
-            .map(|body_atom| &body_atom.symbol)
-            .any(|body_atom_symbol| head_symbol == body_atom_symbol)
-        {
-            recursive.push(rule);
-        } else {
-            nonrecursive.push(rule);
+            .filter(|body_atom| !body_atom.negated)
+            .flat_map(|body_atom| body_atom.terms.iter())
+            .filter_map(|term| match term {
+                Term::Variable(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        if rule.body.iter().all(|body_atom| body_atom.negated) {
+            return Err(format!("rule {} has no positive body atom", rule));
+        }
+
+        let head_variables = rule.head.terms.iter().flat_map(|term| match term {
+            Term::Variable(name) => vec![name],
+            Term::Constant(_) => vec![],
+            Term::Skolemizer(skolem_function) => skolem_function.deps.iter().collect(),
+        });
+        for term in &rule.head.terms {
+            if let Term::Skolemizer(skolem_function) = term {
+                if !skolem_function.is_resolved() {
+                    return Err(format!(
+                        "function {} of rule {} is not resolved",
+                        skolem_function.name, rule
+                    ));
+                }
+            }
+        }
+        for variable in head_variables {
+            if !bound_variables.contains(variable) {
+                return Err(format!(
+                    "variable {} of rule {} is not bound by a positive body atom",
+                    variable, rule
+                ));
+            }
         }
+    }
+
+    Ok(())
+}
+
+// The arity of every stored relation of the program, which has to be the same wherever it appears.
+// External relations are left out, since their atoms are never stored.
+pub fn relation_arities(
+    program: &Program,
+    externals: &ExternalRelations,
+) -> Result<HashMap<String, usize>, String> {
+    let mut arities = HashMap::new();
+    let atoms = program.inner.iter().flat_map(|rule| {
+        let body = rule.body.iter().map(|body_atom| (&body_atom.symbol, body_atom.terms.len()));
+        body.chain(std::iter::once((&rule.head.symbol, rule.head.terms.len())))
     });
+    for (symbol, arity) in atoms.filter(|(symbol, _)| !externals.contains(symbol)) {
+        let known_arity = *arities.entry(symbol.clone()).or_insert(arity);
+        if known_arity != arity {
+            return Err(format!(
+                "relation {} is used with arities {} and {}",
+                symbol, known_arity, arity
+            ));
+        }
+    }
 
-    (Program::from(nonrecursive), Program::from(recursive))
+    Ok(arities)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::helpers::helpers::split_program;
+    use crate::engine::external::ExternalRelations;
+    use crate::helpers::helpers::{check_safety, relation_arities};
     use datalog_rule_macro::program;
     use datalog_syntax::*;
     #[test]
-    fn test_split_program() {
-        let program = program! {
+    fn test_check_safety() {
+        let safe_program = program! {
             tc(?x, ?y) <- [e(?x, ?y)],
-            tc(? x, ?z) <- [e(? x, ?y), tc(? y, ?z)]
+            sink(?x) <- [node(?x), !e(?x, ?y)]
         };
+        assert!(check_safety(&safe_program).is_ok());
 
-        let expected_nonrecursive_program = program! { tc(?x, ?y) <- [e(?x, ?y)] };
-        let expected_recursive_program = program! { tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z)] };
+        let mut unsafe_program = safe_program.clone();
+        unsafe_program.inner[0].body[0].negated = true;
+        assert!(check_safety(&unsafe_program).is_err());
+    }
 
-        let (actual_nonrecursive_program, actual_recursive_program) = split_program(program);
+    #[test]
+    fn test_relation_arities() {
+        let program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            tc(?x, ?z) <- [tc(?x, ?y), e(?y, ?z)]
+        };
+        let arities = relation_arities(&program, &ExternalRelations::default()).unwrap();
+        assert_eq!(Some(&2), arities.get("tc"));
+        assert_eq!(Some(&2), arities.get("e"));
 
-        assert_eq!(expected_nonrecursive_program, actual_nonrecursive_program);
-        assert_eq!(expected_recursive_program, actual_recursive_program);
+        let program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            sink(?x) <- [tc(?x), !e(?x, ?y)]
+        };
+        assert!(relation_arities(&program, &ExternalRelations::default())
+            .unwrap_err()
+            .starts_with("relation tc is used with arities"));
     }
 }
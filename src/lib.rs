@@ -1,4 +1,11 @@
+pub mod cli;
 pub mod engine;
 mod evaluation;
 mod helpers;
+pub mod loaders;
+pub mod modules;
+pub mod parser;
 mod program_transformations;
+pub mod repl;
+pub mod souffle;
+pub mod workflows;
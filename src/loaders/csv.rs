@@ -0,0 +1,424 @@
+use crate::engine::datalog::MicroRuntime;
+use datalog_syntax::{AnonymousGroundAtom, Query, TypedValue};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// What the fields of a column are read as. Columns are read as strings unless told otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    Str,
+    Int,
+    I64,
+    Float,
+    Bool,
+}
+
+impl ColumnType {
+    // Named after the variants of TypedValue, e.g. "i64".
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "str" => Ok(ColumnType::Str),
+            "int" => Ok(ColumnType::Int),
+            "i64" => Ok(ColumnType::I64),
+            "float" => Ok(ColumnType::Float),
+            "bool" => Ok(ColumnType::Bool),
+            _ => Err(format!(
+                "unknown column type {}, expected str, int, i64, float or bool",
+                name
+            )),
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            ColumnType::Str => "str",
+            ColumnType::Int => "int",
+            ColumnType::I64 => "i64",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+        }
+    }
+    pub fn coerce(self, field: &str) -> Result<TypedValue, String> {
+        let value = match self {
+            ColumnType::Str => Some(field.into()),
+            ColumnType::Int => field.parse::<usize>().ok().map(TypedValue::from),
+            ColumnType::I64 => field.parse::<i64>().ok().map(TypedValue::from),
+            ColumnType::Float => field.parse::<f64>().ok().map(TypedValue::from),
+            ColumnType::Bool => field.parse::<bool>().ok().map(TypedValue::from),
+        };
+
+        value.ok_or_else(|| format!("{:?} is not a {}", field, self.name()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CsvFormat {
+    pub delimiter: char,
+    // Whether the first record names the columns, in which case it is skipped
+    pub header: bool,
+}
+
+impl CsvFormat {
+    // Tab separated for .tsv files, comma separated otherwise.
+    pub fn for_path(path: &Path) -> Self {
+        let tsv = path.extension().is_some_and(|extension| extension == "tsv");
+
+        CsvFormat {
+            delimiter: if tsv { '\t' } else { ',' },
+            header: false,
+        }
+    }
+}
+
+// Splits CSV into records, as described by RFC 4180: fields may be quoted, in which case they may
+// hold delimiters, newlines and quotes, the latter doubled. Blank lines are skipped. Records come
+// with the line they start at.
+fn records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = vec![];
+    let mut characters = text.chars().peekable();
+    let mut line = 1;
+
+    while characters.peek().is_some() {
+        let start = line;
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            match characters.next() {
+                Some('"') if field.is_empty() && !quoted => {
+                    quoted = true;
+                    loop {
+                        match characters.next() {
+                            Some('"') if characters.peek() == Some(&'"') => {
+                                characters.next();
+                                field.push('"');
+                            }
+                            Some('"') => break,
+                            Some(character) => {
+                                line += (character == '\n') as usize;
+                                field.push(character);
+                            }
+                            None => return Err(format!("line {}: unterminated quote", start)),
+                        }
+                    }
+                }
+                Some(character) if character == delimiter => {
+                    fields.push(std::mem::take(&mut field));
+                    quoted = false;
+                }
+                Some('\r') if characters.peek() == Some(&'\n') => {}
+                Some('\n') | None => {
+                    line += 1;
+                    break;
+                }
+                Some(_) if quoted => {
+                    return Err(format!("line {}: unexpected text after a quoted field", line));
+                }
+                Some(character) => field.push(character),
+            }
+        }
+        if fields.is_empty() && field.is_empty() && !quoted {
+            continue;
+        }
+        fields.push(field);
+        records.push((start, fields));
+    }
+
+    Ok(records)
+}
+
+// Reads every record as a fact, its fields coerced to the given types. Every field is a string if
+// no type is given.
+pub fn read_csv(
+    mut reader: impl BufRead,
+    format: CsvFormat,
+    types: &[ColumnType],
+) -> Result<Vec<AnonymousGroundAtom>, String> {
+    let mut text = String::new();
+    reader
+        .read_to_string(&mut text)
+        .map_err(|err| err.to_string())?;
+
+    records(&text, format.delimiter)?
+        .into_iter()
+        .skip(format.header as usize)
+        .map(|(line, fields)| {
+            if !types.is_empty() && fields.len() != types.len() {
+                return Err(format!(
+                    "line {}: expected {} fields, found {}",
+                    line,
+                    types.len(),
+                    fields.len()
+                ));
+            }
+
+            fields
+                .iter()
+                .enumerate()
+                .map(|(column, field)| {
+                    let column_type = types.get(column).copied().unwrap_or(ColumnType::Str);
+                    column_type
+                        .coerce(field)
+                        .map_err(|err| format!("line {}: column {}: {}", line, column + 1, err))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Inserts every record as a fact of `relation`, see read_csv, returning how many were read.
+// Nothing is inserted if a record is invalid or doesn't fit the relation, e.g. by arity, and nothing
+// is materialised until the next poll.
+pub fn load_csv(
+    runtime: &mut MicroRuntime,
+    relation: &str,
+    reader: impl BufRead,
+    format: CsvFormat,
+    types: &[ColumnType],
+) -> Result<usize, String> {
+    let facts = read_csv(reader, format, types)?;
+
+    let fact_count = facts.len();
+    runtime.try_insert_all(facts.into_iter().map(|fact| (relation.to_string(), fact)).collect())?;
+
+    Ok(fact_count)
+}
+
+fn write_field(writer: &mut impl Write, field: &str, delimiter: char) -> std::io::Result<()> {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        write!(writer, "\"{}\"", field.replace('"', "\"\""))
+    } else {
+        write!(writer, "{}", field)
+    }
+}
+
+// Writes every fact of `relation`, sorted, one record each, returning how many were written.
+// Values are written as they are displayed, hence lists, bytes and custom values may not read back
+// as they were.
+pub fn write_csv(
+    runtime: &MicroRuntime,
+    relation: &str,
+    mut writer: impl Write,
+    format: CsvFormat,
+) -> Result<usize, String> {
+    // No matcher matches every fact, whatever the arity
+    let mut facts: Vec<_> = runtime
+        .query(&Query {
+            matchers: vec![],
+            symbol: relation,
+        })?
+        .collect();
+    facts.sort();
+
+    for fact in &facts {
+        for (column, value) in fact.iter().enumerate() {
+            if column > 0 {
+                write!(writer, "{}", format.delimiter).map_err(|err| err.to_string())?;
+            }
+            write_field(&mut writer, &value.to_string(), format.delimiter)
+                .map_err(|err| err.to_string())?;
+        }
+        writeln!(writer).map_err(|err| err.to_string())?;
+    }
+    writer.flush().map_err(|err| err.to_string())?;
+
+    Ok(facts.len())
+}
+
+// A relation read from or written to a file, as declared by the `.input` and `.output` directives
+// of a source:
+//
+//     .input repository(filename="repos.csv", types=["str", "str", "int"], header=true).
+//     .output owner(filename="owners.tsv").
+//
+// The delimiter, e.g. delimiter="|", defaults to a tab for .tsv files and to a comma otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvFile {
+    pub relation: String,
+    pub path: PathBuf,
+    pub format: CsvFormat,
+    pub types: Vec<ColumnType>,
+}
+
+impl CsvFile {
+    pub fn new(relation: &str, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+
+        Self {
+            relation: relation.to_string(),
+            format: CsvFormat::for_path(&path),
+            path,
+            types: vec![],
+        }
+    }
+    // Reads the parameters of a directive; header and types are only meaningful for inputs.
+    pub fn from_parameters(
+        relation: &str,
+        parameters: Vec<(String, TypedValue)>,
+        input: bool,
+    ) -> Result<Self, String> {
+        let filename = parameters.iter().find(|(name, _)| name == "filename");
+        let Some((_, TypedValue::Str(filename))) = filename else {
+            return Err(format!("{} needs a filename string", relation));
+        };
+        let mut file = CsvFile::new(relation, filename);
+
+        for (name, value) in parameters {
+            match (name.as_str(), value) {
+                ("filename", _) => {}
+                ("delimiter", TypedValue::Str(delimiter)) if delimiter.chars().count() == 1 => {
+                    file.format.delimiter = delimiter.chars().next().unwrap();
+                }
+                ("header", TypedValue::Bool(header)) if input => file.format.header = header,
+                ("types", TypedValue::List(types)) if input => {
+                    file.types = types
+                        .iter()
+                        .map(|column_type| match column_type {
+                            TypedValue::Str(name) => ColumnType::parse(name),
+                            _ => Err(format!("unknown column type {:?}", column_type)),
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                (name, value) => {
+                    return Err(format!("invalid parameter {}={:?} of {}", name, value, relation))
+                }
+            }
+        }
+
+        Ok(file)
+    }
+    // Resolves a relative path against `directory`, e.g. that of the source declaring the file.
+    pub fn relative_to(mut self, directory: &Path) -> Self {
+        self.path = directory.join(&self.path);
+        self
+    }
+    pub fn read(&self) -> Result<Vec<AnonymousGroundAtom>, String> {
+        let file =
+            File::open(&self.path).map_err(|err| format!("{}: {}", self.path.display(), err))?;
+
+        read_csv(BufReader::new(file), self.format, &self.types)
+            .map_err(|err| format!("{}: {}", self.path.display(), err))
+    }
+    pub fn load(&self, runtime: &mut MicroRuntime) -> Result<usize, String> {
+        let facts = self.read()?;
+
+        let fact_count = facts.len();
+        let facts = facts.into_iter().map(|fact| (self.relation.clone(), fact)).collect();
+        runtime
+            .try_insert_all(facts)
+            .map_err(|err| format!("{}: {}", self.path.display(), err))?;
+
+        Ok(fact_count)
+    }
+    pub fn write(&self, runtime: &MicroRuntime) -> Result<usize, String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| format!("{}: {}", parent.display(), err))?;
+        }
+        let file =
+            File::create(&self.path).map_err(|err| format!("{}: {}", self.path.display(), err))?;
+
+        write_csv(runtime, &self.relation, BufWriter::new(file), self.format)
+            .map_err(|err| format!("{}: {}", self.path.display(), err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::loaders::csv::{load_csv, write_csv, ColumnType, CsvFile, CsvFormat};
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+
+    const INVENTORY: &str = "name,owner,stars\r
+act-logically,\"Doe, Jane\",12\r
+\r
+\"quoted \"\"name\"\"\",\"multi
+line\",3\r
+";
+
+    #[test]
+    fn test_load_and_write() {
+        let program = program! { popular(?n, ?o) <- [repository(?n, ?o, ?s), starred(?s)] };
+        let mut runtime = MicroRuntime::new(program);
+        let format = CsvFormat {
+            delimiter: ',',
+            header: true,
+        };
+        let types = [ColumnType::Str, ColumnType::Str, ColumnType::Int];
+        let read = load_csv(&mut runtime, "repository", INVENTORY.as_bytes(), format, &types);
+        assert_eq!(Ok(2), read);
+        runtime.insert("starred", vec![12usize.into()]);
+        runtime.poll();
+
+        assert!(runtime
+            .contains(
+                "repository",
+                &vec!["quoted \"name\"".into(), "multi\nline".into(), 3usize.into()]
+            )
+            .unwrap());
+
+        let mut written = vec![];
+        assert_eq!(Ok(2), write_csv(&runtime, "repository", &mut written, format));
+        assert_eq!(
+            "act-logically,\"Doe, Jane\",12\n\"quoted \"\"name\"\"\",\"multi\nline\",3\n",
+            String::from_utf8(written).unwrap()
+        );
+        let mut written = vec![];
+        let tsv = CsvFormat::for_path("popular.tsv".as_ref());
+        assert_eq!(Ok(1), write_csv(&runtime, "popular", &mut written, tsv));
+        assert_eq!("act-logically\tDoe, Jane\n", String::from_utf8(written).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_records() {
+        let mut runtime = MicroRuntime::new(program! { p(?x) <- [q(?x)] });
+        let format = CsvFormat::for_path("q.csv".as_ref());
+        let types = [ColumnType::Str, ColumnType::I64];
+
+        assert_eq!(
+            Err("line 2: column 2: \"ten\" is not a i64".to_string()),
+            load_csv(&mut runtime, "q", "a,-1\nb,ten\n".as_bytes(), format, &types)
+        );
+        assert_eq!(
+            Err("line 1: expected 2 fields, found 3".to_string()),
+            load_csv(&mut runtime, "q", "a,1,2".as_bytes(), format, &types)
+        );
+        assert_eq!(
+            Err("line 1: unterminated quote".to_string()),
+            load_csv(&mut runtime, "q", "\"a,1".as_bytes(), format, &[])
+        );
+        // Ragged records of an untyped file, and records that don't fit the program
+        assert_eq!(
+            Err("r: relation of arity 2 cannot hold a fact of arity 1".to_string()),
+            load_csv(&mut runtime, "r", "a,b\nc\n".as_bytes(), format, &[])
+        );
+        assert_eq!(
+            Err("q: relation of arity 1 cannot hold a fact of arity 2".to_string()),
+            load_csv(&mut runtime, "q", "a\nb,c\n".as_bytes(), format, &[])
+        );
+        // Nothing of any file was inserted
+        assert!(runtime.safe());
+    }
+
+    #[test]
+    fn test_parameters() {
+        let parameters = vec![
+            ("filename".to_string(), "runners.tsv".into()),
+            ("types".to_string(), TypedValue::List(vec!["str".into(), "bool".into()])),
+            ("header".to_string(), true.into()),
+        ];
+        let file = CsvFile::from_parameters("runner", parameters.clone(), true).unwrap();
+        assert_eq!(vec![ColumnType::Str, ColumnType::Bool], file.types);
+        assert_eq!('\t', file.format.delimiter);
+        assert!(file.format.header);
+
+        assert!(CsvFile::from_parameters("runner", parameters, false).is_err());
+        assert!(CsvFile::from_parameters("runner", vec![], true).is_err());
+        let unknown = vec![
+            ("filename".to_string(), "runners.csv".into()),
+            ("types".to_string(), TypedValue::List(vec!["symbol".into()])),
+        ];
+        assert!(CsvFile::from_parameters("runner", unknown, true).is_err());
+    }
+}
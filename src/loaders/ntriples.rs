@@ -0,0 +1,189 @@
+use crate::engine::datalog::MicroRuntime;
+use datalog_syntax::TypedValue;
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+// Terms are kept as strings: IRIs without their angle brackets, blank nodes as `_:label`, and
+// literals exactly as written, quotes, language tag and datatype included, so that a literal can
+// never be mistaken for an IRI.
+fn parse_term<'a>(line: &'a str, rest: &'a str) -> Result<(TypedValue, &'a str), String> {
+    let rest = rest.trim_start();
+
+    if let Some(iri) = rest.strip_prefix('<') {
+        let end = iri
+            .find('>')
+            .ok_or_else(|| format!("unterminated IRI in: {}", line))?;
+
+        return Ok((iri[..end].into(), &iri[end + 1..]));
+    }
+
+    if rest.starts_with("_:") {
+        let end = rest
+            .find(|character: char| character.is_whitespace())
+            .unwrap_or(rest.len());
+
+        return Ok((rest[..end].into(), &rest[end..]));
+    }
+
+    if rest.starts_with('"') {
+        let mut escaped = false;
+        let mut end = None;
+        for (position, character) in rest.char_indices().skip(1) {
+            match character {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    end = Some(position);
+                    break;
+                }
+                _ => escaped = false,
+            }
+        }
+        let mut end = end.ok_or_else(|| format!("unterminated literal in: {}", line))? + 1;
+
+        if rest[end..].starts_with("^^<") {
+            end += rest[end..]
+                .find('>')
+                .ok_or_else(|| format!("unterminated datatype in: {}", line))?
+                + 1;
+        } else if rest[end..].starts_with('@') {
+            end += rest[end..]
+                .find(|character: char| character.is_whitespace() || character == '.')
+                .unwrap_or(rest.len() - end);
+        }
+
+        return Ok((rest[..end].into(), &rest[end..]));
+    }
+
+    Err(format!("unexpected term in: {}", line))
+}
+
+pub fn parse_triple(line: &str) -> Result<Option<[TypedValue; 3]>, String> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return Ok(None);
+    }
+
+    let (subject, rest) = parse_term(line, trimmed)?;
+    let (predicate, rest) = parse_term(line, rest)?;
+    let (object, rest) = parse_term(line, rest)?;
+
+    let rest = rest.trim_start();
+    let Some(rest) = rest.strip_prefix('.') else {
+        return Err(format!("missing terminating dot in: {}", line));
+    };
+    // Only a comment may follow the dot
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected text after the dot in: {}", line));
+    }
+
+    Ok(Some([subject, predicate, object]))
+}
+
+// Inserts every triple as a `relation(subject, predicate, object)` fact, returning how many were
+// read. Nothing is materialised until the next poll.
+pub fn load_ntriples(
+    runtime: &mut MicroRuntime,
+    relation: &str,
+    reader: impl BufRead,
+) -> Result<usize, String> {
+    let mut triple_count = 0;
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| err.to_string())?;
+        let triple = parse_triple(&line)
+            .map_err(|err| format!("line {}: {}", line_number + 1, err))?;
+
+        if let Some(triple) = triple {
+            runtime.try_insert(relation, Vec::from(triple))?;
+            triple_count += 1;
+        }
+    }
+
+    Ok(triple_count)
+}
+
+// Same as load_ntriples, transparently decompressing files ending in `.gz`.
+pub fn load_ntriples_file(
+    runtime: &mut MicroRuntime,
+    relation: &str,
+    path: impl AsRef<Path>,
+) -> Result<usize, String> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        return load_ntriples(
+            runtime,
+            relation,
+            BufReader::new(MultiGzDecoder::new(file)),
+        );
+    }
+
+    load_ntriples(runtime, relation, BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::loaders::ntriples::{load_ntriples, load_ntriples_file, parse_triple};
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const DOCUMENT: &str = r#"
+# A comment
+<http://ex.org/a> <http://ex.org/knows> <http://ex.org/b> .
+_:b0 <http://ex.org/name> "Bob \"the builder\""@en .
+<http://ex.org/b> <http://ex.org/age> "42"^^<http://www.w3.org/2001/XMLSchema#integer> .
+"#;
+
+    #[test]
+    fn test_parse_triple() {
+        assert_eq!(None, parse_triple("   ").unwrap());
+        assert_eq!(
+            Some([
+                "_:b0".into(),
+                "http://ex.org/name".into(),
+                r#""Bob \"the builder\""@en"#.into()
+            ]),
+            parse_triple(r#"_:b0 <http://ex.org/name> "Bob \"the builder\""@en ."#).unwrap()
+        );
+        assert!(parse_triple("<http://ex.org/a> <http://ex.org/b> <http://ex.org/c>").is_err());
+        assert!(parse_triple("<http://ex.org/a> <http://ex.org/b> \"open .").is_err());
+        assert!(parse_triple("<a> <b> <c> . # comment").unwrap().is_some());
+        assert_eq!(
+            Err("unexpected text after the dot in: <a> <b> <c> . <d>".to_string()),
+            parse_triple("<a> <b> <c> . <d>")
+        );
+    }
+
+    #[test]
+    fn test_load_plain_and_gzipped() {
+        let path = std::env::temp_dir().join(format!("act-logically-{}.nt.gz", std::process::id()));
+        let mut encoder = GzEncoder::new(std::fs::File::create(&path).unwrap(), Compression::fast());
+        encoder.write_all(DOCUMENT.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let program = program! { knows(?x, ?y) <- [T(?x, "http://ex.org/knows", ?y)] };
+        let mut plain = MicroRuntime::new(program.clone());
+        let mut gzipped = MicroRuntime::new(program);
+
+        assert_eq!(3, load_ntriples(&mut plain, "T", DOCUMENT.as_bytes()).unwrap());
+        assert_eq!(3, load_ntriples_file(&mut gzipped, "T", &path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        for runtime in [&mut plain, &mut gzipped] {
+            runtime.poll();
+            let knows: Vec<_> = runtime.query(&build_query!(knows(_, _))).unwrap().collect();
+            assert_eq!(
+                vec![vec![TypedValue::from("http://ex.org/a"), "http://ex.org/b".into()]],
+                knows
+            );
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use act_logically::cli::run;
+
+fn main() {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let status = run(
+        &arguments,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+        &mut std::io::stderr(),
+    );
+
+    std::process::exit(status);
+}
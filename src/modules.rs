@@ -0,0 +1,351 @@
+use crate::engine::datalog::MicroRuntime;
+use datalog_syntax::{AnonymousGroundAtom, Atom, Program, Rule};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+pub const NAMESPACE_SEPARATOR: &str = "::";
+
+// What a parameter of a module is instantiated with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Argument {
+    // A relation of the importing program, read as it is
+    Relation(String),
+    // Facts of the instance's own, e.g. a list of toolchains
+    Facts(Vec<AnonymousGroundAtom>),
+}
+
+// A reusable set of rules. Exported relations are the ones importers see, and parameters the ones
+// importers provide. Every other relation is private to each instance, so that instances never
+// collide with each other nor with the importing program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    pub name: String,
+    program: Program,
+    exports: Vec<String>,
+    parameters: Vec<String>,
+}
+
+// A module instantiated within a namespace: its rules over namespaced relations, along with the
+// facts its arguments stand for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instance {
+    pub namespace: String,
+    pub program: Program,
+    pub facts: Vec<(String, AnonymousGroundAtom)>,
+}
+
+impl Module {
+    pub fn new(name: &str, program: Program) -> Self {
+        Self {
+            name: name.to_string(),
+            program,
+            exports: vec![],
+            parameters: vec![],
+        }
+    }
+    pub fn export(mut self, relation: &str) -> Self {
+        self.exports.push(relation.to_string());
+        self
+    }
+    pub fn parameter(mut self, relation: &str) -> Self {
+        self.parameters.push(relation.to_string());
+        self
+    }
+    fn relations(&self) -> HashSet<&str> {
+        self.program
+            .inner
+            .iter()
+            .flat_map(|rule| std::iter::once(&rule.head).chain(rule.body.iter()))
+            .map(|atom| atom.symbol.as_str())
+            .collect()
+    }
+    // Exported relations keep their names, hence instances of modules exporting the same relation
+    // all add to it, e.g. to `job`. Every argument has to be given.
+    pub fn instantiate(
+        &self,
+        namespace: &str,
+        arguments: Vec<(&str, Argument)>,
+    ) -> Result<Instance, String> {
+        let relations = self.relations();
+        let heads: HashSet<_> = self
+            .program
+            .inner
+            .iter()
+            .map(|rule| rule.head.symbol.as_str())
+            .collect();
+        if namespace.is_empty() || namespace.contains(NAMESPACE_SEPARATOR) {
+            return Err(format!("invalid namespace {:?}", namespace));
+        }
+        if let Some(export) = self
+            .exports
+            .iter()
+            .find(|export| !relations.contains(export.as_str()))
+        {
+            return Err(format!("module {} exports unknown relation {}", self.name, export));
+        }
+        if let Some(parameter) = self
+            .parameters
+            .iter()
+            .find(|parameter| heads.contains(parameter.as_str()))
+        {
+            return Err(format!(
+                "parameter {} of module {} is derived by the module itself",
+                parameter, self.name
+            ));
+        }
+
+        let mut arguments: HashMap<_, _> = arguments.into_iter().collect();
+        let mut renames = HashMap::new();
+        let mut facts = vec![];
+        for parameter in &self.parameters {
+            let Some(argument) = arguments.remove(parameter.as_str()) else {
+                return Err(format!(
+                    "missing argument {} to module {}",
+                    parameter, self.name
+                ));
+            };
+
+            match argument {
+                Argument::Relation(relation) => {
+                    renames.insert(parameter.as_str(), relation);
+                }
+                Argument::Facts(parameter_facts) => {
+                    let relation = namespaced(namespace, parameter);
+                    parameter_facts
+                        .into_iter()
+                        .for_each(|fact| facts.push((relation.clone(), fact)));
+                    renames.insert(parameter.as_str(), relation);
+                }
+            }
+        }
+        if let Some(argument) = arguments.keys().next() {
+            return Err(format!(
+                "module {} has no parameter {}",
+                self.name, argument
+            ));
+        }
+
+        let rename = |atom: &Atom| {
+            let symbol = match renames.get(atom.symbol.as_str()) {
+                Some(relation) => relation.clone(),
+                None if self.exports.contains(&atom.symbol) => atom.symbol.clone(),
+                None => namespaced(namespace, &atom.symbol),
+            };
+
+            Atom {
+                symbol,
+                ..atom.clone()
+            }
+        };
+        let rules: Vec<_> = self
+            .program
+            .inner
+            .iter()
+            .map(|rule| Rule {
+                head: rename(&rule.head),
+                body: rule.body.iter().map(rename).collect(),
+                id: 0,
+            })
+            .collect();
+
+        Ok(Instance {
+            namespace: namespace.to_string(),
+            program: Program::from(rules),
+            facts,
+        })
+    }
+}
+
+pub fn namespaced(namespace: &str, relation: &str) -> String {
+    format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, relation)
+}
+
+// Rule packs, e.g. the lint rules, derive `exports` out of the relations they read without
+// deriving them, such as the workflow vocabulary, and keep every other relation to themselves
+// within `namespace`. They can then be added to any program without colliding with its relations.
+pub fn pack(namespace: &str, program: Program, exports: &[&str]) -> Program {
+    let heads: HashSet<_> = program
+        .inner
+        .iter()
+        .map(|rule| rule.head.symbol.clone())
+        .collect();
+    let inputs: BTreeSet<_> = program
+        .inner
+        .iter()
+        .flat_map(|rule| rule.body.iter())
+        .map(|atom| atom.symbol.clone())
+        .filter(|symbol| !heads.contains(symbol))
+        .collect();
+
+    let module = exports
+        .iter()
+        .fold(Module::new(namespace, program), |module, export| module.export(export));
+    let module = inputs
+        .iter()
+        .fold(module, |module, input| module.parameter(input));
+    let arguments = inputs
+        .iter()
+        .map(|input| (input.as_str(), Argument::Relation(input.clone())))
+        .collect();
+
+    // Packs are written along with their exports, hence they are always valid modules
+    module.instantiate(namespace, arguments).unwrap().program
+}
+
+impl Instance {
+    pub fn insert_facts(&self, runtime: &mut MicroRuntime) -> Result<(), String> {
+        for (relation, fact) in &self.facts {
+            runtime.try_insert(relation, fact.clone())?;
+        }
+
+        Ok(())
+    }
+}
+
+// Adds the rules of every instance to `program`. Two instances can't share a namespace.
+pub fn link(program: Program, instances: &[Instance]) -> Result<Program, String> {
+    let mut namespaces = HashSet::new();
+    let mut rules = program.inner;
+    for instance in instances {
+        if !namespaces.insert(instance.namespace.as_str()) {
+            return Err(format!("namespace {} is used twice", instance.namespace));
+        }
+        rules.extend(instance.program.inner.iter().cloned());
+    }
+
+    Ok(Program::from(rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::modules::{link, Argument, Module};
+    use crate::workflows::emitter::emit_workflow;
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+
+    // Tests a crate with every toolchain it is given
+    fn rust_ci() -> Module {
+        Module::new(
+            "rust-ci",
+            program! {
+                job(?f, ?k)                    <- [ci(?f), krate(?k)],
+                runs_on(?f, ?k, "ubuntu-latest") <- [ci(?f), krate(?k)],
+                matrix_axis(?f, ?k, "rust", ?t) <- [ci(?f), krate(?k), toolchain(?t)],
+                step(?f, ?k, 0)                <- [ci(?f), krate(?k)],
+                run(?f, ?k, 0, "cargo test")   <- [tested(?f, ?k)],
+                tested(?f, ?k)                 <- [ci(?f), krate(?k)],
+            },
+        )
+        .export("job")
+        .export("runs_on")
+        .export("matrix_axis")
+        .export("step")
+        .export("run")
+        .parameter("ci")
+        .parameter("krate")
+        .parameter("toolchain")
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let module = rust_ci();
+        let core = module
+            .instantiate(
+                "core",
+                vec![
+                    ("ci", Argument::Relation("ci".to_string())),
+                    ("krate", Argument::Facts(vec![vec!["core".into()]])),
+                    (
+                        "toolchain",
+                        Argument::Facts(vec![vec!["stable".into()], vec!["1.70".into()]]),
+                    ),
+                ],
+            )
+            .unwrap();
+        let cli = module
+            .instantiate(
+                "cli",
+                vec![
+                    ("ci", Argument::Relation("ci".to_string())),
+                    ("krate", Argument::Facts(vec![vec!["cli".into()]])),
+                    ("toolchain", Argument::Relation("toolchain".to_string())),
+                ],
+            )
+            .unwrap();
+        assert!(core
+            .program
+            .inner
+            .iter()
+            .any(|rule| rule.head.symbol == "core::tested"));
+
+        // The importer has a tested relation of its own, which the instances don't add to
+        let program = link(
+            program! {
+                workflow(?f)              <- [ci(?f)],
+                on_trigger(?f, "push")    <- [ci(?f)],
+                tested(?f, ?k)            <- [ci(?f), krate(?k)],
+            },
+            &[core.clone(), cli.clone()],
+        )
+        .unwrap();
+        let mut runtime = MicroRuntime::new(program);
+        core.insert_facts(&mut runtime).unwrap();
+        cli.insert_facts(&mut runtime).unwrap();
+        runtime.insert("ci", vec!["ci.yml".into()]);
+        runtime.insert("toolchain", vec!["nightly".into()]);
+        runtime.poll();
+
+        assert_eq!(0, runtime.query(&build_query!(tested(_, _))).unwrap().count());
+        let expected = r#"on:
+  push:
+jobs:
+  cli:
+    runs-on: ubuntu-latest
+    strategy:
+      matrix:
+        rust:
+          - nightly
+    steps:
+      - run: cargo test
+  core:
+    runs-on: ubuntu-latest
+    strategy:
+      matrix:
+        rust:
+          - "1.70"
+          - stable
+    steps:
+      - run: cargo test
+"#;
+        assert_eq!(expected, emit_workflow(&runtime, "ci.yml").unwrap());
+
+        assert!(link(program! { p(?x) <- [q(?x)] }, &[core.clone(), core]).is_err());
+    }
+
+    #[test]
+    fn test_instantiate_checks_arguments() {
+        let module = rust_ci();
+        let arguments = || {
+            vec![
+                ("ci", Argument::Relation("ci".to_string())),
+                ("krate", Argument::Relation("krate".to_string())),
+            ]
+        };
+
+        assert_eq!(
+            Err("missing argument toolchain to module rust-ci".to_string()),
+            module.instantiate("core", arguments())
+        );
+
+        let mut extra_arguments = arguments();
+        extra_arguments.push(("toolchain", Argument::Facts(vec![])));
+        extra_arguments.push(("os", Argument::Facts(vec![])));
+        assert_eq!(
+            Err("module rust-ci has no parameter os".to_string()),
+            module.instantiate("core", extra_arguments)
+        );
+
+        assert!(module.clone().export("nothing").instantiate("core", arguments()).is_err());
+        assert!(module.parameter("tested").instantiate("core", arguments()).is_err());
+    }
+}
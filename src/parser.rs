@@ -0,0 +1,765 @@
+use crate::loaders::csv::CsvFile;
+use datalog_syntax::{AnonymousGroundAtom, Atom, Matcher, Rule, SkolemFunction, Term, TypedValue};
+
+// The textual counterpart of the rule! and program! macros, for programs that live on disk:
+//
+//     // Comments run until the end of the line
+//     e("a", "b").
+//     tc(?x, ?y) <- [e(?x, ?y)].
+//     sink(?x)   <- [node(?x), !e(?x, ?y)].
+//
+// Statements end with a dot, and atoms without a body are facts. Constants are written the way
+// TypedValue's Debug writes them: integers are usize unless negative, and then i64, besides floats
+// such as 1.5 or 2e-3, byte strings such as b"\x00" and lists such as [1, "a"]. Heads may call
+// functions by name, as in `id(label(?x, ?y)) <- [e(?x, ?y)].`, which are left unresolved until
+// bound to Rust functions by a FunctionRegistry. Relations may be read from and written to CSV
+// files, see CsvFile:
+//
+//     .input repository(filename="repos.csv", types=["str", "int"]).
+//     .output owner(filename="owners.csv").
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Identifier(String),
+    Variable(String),
+    Constant(TypedValue),
+    LeftParenthesis,
+    RightParenthesis,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Dot,
+    Bang,
+    Arrow,
+    Equals,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Identifier(name) => name.clone(),
+            Token::Variable(name) => format!("?{}", name),
+            Token::Constant(value) => format!("{:?}", value),
+            Token::LeftParenthesis => "(".to_string(),
+            Token::RightParenthesis => ")".to_string(),
+            Token::LeftBracket => "[".to_string(),
+            Token::RightBracket => "]".to_string(),
+            Token::Comma => ",".to_string(),
+            Token::Dot => ".".to_string(),
+            Token::Bang => "!".to_string(),
+            Token::Arrow => "<-".to_string(),
+            Token::Equals => "=".to_string(),
+        }
+    }
+}
+
+// (line, column), both starting at 1
+type Position = (usize, usize);
+
+fn is_identifier_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, Position)>, String> {
+    let mut tokens = vec![];
+    let mut characters = source.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+
+    macro_rules! next {
+        () => {{
+            let character = characters.next();
+            if character == Some('\n') {
+                line += 1;
+                column = 1;
+            } else if character.is_some() {
+                column += 1;
+            }
+            character
+        }};
+    }
+
+    while let Some(&character) = characters.peek() {
+        let position = (line, column);
+
+        let token = match character {
+            character if character.is_whitespace() => {
+                next!();
+                continue;
+            }
+            '/' => {
+                next!();
+                if next!() != Some('/') {
+                    return Err(format!("{}:{}: expected a comment", position.0, position.1));
+                }
+                while !matches!(characters.peek(), Some('\n') | None) {
+                    next!();
+                }
+                continue;
+            }
+            '(' | ')' | '[' | ']' | ',' | '.' | '!' | '=' => {
+                next!();
+                match character {
+                    '(' => Token::LeftParenthesis,
+                    ')' => Token::RightParenthesis,
+                    '[' => Token::LeftBracket,
+                    ']' => Token::RightBracket,
+                    ',' => Token::Comma,
+                    '.' => Token::Dot,
+                    '!' => Token::Bang,
+                    _ => Token::Equals,
+                }
+            }
+            '<' => {
+                next!();
+                if next!() != Some('-') {
+                    return Err(format!("{}:{}: expected <-", position.0, position.1));
+                }
+                Token::Arrow
+            }
+            '?' => {
+                next!();
+                let mut name = String::new();
+                while characters.peek().is_some_and(|c| is_identifier_character(*c)) {
+                    name.push(next!().unwrap());
+                }
+                if name.is_empty() {
+                    return Err(format!("{}:{}: expected a variable name", position.0, position.1));
+                }
+                Token::Variable(name)
+            }
+            '"' | 'b'
+                if character == '"' || {
+                    let mut lookahead = characters.clone();
+                    lookahead.next();
+                    lookahead.next() == Some('"')
+                } =>
+            {
+                // b"..." are bytes, which can hold any byte as \xff
+                let bytes = character == 'b';
+                if bytes {
+                    next!();
+                }
+                next!();
+                let invalid_escape =
+                    || format!("{}:{}: invalid escape sequence", position.0, position.1);
+                let mut value: Vec<u8> = vec![];
+                loop {
+                    match next!() {
+                        Some('"') => break,
+                        Some('\\') => match next!() {
+                            Some('n') => value.push(b'\n'),
+                            Some('t') => value.push(b'\t'),
+                            Some('r') => value.push(b'\r'),
+                            Some(escaped @ ('"' | '\\')) => value.push(escaped as u8),
+                            Some('\'') if bytes => value.push(b'\''),
+                            Some('x') if bytes => {
+                                let digits: String =
+                                    [next!(), next!()].into_iter().flatten().collect();
+                                let byte = u8::from_str_radix(&digits, 16)
+                                    .ok()
+                                    .filter(|_| digits.len() == 2)
+                                    .ok_or_else(invalid_escape)?;
+                                value.push(byte);
+                            }
+                            _ => return Err(invalid_escape()),
+                        },
+                        Some(character) => {
+                            value.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes())
+                        }
+                        None => {
+                            return Err(format!(
+                                "{}:{}: unterminated string",
+                                position.0, position.1
+                            ))
+                        }
+                    }
+                }
+                match bytes {
+                    true => Token::Constant(TypedValue::Bytes(value)),
+                    // Only characters and ASCII escapes were pushed
+                    false => Token::Constant(TypedValue::Str(String::from_utf8(value).unwrap())),
+                }
+            }
+            character
+                if character.is_ascii_digit()
+                    || character == '-' && {
+                        let mut lookahead = characters.clone();
+                        lookahead.next();
+                        lookahead.next().is_some_and(|c| c.is_ascii_digit())
+                    } =>
+            {
+                let mut text = String::new();
+                if character == '-' {
+                    text.push(next!().unwrap());
+                }
+                macro_rules! digits {
+                    () => {
+                        while characters.peek().is_some_and(|c| c.is_ascii_digit()) {
+                            text.push(next!().unwrap());
+                        }
+                    };
+                }
+                digits!();
+
+                // A dot ends the statement unless a digit follows it
+                let mut float = false;
+                let mut lookahead = characters.clone();
+                if lookahead.next() == Some('.')
+                    && lookahead.next().is_some_and(|c| c.is_ascii_digit())
+                {
+                    float = true;
+                    text.push(next!().unwrap());
+                    digits!();
+                }
+                let mut lookahead = characters.clone();
+                if matches!(lookahead.next(), Some('e' | 'E')) {
+                    let mut after = lookahead.clone();
+                    if matches!(after.next(), Some('-' | '+')) {
+                        lookahead = after;
+                    }
+                    if lookahead.next().is_some_and(|c| c.is_ascii_digit()) {
+                        float = true;
+                        text.push(next!().unwrap());
+                        if matches!(characters.peek(), Some('-' | '+')) {
+                            text.push(next!().unwrap());
+                        }
+                        digits!();
+                    }
+                }
+
+                let invalid =
+                    |err: &dyn std::fmt::Display| format!("{}:{}: {}", position.0, position.1, err);
+                let value = if float {
+                    TypedValue::from(text.parse::<f64>().map_err(|err| invalid(&err))?)
+                } else if character == '-' {
+                    TypedValue::I64(text.parse::<i64>().map_err(|err| invalid(&err))?)
+                } else {
+                    TypedValue::Int(text.parse::<usize>().map_err(|err| invalid(&err))?)
+                };
+                Token::Constant(value)
+            }
+            character if is_identifier_character(character) => {
+                let mut name = String::new();
+                loop {
+                    while characters.peek().is_some_and(|c| is_identifier_character(*c)) {
+                        name.push(next!().unwrap());
+                    }
+                    // Relations of module instances are namespaced, e.g. `ci::toolchain`
+                    let mut lookahead = characters.clone();
+                    if lookahead.next() != Some(':')
+                        || lookahead.next() != Some(':')
+                        || !lookahead.next().is_some_and(is_identifier_character)
+                    {
+                        break;
+                    }
+                    next!();
+                    next!();
+                    name.push_str("::");
+                }
+                match name.as_str() {
+                    "true" => Token::Constant(TypedValue::Bool(true)),
+                    "false" => Token::Constant(TypedValue::Bool(false)),
+                    _ => Token::Identifier(name),
+                }
+            }
+            character => {
+                return Err(format!(
+                    "{}:{}: unexpected character {:?}",
+                    position.0, position.1, character
+                ))
+            }
+        };
+
+        tokens.push((token, position));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, Position)>,
+    index: usize,
+    end: Position,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let lines = source.split('\n').collect::<Vec<_>>();
+        let end = (lines.len(), lines.last().map_or(0, |line| line.chars().count()) + 1);
+
+        Ok(Self {
+            tokens,
+            index: 0,
+            end,
+        })
+    }
+
+    fn is_done(&self) -> bool {
+        self.index == self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.tokens.get(self.index) {
+            Some((token, (line, column))) => format!(
+                "{}:{}: expected {}, found {}",
+                line,
+                column,
+                expected,
+                token.describe()
+            ),
+            None => format!(
+                "{}:{}: expected {}, found the end of the input",
+                self.end.0, self.end.1, expected
+            ),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        if self.peek() != Some(&expected) {
+            return Err(self.error(&expected.describe()));
+        }
+        self.index += 1;
+
+        Ok(())
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        let found = self.peek() == Some(&token);
+        if found {
+            self.index += 1;
+        }
+
+        found
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        match self.peek().cloned() {
+            Some(Token::Identifier(name)) => {
+                self.index += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("a relation name")),
+        }
+    }
+
+    // Parses `symbol(term, ...)`, where terms are read by `term`.
+    fn atom_with<T>(
+        &mut self,
+        mut term: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<(String, Vec<T>), String> {
+        let symbol = self.identifier()?;
+        self.expect(Token::LeftParenthesis)?;
+
+        let mut terms = vec![];
+        while !self.eat(Token::RightParenthesis) {
+            terms.push(term(self)?);
+            if !self.eat(Token::Comma) {
+                self.expect(Token::RightParenthesis)?;
+                break;
+            }
+        }
+
+        Ok((symbol, terms))
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        match self.peek().cloned() {
+            Some(Token::Variable(name)) => {
+                self.index += 1;
+                Ok(Term::Variable(name))
+            }
+            Some(Token::Constant(_) | Token::LeftBracket) => self.constant().map(Term::Constant),
+            _ => Err(self.error("a variable or a constant")),
+        }
+    }
+
+    // Heads may call functions as well, e.g. `label(?x, ?y)`.
+    fn head_term(&mut self) -> Result<Term, String> {
+        match self.peek() {
+            Some(Token::Identifier(_)) => {
+                let (name, deps) = self.atom_with(Self::variable)?;
+
+                Ok(Term::Skolemizer(SkolemFunction::unresolved(&name, deps)))
+            }
+            _ => self.term(),
+        }
+    }
+
+    fn variable(&mut self) -> Result<String, String> {
+        match self.peek().cloned() {
+            Some(Token::Variable(name)) => {
+                self.index += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("a variable")),
+        }
+    }
+
+    // Lists are written as `[1, "a", []]`.
+    fn constant(&mut self) -> Result<TypedValue, String> {
+        match self.peek().cloned() {
+            Some(Token::Constant(value)) => {
+                self.index += 1;
+                Ok(value)
+            }
+            Some(Token::LeftBracket) => {
+                self.index += 1;
+                let mut items = vec![];
+                while !self.eat(Token::RightBracket) {
+                    items.push(self.constant()?);
+                    if !self.eat(Token::Comma) {
+                        self.expect(Token::RightBracket)?;
+                        break;
+                    }
+                }
+                Ok(TypedValue::List(items))
+            }
+            _ => Err(self.error("a constant")),
+        }
+    }
+
+    fn matcher(&mut self) -> Result<Matcher, String> {
+        if self.peek() == Some(&Token::Identifier("_".to_string())) {
+            self.index += 1;
+            return Ok(Matcher::Any);
+        }
+
+        self.constant()
+            .map(Matcher::Constant)
+            .map_err(|_| self.error("_ or a constant"))
+    }
+
+    fn atom(&mut self, term: fn(&mut Self) -> Result<Term, String>) -> Result<Atom, String> {
+        let negated = self.eat(Token::Bang);
+        let (symbol, terms) = self.atom_with(term)?;
+
+        Ok(Atom {
+            terms,
+            symbol,
+            negated,
+        })
+    }
+
+    // Parses `name=constant`.
+    fn parameter(&mut self) -> Result<(String, TypedValue), String> {
+        let name = match self.peek().cloned() {
+            Some(Token::Identifier(name)) => {
+                self.index += 1;
+                name
+            }
+            _ => return Err(self.error("a parameter name")),
+        };
+        self.expect(Token::Equals)?;
+
+        Ok((name, self.constant()?))
+    }
+
+    // Parses `.input relation(parameter=value, ...).` or `.output ...`.
+    fn directive(&mut self) -> Result<Statement, String> {
+        let (line, column) = self.tokens[self.index].1;
+        self.expect(Token::Dot)?;
+        let input = match self.peek() {
+            Some(Token::Identifier(name)) if name == "input" => true,
+            Some(Token::Identifier(name)) if name == "output" => false,
+            _ => return Err(self.error("input or output")),
+        };
+        self.index += 1;
+
+        let (relation, parameters) = self.atom_with(Self::parameter)?;
+        self.expect(Token::Dot)?;
+        let file = CsvFile::from_parameters(&relation, parameters, input)
+            .map_err(|err| format!("{}:{}: {}", line, column, err))?;
+
+        Ok(match input {
+            true => Statement::Input(file),
+            false => Statement::Output(file),
+        })
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.peek() == Some(&Token::Dot) {
+            return self.directive();
+        }
+
+        let (line, column) = self.tokens[self.index].1;
+        let head = self.atom(Self::head_term)?;
+        if head.negated {
+            return Err(format!("{}:{}: the head cannot be negated", line, column));
+        }
+
+        if !self.eat(Token::Arrow) {
+            self.expect(Token::Dot)?;
+
+            let fact = head
+                .terms
+                .into_iter()
+                .map(|term| match term {
+                    Term::Constant(value) => Ok(value),
+                    _ => Err(format!("{}:{}: facts cannot have variables", line, column)),
+                })
+                .collect::<Result<_, _>>()?;
+
+            return Ok(Statement::Fact(head.symbol, fact));
+        }
+
+        self.expect(Token::LeftBracket)?;
+        let mut body = vec![];
+        while !self.eat(Token::RightBracket) {
+            body.push(self.atom(Self::term)?);
+            if !self.eat(Token::Comma) {
+                self.expect(Token::RightBracket)?;
+                break;
+            }
+        }
+        self.expect(Token::Dot)?;
+
+        Ok(Statement::Rule(Rule { head, body, id: 0 }))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    Rule(Rule),
+    Fact(String, AnonymousGroundAtom),
+    Input(CsvFile),
+    Output(CsvFile),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Source {
+    pub rules: Vec<Rule>,
+    pub facts: Vec<(String, AnonymousGroundAtom)>,
+    // Files whose records are facts, and files relations are written to once materialised
+    pub inputs: Vec<CsvFile>,
+    pub outputs: Vec<CsvFile>,
+}
+
+pub fn parse_statements(source: &str) -> Result<Vec<Statement>, String> {
+    let mut parser = Parser::new(source)?;
+    let mut statements = vec![];
+    while !parser.is_done() {
+        statements.push(parser.statement()?);
+    }
+
+    Ok(statements)
+}
+
+pub fn parse_source(source: &str) -> Result<Source, String> {
+    let mut parsed = Source::default();
+    parse_statements(source)?
+        .into_iter()
+        .for_each(|statement| match statement {
+            Statement::Rule(rule) => parsed.rules.push(rule),
+            Statement::Fact(relation, fact) => parsed.facts.push((relation, fact)),
+            Statement::Input(file) => parsed.inputs.push(file),
+            Statement::Output(file) => parsed.outputs.push(file),
+        });
+
+    Ok(parsed)
+}
+
+fn parse_whole<T>(
+    text: &str,
+    parse: impl FnOnce(&mut Parser) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut parser = Parser::new(text)?;
+    let parsed = parse(&mut parser)?;
+    // A trailing dot is tolerated, so that facts can be copied as they are.
+    parser.eat(Token::Dot);
+    if !parser.is_done() {
+        return Err(parser.error("the end of the input"));
+    }
+
+    Ok(parsed)
+}
+
+// Parses a query such as `tc("a", _)`, returning its relation and matchers.
+pub fn parse_query(text: &str) -> Result<(String, Vec<Matcher>), String> {
+    parse_whole(text, |parser| parser.atom_with(Parser::matcher))
+}
+
+// Parses a ground atom such as `tc("a", "b")`.
+pub fn parse_fact(text: &str) -> Result<(String, AnonymousGroundAtom), String> {
+    parse_whole(text, |parser| parser.atom_with(Parser::constant))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loaders::csv::{ColumnType, CsvFile};
+    use crate::parser::{parse_fact, parse_query, parse_source};
+    use datalog_rule_macro::{program, rule};
+    use datalog_syntax::*;
+
+    #[test]
+    fn test_parse_source() {
+        let source = r#"
+            // Edges
+            e("a", "b"). e("b", "c\n").
+            weight("a", 42, true).
+            tc(?x, ?y) <- [e(?x, ?y)].
+            tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z),].
+            sink(?x)   <- [node(?x), !e(?x, ?y)].
+        "#;
+
+        let parsed = parse_source(source).unwrap();
+
+        assert_eq!(
+            vec![
+                ("e".to_string(), vec!["a".into(), "b".into()]),
+                ("e".to_string(), vec!["b".into(), "c\n".into()]),
+                ("weight".to_string(), vec!["a".into(), 42usize.into(), true.into()]),
+            ],
+            parsed.facts
+        );
+        assert_eq!(
+            vec![
+                rule! { tc(?x, ?y) <- [e(?x, ?y)] },
+                rule! { tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z)] },
+                rule! { sink(?x) <- [node(?x), !e(?x, ?y)] },
+            ],
+            parsed.rules
+        );
+    }
+
+    #[test]
+    fn test_parse_functions() {
+        fn label(args: std::collections::HashMap<&str, &TypedValue>) -> TypedValue {
+            format!("{}-{}", args["x"], args["y"]).into()
+        }
+
+        let parsed = parse_source("id(?x, label(?x, ?y)) <- [e(?x, ?y)].").unwrap();
+        let program = Program::from(parsed.rules);
+        assert_eq!(program! { id(?x, label(?x, ?y)) <- [e(?x, ?y)] }, program);
+        assert_eq!("id(x, label(x, y)) <- [e(x, y)]", program.inner[0].to_string());
+
+        let mut functions = FunctionRegistry::default();
+        assert_eq!(
+            Err("unknown function label".to_string()),
+            functions.resolve(program.clone())
+        );
+        functions.register("label", label);
+        let resolved = functions.resolve(program).unwrap();
+        let Term::Skolemizer(function) = &resolved.inner[0].head.terms[1] else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            Ok(TypedValue::from("a-b")),
+            function.call([("x", &"a".into()), ("y", &"b".into())].into())
+        );
+
+        assert_eq!(
+            Err("1:14: expected a variable, found \"a\"".to_string()),
+            parse_source("id(?x, label(\"a\")) <- [e(?x)].").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Err("2:18: expected ., found the end of the input".to_string()),
+            parse_source("e(\"a\").\ntc(?x) <- [e(?x)]").map(|_| ())
+        );
+        assert_eq!(
+            Err("1:1: facts cannot have variables".to_string()),
+            parse_source("e(?x).").map(|_| ())
+        );
+        assert_eq!(
+            Err("1:3: unterminated string".to_string()),
+            parse_source("e(\"a).").map(|_| ())
+        );
+        assert_eq!(
+            Err("1:13: expected a variable or a constant, found f".to_string()),
+            parse_source("p(?x) <- [q(f(?x))].").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_parse_query_and_fact() {
+        let (symbol, matchers) = parse_query("tc(\"a\", _)").unwrap();
+        assert_eq!("tc", symbol);
+        assert!(matches!(
+            matchers.as_slice(),
+            [Matcher::Constant(TypedValue::Str(a)), Matcher::Any] if a == "a"
+        ));
+        assert!(parse_query("tc(?x, _)").is_err());
+
+        assert_eq!(
+            ("tc".to_string(), vec!["a".into(), 3usize.into()]),
+            parse_fact("tc(\"a\", 3).").unwrap()
+        );
+        assert_eq!(
+            ("ci::toolchain".to_string(), vec!["stable".into()]),
+            parse_fact("ci::toolchain(\"stable\")").unwrap()
+        );
+        assert!(parse_fact("ci:toolchain(\"stable\")").is_err());
+        assert!(parse_fact("tc(\"a\", _)").is_err());
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let values: Vec<TypedValue> = vec![
+            (-3i64).into(),
+            1.5.into(),
+            (-0.25).into(),
+            2e-30.into(),
+            1e300.into(),
+            b"\x00\"bytes\"\n\xff".as_slice().into(),
+            TypedValue::List(vec![
+                1usize.into(),
+                "a".into(),
+                TypedValue::List(vec![]),
+                (-1i64).into(),
+            ]),
+        ];
+        let fact = format!(
+            "v({}).",
+            values.iter().map(|value| format!("{:?}", value)).collect::<Vec<_>>().join(", ")
+        );
+
+        assert_eq!(("v".to_string(), values), parse_fact(&fact).unwrap());
+        assert_eq!(
+            rule! { p(?x, [1, -2]) <- [q(?x, -1.5, b"a")] },
+            parse_source("p(?x, [1, -2]) <- [q(?x, -1.5, b\"a\")].").unwrap().rules[0]
+        );
+        assert_eq!(2, parse_source("e(1).e(2).").unwrap().facts.len());
+        assert!(parse_fact("e(b\"\\xg0\")").is_err());
+        assert!(parse_fact("e([1, 2)").is_err());
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        let source = r#"
+            .input repository(filename="repos.tsv", types=["str", "int"], header=true).
+            .output owner(filename="owners.csv", delimiter=";").
+            owner(?o) <- [repository(?o, ?s)].
+        "#;
+        let parsed = parse_source(source).unwrap();
+
+        let mut input = CsvFile::new("repository", "repos.tsv");
+        input.types = vec![ColumnType::Str, ColumnType::Int];
+        input.format.header = true;
+        assert_eq!(vec![input], parsed.inputs);
+        let mut output = CsvFile::new("owner", "owners.csv");
+        output.format.delimiter = ';';
+        assert_eq!(vec![output], parsed.outputs);
+        assert_eq!(1, parsed.rules.len());
+
+        assert_eq!(
+            Err("1:2: expected input or output, found e".to_string()),
+            parse_source(".e(filename=\"e.csv\").")
+        );
+        assert_eq!(
+            Err("1:1: invalid parameter header=true of e".to_string()),
+            parse_source(".output e(filename=\"e.csv\", header=true).")
+        );
+        assert_eq!(
+            Err("1:19: expected =, found \"e.csv\"".to_string()),
+            parse_source(".input e(filename \"e.csv\").")
+        );
+    }
+}
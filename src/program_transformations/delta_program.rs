@@ -27,7 +27,12 @@ pub fn make_delta_program(program: &Program, update: bool) -> Program {
             delta_rules_set.insert(delta_rule);
         } else {
             // Otherwise, consider each body atom and deltaify if necessary.
+            // Negated atoms always refer to complete relations of earlier strata, hence they never
+            // have deltas of their own.
             for (index, body_atom) in rule.body.iter().enumerate() {
+                if body_atom.negated {
+                    continue;
+                }
                 if update || idb_relation_symbols.contains(&body_atom.symbol) {
                     let mut new_rule = delta_rule.clone();
                     add_prefix(&mut new_rule.body[index].symbol, DELTA_PREFIX);
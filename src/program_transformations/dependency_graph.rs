@@ -1,45 +1,159 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use datalog_syntax::{Program, Rule};
-use petgraph::{algo, Directed};
-use petgraph::graphmap::{DiGraphMap, GraphMap};
+use petgraph::algo;
+use petgraph::graphmap::DiGraphMap;
+use petgraph::Direction;
 
-type RuleGraph<'a> = GraphMap<&'a Rule, bool, Directed>;
+// Every body atom adds an edge from its relation to the head's, labelled with whether it is negated.
+type RelationGraph<'a> = DiGraphMap<&'a str, bool>;
 
-pub fn generate_rule_dependency_graph<'a>(program: &Vec<Rule>) -> RuleGraph {
+pub fn generate_relation_dependency_graph(program: &Program) -> RelationGraph<'_> {
     let mut output = DiGraphMap::new();
-    let mut idb_relations = HashMap::new();
-    for rule in program {
-        idb_relations.insert(rule.clone().head.symbol, rule);
-        output.add_node(rule);
-    }
-    for rule in program {
+    for rule in &program.inner {
+        let head_symbol = rule.head.symbol.as_str();
+        output.add_node(head_symbol);
+
         for body_atom in &rule.body {
-            if let Some(body_atom_rule) = idb_relations.get(&body_atom.symbol) {
-                output.add_edge(body_atom_rule, rule, true);
-            }
+            let body_symbol = body_atom.symbol.as_str();
+            let negated = body_atom.negated
+                || output
+                    .edge_weight(body_symbol, head_symbol)
+                    .is_some_and(|negated| *negated);
+
+            output.add_edge(body_symbol, head_symbol, negated);
         }
     }
-    return output;
+    output
 }
 
-pub fn stratify<'a>(rule_graph: &'a RuleGraph) -> Vec<Vec<&'a Rule>> {
-    let sccs = algo::kosaraju_scc(&rule_graph);
+// Relations that negated atoms read, directly or through the rules deriving them. Only new facts
+// of these can withdraw conclusions.
+pub fn negation_dependencies(program: &Program) -> HashSet<String> {
+    let relation_graph = generate_relation_dependency_graph(program);
+    let mut pending: Vec<_> = relation_graph
+        .all_edges()
+        .filter(|(_, _, negated)| **negated)
+        .map(|(body_symbol, _, _)| body_symbol)
+        .collect();
+
+    let mut dependencies = HashSet::new();
+    while let Some(symbol) = pending.pop() {
+        if dependencies.insert(symbol.to_string()) {
+            pending.extend(relation_graph.neighbors_directed(symbol, Direction::Incoming));
+        }
+    }
 
-    return sccs;
+    dependencies
 }
 
-pub fn sort_program(program: Program) -> Program {
-    let rule_graph = generate_rule_dependency_graph(&program.inner);
-    let stratification = stratify(&rule_graph)
+// Splits the program into strata, in evaluation order, such that a relation is only ever negated
+// once the stratum computing it is done. Recursion through negation has no such order.
+pub fn stratify(program: &Program) -> Result<Vec<Program>, String> {
+    let relation_graph = generate_relation_dependency_graph(program);
+    let components = algo::kosaraju_scc(&relation_graph);
+
+    let mut component_of = HashMap::new();
+    components
+        .iter()
+        .enumerate()
+        .for_each(|(index, component)| {
+            component.iter().for_each(|symbol| {
+                component_of.insert(*symbol, index);
+            })
+        });
+
+    for (body_symbol, head_symbol, negated) in relation_graph.all_edges() {
+        if *negated && component_of[body_symbol] == component_of[head_symbol] {
+            return Err(format!(
+                "{} depends on the negation of {} recursively, hence the program can't be stratified",
+                head_symbol, body_symbol
+            ));
+        }
+    }
+
+    // Components come in reverse topological order
+    Ok(components
         .into_iter()
         .rev()
-        .flat_map( | program| {
-            program
+        .map(|component| {
+            let rules: Vec<Rule> = program
+                .inner
+                .iter()
+                .filter(|rule| component.contains(&rule.head.symbol.as_str()))
+                .cloned()
+                .collect();
+
+            Program::from(rules)
         })
-        .cloned()
-        .collect();
+        .filter(|stratum| !stratum.inner.is_empty())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program_transformations::dependency_graph::{negation_dependencies, stratify};
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+    use std::collections::HashSet;
+
+    // Recursive and nonrecursive rules of a relation are evaluated together, in one stratum
+    #[test]
+    fn test_stratify_positive_program() {
+        let program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            tc(? x, ?z) <- [e(? x, ?y), tc(? y, ?z)]
+        };
+
+        let expected_program = program! {
+            tc(?x, ?y) <- [e(?x, ?y)],
+            tc(?x, ?z) <- [e(?x, ?y), tc(?y, ?z)]
+        };
+
+        assert_eq!(vec![expected_program], stratify(&program).unwrap());
+        assert!(negation_dependencies(&program).is_empty());
+    }
+
+    #[test]
+    fn test_stratify() {
+        let program = program! {
+            unreachable(?x) <- [node(?x), !reachable(?x)],
+            reachable(?y)   <- [reachable(?x), e(?x, ?y)],
+            reachable(?x)   <- [root(?x)],
+        };
+
+        let strata = stratify(&program).unwrap();
+
+        assert_eq!(
+            vec![
+                program! {
+                    reachable(?y) <- [reachable(?x), e(?x, ?y)],
+                    reachable(?x) <- [root(?x)],
+                },
+                program! { unreachable(?x) <- [node(?x), !reachable(?x)] },
+            ],
+            strata
+        );
+    }
+
+    #[test]
+    fn test_stratify_rejects_recursion_through_negation() {
+        let program = program! {
+            wins(?x) <- [edge(?x, ?y), !wins(?y)],
+        };
+
+        assert!(stratify(&program).is_err());
+    }
+
+    #[test]
+    fn test_negation_dependencies() {
+        let program = program! {
+            reachable(?x)   <- [root(?x)],
+            reachable(?y)   <- [reachable(?x), e(?x, ?y)],
+            unreachable(?x) <- [node(?x), !reachable(?x)],
+            report(?x)      <- [unreachable(?x), label(?x, ?l)],
+        };
 
-    return Program {
-        inner: stratification
+        let expected: HashSet<_> = ["reachable", "root", "e"].map(String::from).into();
+        assert_eq!(expected, negation_dependencies(&program));
     }
 }
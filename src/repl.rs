@@ -0,0 +1,281 @@
+use crate::engine::datalog::MicroRuntime;
+use crate::engine::external::ExternalRelations;
+use crate::engine::fact_storage::StorageLayout;
+use crate::loaders::csv::CsvFile;
+use crate::engine::explanation::{explain, format_fact};
+use crate::parser::{parse_fact, parse_query, parse_source, Source};
+use crate::souffle::parse_souffle;
+use datalog_syntax::{AnonymousGroundAtom, FunctionRegistry, Program, Query, Rule};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+const HELP: &str = "Statements such as `e(\"a\", \"b\").` or `tc(?x, ?y) <- [e(?x, ?y)].` are added as they are.
+Files declared by `.input e(filename=\"e.csv\").` are read, and `.output` ones written on :poll.
+:load FILE       adds the rules and facts of a .dl file, or of a Soufflé .souffle one
+:poll            materialises what was added since the last poll
+:query QUERY     lists the facts matching e.g. tc(\"a\", _)
+:explain FACT    prints the derivation of a fact
+:relations       lists every relation with its number of facts
+:rules           lists the rules of the program
+:help            prints this message
+:quit            leaves";
+
+// A runtime whose program can grow. Rules can't be added to a MicroRuntime, hence every rule
+// rebuilds it out of the facts inserted so far, which are then pending until the next poll.
+pub struct Repl {
+    rules: Vec<Rule>,
+    facts: Vec<(String, AnonymousGroundAtom)>,
+    runtime: MicroRuntime,
+    // What the functions called by rules are resolved with
+    functions: FunctionRegistry,
+    // The string built-ins, which rule bodies may call
+    externals: ExternalRelations,
+    // Written after every poll
+    outputs: Vec<CsvFile>,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::with_functions(FunctionRegistry::default())
+    }
+}
+
+impl Repl {
+    pub fn with_functions(functions: FunctionRegistry) -> Self {
+        Self {
+            rules: vec![],
+            facts: vec![],
+            runtime: MicroRuntime::new(Program::from(vec![])),
+            functions,
+            externals: ExternalRelations::builtins(),
+            outputs: vec![],
+        }
+    }
+    pub fn runtime(&self) -> &MicroRuntime {
+        &self.runtime
+    }
+    // Adds the rules and facts of `source`, along with the facts of its inputs. Nothing is added if
+    // the rules would make the program invalid or an input can't be read.
+    pub fn add(&mut self, mut source: Source) -> Result<(), String> {
+        for input in &source.inputs {
+            let facts = input.read()?;
+            source
+                .facts
+                .extend(facts.into_iter().map(|fact| (input.relation.clone(), fact)));
+        }
+
+        if !source.rules.is_empty() {
+            let mut rules = self.rules.clone();
+            for rule in source.rules {
+                rules.push(self.functions.resolve_rule(rule)?);
+            }
+            let mut runtime = MicroRuntime::try_with_externals(
+                Program::from(rules.clone()),
+                StorageLayout::default(),
+                self.externals.clone(),
+            )?;
+            for (relation, fact) in &self.facts {
+                runtime.try_insert(relation, fact.clone())?;
+            }
+
+            self.rules = rules;
+            self.runtime = runtime;
+        }
+
+        for (relation, fact) in source.facts {
+            self.runtime.try_insert(&relation, fact.clone())?;
+            self.facts.push((relation, fact));
+        }
+        self.outputs.extend(source.outputs);
+
+        Ok(())
+    }
+    pub fn poll(&mut self) -> Result<(), String> {
+        self.runtime.try_poll()?;
+        for output in &self.outputs {
+            output.write(&self.runtime)?;
+        }
+
+        Ok(())
+    }
+    // Files the source declares are relative to it.
+    pub fn load(&mut self, path: &str) -> Result<(), String> {
+        let source = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let source = match path.ends_with(".souffle") {
+            true => parse_souffle(&source),
+            false => parse_source(&source),
+        };
+        let mut source = source.map_err(|err| format!("{}:{}", path, err))?;
+
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+        source.inputs = source.inputs.into_iter().map(|file| file.relative_to(directory)).collect();
+        source.outputs =
+            source.outputs.into_iter().map(|file| file.relative_to(directory)).collect();
+
+        self.add(source)
+    }
+    // Executes a single line, writing its results to `output`. Returns false on :quit.
+    pub fn execute(&mut self, line: &str, output: &mut impl Write) -> Result<bool, String> {
+        let line = line.trim();
+        let (command, argument) = line
+            .split_once(char::is_whitespace)
+            .map(|(command, argument)| (command, argument.trim()))
+            .unwrap_or((line, ""));
+
+        let written = match command {
+            "" => Ok(()),
+            ":load" => return self.load(argument).map(|_| true),
+            ":poll" => {
+                self.poll()?;
+                Ok(())
+            }
+            ":query" => {
+                let (symbol, matchers) = parse_query(argument)?;
+                let mut facts: Vec<_> = self
+                    .runtime
+                    .query(&Query {
+                        matchers,
+                        symbol: &symbol,
+                    })?
+                    .collect();
+                facts.sort();
+
+                facts
+                    .iter()
+                    .try_for_each(|fact| writeln!(output, "{}.", format_fact(&symbol, fact)))
+            }
+            ":explain" => {
+                let (symbol, fact) = parse_fact(argument)?;
+                match explain(&self.runtime, &symbol, &fact)? {
+                    Some(derivation) => write!(output, "{}", derivation),
+                    None => writeln!(output, "{} does not hold", format_fact(&symbol, &fact)),
+                }
+            }
+            ":relations" => self
+                .runtime
+                .relation_sizes()?
+                .iter()
+                .try_for_each(|(relation, size)| writeln!(output, "{}: {}", relation, size)),
+            ":rules" => self
+                .runtime
+                .program()
+                .inner
+                .iter()
+                .try_for_each(|rule| writeln!(output, "{}", rule)),
+            ":help" => writeln!(output, "{}", HELP),
+            ":quit" => return Ok(false),
+            command if command.starts_with(':') => {
+                return Err(format!("unknown command {}, see :help", command))
+            }
+            _ => return self.add(parse_source(line)?).map(|_| true),
+        };
+        written.map_err(|err| err.to_string())?;
+
+        Ok(true)
+    }
+    // Reads lines until the end of `input` or :quit, prompting for each. Errors are reported and
+    // the session goes on.
+    pub fn run(
+        &mut self,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+        errors: &mut impl Write,
+    ) -> Result<(), String> {
+        loop {
+            write!(output, "> ").map_err(|err| err.to_string())?;
+            output.flush().map_err(|err| err.to_string())?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line).map_err(|err| err.to_string())? == 0 {
+                return Ok(());
+            }
+
+            match self.execute(&line, output) {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(err) => writeln!(errors, "error: {}", err).map_err(|err| err.to_string())?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repl::Repl;
+    use datalog_syntax::{FunctionRegistry, TypedValue};
+
+    fn execute(repl: &mut Repl, line: &str) -> Result<String, String> {
+        let mut output = vec![];
+        repl.execute(line, &mut output)?;
+
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut repl = Repl::default();
+        execute(&mut repl, "e(\"a\", \"b\"). e(\"b\", \"c\").").unwrap();
+        execute(&mut repl, ":poll").unwrap();
+        assert_eq!("e: 2\n", execute(&mut repl, ":relations").unwrap());
+
+        // Rules apply to the facts added before them, once polled
+        execute(&mut repl, "tc(?x, ?y) <- [e(?x, ?y)].").unwrap();
+        execute(&mut repl, "tc(?x, ?z) <- [tc(?x, ?y), e(?y, ?z)].").unwrap();
+        assert!(execute(&mut repl, ":query tc(_, _)").is_err());
+        execute(&mut repl, ":poll").unwrap();
+        assert_eq!(
+            "tc(\"a\", \"b\").\ntc(\"a\", \"c\").\n",
+            execute(&mut repl, ":query tc(\"a\", _)").unwrap()
+        );
+        assert_eq!("e: 2\ntc: 3\n", execute(&mut repl, ":relations").unwrap());
+        assert_eq!(
+            "tc(\"a\", \"c\") by tc(x, z) <- [tc(x, y), e(y, z)]\n  tc(\"a\", \"b\") by tc(x, y) <- [e(x, y)]\n    e(\"a\", \"b\")\n  e(\"b\", \"c\")\n",
+            execute(&mut repl, ":explain tc(\"a\", \"c\")").unwrap()
+        );
+
+        // Invalid rules leave the program as it was
+        assert!(execute(&mut repl, "p(?x) <- [e(?x, ?y), !p(?y)].").is_err());
+        assert_eq!(2, execute(&mut repl, ":rules").unwrap().lines().count());
+        assert!(execute(&mut repl, ":frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let mut repl = Repl::default();
+        let mut input = "e(\"a\").\n:query e(_)\n:poll\n:query e(_)\n:quit\n:poll\n".as_bytes();
+        let mut output = vec![];
+        let mut errors = vec![];
+        repl.run(&mut input, &mut output, &mut errors).unwrap();
+
+        assert_eq!("> > > > e(\"a\").\n> ", String::from_utf8(output).unwrap());
+        assert_eq!(
+            "error: poll needed to obtain correct results\n",
+            String::from_utf8(errors).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut functions = FunctionRegistry::default();
+        functions.register("greeting", |args| match args["x"] {
+            TypedValue::Str(x) => Ok(TypedValue::from(format!("hello {}", x))),
+            x => Err(format!("cannot greet {}", x)),
+        });
+        let mut repl = Repl::with_functions(functions);
+
+        assert_eq!(
+            Err("unknown function farewell".to_string()),
+            execute(&mut repl, "bye(farewell(?x)) <- [name(?x)].")
+        );
+        execute(&mut repl, "hi(greeting(?x)) <- [name(?x)].").unwrap();
+        execute(&mut repl, "name(\"bob\"). name(7).").unwrap();
+        assert_eq!(
+            Err("greeting(x): cannot greet 7".to_string()),
+            execute(&mut repl, ":poll")
+        );
+        assert_eq!(
+            "hi(\"hello bob\").\n",
+            execute(&mut repl, ":query hi(_)").unwrap()
+        );
+    }
+}
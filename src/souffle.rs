@@ -0,0 +1,865 @@
+use crate::engine::external::ExternalRelations;
+use crate::loaders::csv::{ColumnType, CsvFile};
+use crate::parser::Source;
+use datalog_syntax::{Atom, Rule, Term, TypedValue};
+use std::collections::HashMap;
+
+// The common subset of Soufflé, so that its programs can be run as they are, and compared with
+// what Soufflé makes of them:
+//
+//     .decl edge(x: symbol, y: symbol)
+//     .decl path(x: symbol, y: symbol)
+//     .input edge
+//     .output path(IO=file, filename="path.tsv")
+//     path(X, Y) :- edge(X, Y).
+//     path(X, Z) :- path(X, Y), edge(Y, Z), X != Z, !blocked(Y, _).
+//
+// Types are symbol, number, unsigned and float, read as Str, I64, Int and Float. Every relation has
+// to be declared, and constants are read as the type of their column. Comparisons are lowered to
+// the eq, ne, lt, le, gt and ge built-ins, see ExternalRelations::builtins, hence no relation can
+// be named after a built-in, and wildcards to fresh variables. Inputs default to tab separated
+// <relation>.facts files, and outputs to <relation>.csv ones.
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Identifier(String),
+    // .decl, .input and .output
+    Directive(String),
+    Str(String),
+    // Kept as written until its type is known
+    Number(String),
+    LeftParenthesis,
+    RightParenthesis,
+    Comma,
+    Dot,
+    Colon,
+    Implication,
+    Bang,
+    // Named after the built-in it is lowered to, e.g. lt for <
+    Comparison(&'static str),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Identifier(name) => name.clone(),
+            Token::Directive(name) => format!(".{}", name),
+            Token::Str(value) => format!("{:?}", value),
+            Token::Number(value) => value.clone(),
+            Token::LeftParenthesis => "(".to_string(),
+            Token::RightParenthesis => ")".to_string(),
+            Token::Comma => ",".to_string(),
+            Token::Dot => ".".to_string(),
+            Token::Colon => ":".to_string(),
+            Token::Implication => ":-".to_string(),
+            Token::Bang => "!".to_string(),
+            Token::Comparison(name) => COMPARISONS
+                .iter()
+                .find(|(_, builtin)| builtin == name)
+                .unwrap()
+                .0
+                .to_string(),
+        }
+    }
+}
+
+// Longest operators first, so that <= is not read as <
+const COMPARISONS: [(&str, &str); 6] = [
+    ("!=", "ne"),
+    ("<=", "le"),
+    (">=", "ge"),
+    ("=", "eq"),
+    ("<", "lt"),
+    (">", "gt"),
+];
+
+// (line, column), both starting at 1
+type Position = (usize, usize);
+
+fn is_identifier_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, Position)>, String> {
+    let characters: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut index = 0;
+    let (mut line, mut column) = (1, 1);
+    let at = |index: usize| characters.get(index).copied();
+
+    while let Some(character) = at(index) {
+        let position = (line, column);
+        let start = index;
+        let error = |message: &str| format!("{}:{}: {}", position.0, position.1, message);
+
+        let token = match character {
+            character if character.is_whitespace() => {
+                index += 1;
+                None
+            }
+            '/' if at(index + 1) == Some('/') => {
+                while !matches!(at(index), Some('\n') | None) {
+                    index += 1;
+                }
+                None
+            }
+            '/' if at(index + 1) == Some('*') => {
+                index += 2;
+                while !(at(index) == Some('*') && at(index + 1) == Some('/')) {
+                    if at(index).is_none() {
+                        return Err(error("unterminated comment"));
+                    }
+                    index += 1;
+                }
+                index += 2;
+                None
+            }
+            '"' => {
+                index += 1;
+                let mut value = String::new();
+                loop {
+                    match at(index) {
+                        Some('"') => break,
+                        Some('\\') => {
+                            index += 1;
+                            value.push(match at(index) {
+                                Some('n') => '\n',
+                                Some('t') => '\t',
+                                Some(escaped @ ('"' | '\\')) => escaped,
+                                _ => return Err(error("invalid escape sequence")),
+                            });
+                        }
+                        Some('\n') | None => return Err(error("unterminated string")),
+                        Some(character) => value.push(character),
+                    }
+                    index += 1;
+                }
+                index += 1;
+                Some(Token::Str(value))
+            }
+            '-' | '0'..='9'
+                if character != '-' || at(index + 1).is_some_and(|c| c.is_ascii_digit()) =>
+            {
+                index += 1;
+                let digits = |index: &mut usize| {
+                    while at(*index).is_some_and(|c| c.is_ascii_digit()) {
+                        *index += 1;
+                    }
+                };
+                digits(&mut index);
+                // A dot ends the statement unless a digit follows it
+                if at(index) == Some('.') && at(index + 1).is_some_and(|c| c.is_ascii_digit()) {
+                    index += 1;
+                    digits(&mut index);
+                }
+                if matches!(at(index), Some('e' | 'E')) {
+                    let sign = matches!(at(index + 1), Some('-' | '+')) as usize;
+                    if at(index + 1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                        index += 1 + sign;
+                        digits(&mut index);
+                    }
+                }
+                Some(Token::Number(characters[start..index].iter().collect()))
+            }
+            '.' if at(index + 1).is_some_and(|c| c.is_alphabetic()) => {
+                index += 1;
+                while at(index).is_some_and(is_identifier_character) {
+                    index += 1;
+                }
+                Some(Token::Directive(characters[start + 1..index].iter().collect()))
+            }
+            character if is_identifier_character(character) => {
+                while at(index).is_some_and(is_identifier_character) {
+                    index += 1;
+                }
+                Some(Token::Identifier(characters[start..index].iter().collect()))
+            }
+            ':' if at(index + 1) == Some('-') => {
+                index += 2;
+                Some(Token::Implication)
+            }
+            '(' | ')' | ',' | '.' | ':' => {
+                index += 1;
+                Some(match character {
+                    '(' => Token::LeftParenthesis,
+                    ')' => Token::RightParenthesis,
+                    ',' => Token::Comma,
+                    '.' => Token::Dot,
+                    _ => Token::Colon,
+                })
+            }
+            _ => {
+                let comparison = COMPARISONS.iter().find(|(operator, _)| {
+                    operator.chars().enumerate().all(|(offset, c)| at(index + offset) == Some(c))
+                });
+                match comparison {
+                    Some((operator, builtin)) => {
+                        index += operator.len();
+                        Some(Token::Comparison(builtin))
+                    }
+                    None if character == '!' => {
+                        index += 1;
+                        Some(Token::Bang)
+                    }
+                    None => return Err(error(&format!("unexpected character {:?}", character))),
+                }
+            }
+        };
+
+        for character in &characters[start..index] {
+            if *character == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        if let Some(token) = token {
+            tokens.push((token, position));
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+enum Literal {
+    Str(String),
+    Number(String),
+}
+
+impl Literal {
+    fn describe(&self) -> String {
+        match self {
+            Literal::Str(value) => format!("{:?}", value),
+            Literal::Number(value) => value.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Argument {
+    Variable(String),
+    Wildcard,
+    Literal(Literal),
+}
+
+struct RawAtom {
+    symbol: String,
+    arguments: Vec<Argument>,
+    negated: bool,
+    position: Position,
+}
+
+enum BodyItem {
+    Atom(RawAtom),
+    Comparison(&'static str, Argument, Argument),
+}
+
+enum Statement {
+    Declaration(String, Vec<ColumnType>, Position),
+    Io(bool, String, Vec<(String, Literal)>, Position),
+    Clause(Vec<RawAtom>, Vec<BodyItem>),
+}
+
+struct Parser {
+    tokens: Vec<(Token, Position)>,
+    index: usize,
+    end: Position,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> Position {
+        self.tokens.get(self.index).map_or(self.end, |(_, position)| *position)
+    }
+
+    fn error(&self, expected: &str) -> String {
+        let (line, column) = self.position();
+        match self.peek() {
+            Some(token) => format!(
+                "{}:{}: expected {}, found {}",
+                line,
+                column,
+                expected,
+                token.describe()
+            ),
+            None => format!(
+                "{}:{}: expected {}, found the end of the input",
+                line, column, expected
+            ),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        if self.peek() != Some(&expected) {
+            return Err(self.error(&expected.describe()));
+        }
+        self.index += 1;
+
+        Ok(())
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        let found = self.peek() == Some(&token);
+        if found {
+            self.index += 1;
+        }
+
+        found
+    }
+
+    fn identifier(&mut self, expected: &str) -> Result<String, String> {
+        match self.peek().cloned() {
+            Some(Token::Identifier(name)) => {
+                self.index += 1;
+                Ok(name)
+            }
+            _ => Err(self.error(expected)),
+        }
+    }
+
+    // Parses `(item, ...)`.
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        self.expect(Token::LeftParenthesis)?;
+        let mut items = vec![];
+        while !self.eat(Token::RightParenthesis) {
+            items.push(item(self)?);
+            if !self.eat(Token::Comma) {
+                self.expect(Token::RightParenthesis)?;
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+
+    fn column_type(&mut self) -> Result<ColumnType, String> {
+        self.identifier("an attribute name")?;
+        self.expect(Token::Colon)?;
+        let position = self.position();
+        match self.identifier("a type")?.as_str() {
+            "symbol" => Ok(ColumnType::Str),
+            "number" => Ok(ColumnType::I64),
+            "unsigned" => Ok(ColumnType::Int),
+            "float" => Ok(ColumnType::Float),
+            name => Err(format!(
+                "{}:{}: unknown type {}, expected symbol, number, unsigned or float",
+                position.0, position.1, name
+            )),
+        }
+    }
+
+    fn literal(&mut self) -> Option<Literal> {
+        let literal = match self.peek()? {
+            Token::Str(value) => Literal::Str(value.clone()),
+            Token::Number(value) => Literal::Number(value.clone()),
+            _ => return None,
+        };
+        self.index += 1;
+
+        Some(literal)
+    }
+
+    // Parameters such as `IO=file` or `filename="e.csv"`.
+    fn parameter(&mut self) -> Result<(String, Literal), String> {
+        let name = self.identifier("a parameter name")?;
+        self.expect(Token::Comparison("eq"))?;
+        if let Some(literal) = self.literal() {
+            return Ok((name, literal));
+        }
+
+        Ok((name, Literal::Str(self.identifier("a parameter value")?)))
+    }
+
+    fn argument(&mut self) -> Result<Argument, String> {
+        if let Some(literal) = self.literal() {
+            return Ok(Argument::Literal(literal));
+        }
+
+        match self.identifier("a variable or a constant")? {
+            name if name == "_" => Ok(Argument::Wildcard),
+            name => Ok(Argument::Variable(name)),
+        }
+    }
+
+    fn atom(&mut self) -> Result<RawAtom, String> {
+        let position = self.position();
+        let negated = self.eat(Token::Bang);
+        let symbol = self.identifier("a relation name")?;
+        let arguments = self.list(Self::argument)?;
+
+        Ok(RawAtom {
+            symbol,
+            arguments,
+            negated,
+            position,
+        })
+    }
+
+    fn body_item(&mut self) -> Result<BodyItem, String> {
+        let is_atom = matches!(self.peek(), Some(Token::Bang))
+            || matches!(self.tokens.get(self.index + 1), Some((Token::LeftParenthesis, _)));
+        if is_atom {
+            return self.atom().map(BodyItem::Atom);
+        }
+
+        let left = self.argument()?;
+        let Some(Token::Comparison(builtin)) = self.peek().cloned() else {
+            return Err(self.error("a comparison"));
+        };
+        self.index += 1;
+
+        Ok(BodyItem::Comparison(builtin, left, self.argument()?))
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        let position = self.position();
+        if let Some(Token::Directive(directive)) = self.peek().cloned() {
+            self.index += 1;
+            let name = self.identifier("a relation name")?;
+
+            return match directive.as_str() {
+                "decl" => Ok(Statement::Declaration(name, self.list(Self::column_type)?, position)),
+                "input" | "output" => {
+                    let mut parameters = vec![];
+                    if self.peek() == Some(&Token::LeftParenthesis) {
+                        parameters = self.list(Self::parameter)?;
+                    }
+                    Ok(Statement::Io(directive == "input", name, parameters, position))
+                }
+                _ => Err(format!(
+                    "{}:{}: unsupported directive .{}",
+                    position.0, position.1, directive
+                )),
+            };
+        }
+
+        let mut heads = vec![self.atom()?];
+        while self.eat(Token::Comma) {
+            heads.push(self.atom()?);
+        }
+
+        let mut body = vec![];
+        if self.eat(Token::Implication) {
+            body.push(self.body_item()?);
+            while self.eat(Token::Comma) {
+                body.push(self.body_item()?);
+            }
+        }
+        self.expect(Token::Dot)?;
+
+        Ok(Statement::Clause(heads, body))
+    }
+}
+
+fn type_name(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Str => "symbol",
+        ColumnType::I64 => "number",
+        ColumnType::Int => "unsigned",
+        ColumnType::Float => "float",
+        ColumnType::Bool => "bool",
+    }
+}
+
+// Literals without a column, e.g. compared with one another, are numbers unless they are floats.
+fn constant(literal: &Literal, column_type: Option<ColumnType>) -> Result<TypedValue, String> {
+    match (literal, column_type) {
+        (Literal::Str(value), None | Some(ColumnType::Str)) => Ok(value.as_str().into()),
+        (Literal::Number(value), None) if value.contains(['.', 'e', 'E']) => {
+            ColumnType::Float.coerce(value)
+        }
+        (Literal::Number(value), None) => ColumnType::I64.coerce(value),
+        (Literal::Number(value), Some(column_type)) if column_type != ColumnType::Str => column_type
+            .coerce(value)
+            .map_err(|_| format!("{} is not a {}", value, type_name(column_type))),
+        (Literal::Str(value), Some(column_type)) => {
+            Err(format!("{:?} is not a {}", value, type_name(column_type)))
+        }
+        (Literal::Number(value), Some(column_type)) => {
+            Err(format!("{} is not a {}", value, type_name(column_type)))
+        }
+    }
+}
+
+// Turns the statements into rules, facts and files, once every relation is declared.
+struct Lowering {
+    declarations: HashMap<String, Vec<ColumnType>>,
+    wildcards: usize,
+}
+
+impl Lowering {
+    fn columns(&self, atom: &RawAtom) -> Result<&[ColumnType], String> {
+        let (line, column) = atom.position;
+        let columns = self.declarations.get(&atom.symbol).ok_or_else(|| {
+            format!("{}:{}: relation {} is not declared", line, column, atom.symbol)
+        })?;
+        if columns.len() != atom.arguments.len() {
+            return Err(format!(
+                "{}:{}: relation {} has {} columns, found {}",
+                line,
+                column,
+                atom.symbol,
+                columns.len(),
+                atom.arguments.len()
+            ));
+        }
+
+        Ok(columns)
+    }
+
+    fn term(
+        &mut self,
+        argument: &Argument,
+        column_type: Option<ColumnType>,
+    ) -> Result<Term, String> {
+        Ok(match argument {
+            Argument::Variable(name) => Term::Variable(name.clone()),
+            Argument::Wildcard => {
+                self.wildcards += 1;
+                // Soufflé identifiers can't hold a #, hence no variable of the program is named so
+                Term::Variable(format!("_#{}", self.wildcards))
+            }
+            Argument::Literal(literal) => Term::Constant(constant(literal, column_type)?),
+        })
+    }
+
+    fn atom(&mut self, atom: &RawAtom) -> Result<Atom, String> {
+        let columns = self.columns(atom)?.to_vec();
+        let (line, column) = atom.position;
+        let terms = atom
+            .arguments
+            .iter()
+            .zip(columns)
+            .map(|(argument, column_type)| self.term(argument, Some(column_type)))
+            .collect::<Result<_, _>>()
+            .map_err(|err| format!("{}:{}: {}", line, column, err))?;
+
+        Ok(Atom {
+            terms,
+            symbol: atom.symbol.clone(),
+            negated: atom.negated,
+        })
+    }
+
+    fn clause(
+        &mut self,
+        heads: Vec<RawAtom>,
+        body: Vec<BodyItem>,
+        source: &mut Source,
+    ) -> Result<(), String> {
+        for head in &heads {
+            let (line, column) = head.position;
+            if head.negated {
+                return Err(format!("{}:{}: the head cannot be negated", line, column));
+            }
+            if head.arguments.iter().any(|argument| matches!(argument, Argument::Wildcard)) {
+                return Err(format!("{}:{}: the head cannot have wildcards", line, column));
+            }
+        }
+
+        if body.is_empty() {
+            for head in heads {
+                let columns = self.columns(&head)?.to_vec();
+                let (line, column) = head.position;
+                let fact = head
+                    .arguments
+                    .iter()
+                    .zip(columns)
+                    .map(|(argument, column_type)| match argument {
+                        Argument::Literal(literal) => constant(literal, Some(column_type)),
+                        _ => Err("facts cannot have variables".to_string()),
+                    })
+                    .collect::<Result<_, _>>()
+                    .map_err(|err| format!("{}:{}: {}", line, column, err))?;
+                source.facts.push((head.symbol, fact));
+            }
+
+            return Ok(());
+        }
+
+        // Variables take the type of the columns they appear in, which literals compared with
+        // them are read as.
+        let mut variable_types = HashMap::new();
+        for atom in heads.iter().chain(body.iter().filter_map(|item| match item {
+            BodyItem::Atom(atom) => Some(atom),
+            BodyItem::Comparison(..) => None,
+        })) {
+            for (argument, column_type) in atom.arguments.iter().zip(self.columns(atom)?) {
+                if let Argument::Variable(name) = argument {
+                    variable_types.insert(name.clone(), *column_type);
+                }
+            }
+        }
+        let type_of = |argument: &Argument| match argument {
+            Argument::Variable(name) => variable_types.get(name).copied(),
+            _ => None,
+        };
+
+        let mut atoms = vec![];
+        for item in &body {
+            match item {
+                BodyItem::Atom(atom) => atoms.push(self.atom(atom)?),
+                BodyItem::Comparison(builtin, left, right) => {
+                    let left_term = self.term(left, type_of(right))?;
+                    let right_term = self.term(right, type_of(left))?;
+                    atoms.push(Atom {
+                        terms: vec![left_term, right_term],
+                        symbol: builtin.to_string(),
+                        negated: false,
+                    });
+                }
+            }
+        }
+        for head in &heads {
+            source.rules.push(Rule {
+                head: self.atom(head)?,
+                body: atoms.clone(),
+                id: 0,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn file(
+        &self,
+        input: bool,
+        relation: String,
+        parameters: Vec<(String, Literal)>,
+        position: Position,
+    ) -> Result<CsvFile, String> {
+        let error = |message: String| format!("{}:{}: {}", position.0, position.1, message);
+        let columns = self
+            .declarations
+            .get(&relation)
+            .ok_or_else(|| error(format!("relation {} is not declared", relation)))?;
+
+        // Soufflé's parameters in the terms of CsvFile::from_parameters, with its own defaults
+        let extension = if input { "facts" } else { "csv" };
+        let mut csv_parameters = vec![
+            ("filename".to_string(), format!("{}.{}", relation, extension).into()),
+            ("delimiter".to_string(), "\t".into()),
+        ];
+        for (name, value) in parameters {
+            let parameter = match (name.as_str(), value) {
+                ("IO", Literal::Str(io)) if io == "file" => continue,
+                ("filename" | "delimiter", Literal::Str(value)) => (name, value.into()),
+                ("headers", Literal::Str(headers)) if input && headers == "true" => {
+                    ("header".to_string(), true.into())
+                }
+                (name, value) => {
+                    return Err(error(format!(
+                        "unsupported parameter {}={} of {}",
+                        name,
+                        value.describe(),
+                        relation
+                    )))
+                }
+            };
+            csv_parameters.retain(|(name, _)| *name != parameter.0);
+            csv_parameters.push(parameter);
+        }
+        let mut file = CsvFile::from_parameters(&relation, csv_parameters, input).map_err(error)?;
+        if input {
+            file.types = columns.clone();
+        }
+
+        Ok(file)
+    }
+}
+
+// Reads a Soufflé program into the rules, facts and files of a source, see Source.
+pub fn parse_souffle(source: &str) -> Result<Source, String> {
+    let lines = source.split('\n').collect::<Vec<_>>();
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        index: 0,
+        end: (lines.len(), lines.last().map_or(0, |line| line.chars().count()) + 1),
+    };
+    let mut statements = vec![];
+    while parser.peek().is_some() {
+        statements.push(parser.statement()?);
+    }
+
+    let mut lowering = Lowering {
+        declarations: HashMap::new(),
+        wildcards: 0,
+    };
+    let builtins = ExternalRelations::builtins();
+    for statement in &statements {
+        if let Statement::Declaration(relation, columns, (line, column)) = statement {
+            if lowering.declarations.contains_key(relation) {
+                return Err(format!("{}:{}: relation {} is declared twice", line, column, relation));
+            }
+            // Comparisons are lowered to built-ins, which the program can't derive
+            if builtins.contains(relation) {
+                return Err(format!(
+                    "{}:{}: relation {} is reserved for a built-in",
+                    line, column, relation
+                ));
+            }
+            lowering.declarations.insert(relation.clone(), columns.clone());
+        }
+    }
+
+    let mut parsed = Source::default();
+    for statement in statements {
+        match statement {
+            Statement::Declaration(..) => {}
+            Statement::Io(input, relation, parameters, position) => {
+                let file = lowering.file(input, relation, parameters, position)?;
+                match input {
+                    true => parsed.inputs.push(file),
+                    false => parsed.outputs.push(file),
+                }
+            }
+            Statement::Clause(heads, body) => lowering.clause(heads, body, &mut parsed)?,
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::engine::external::ExternalRelations;
+    use crate::engine::fact_storage::StorageLayout;
+    use crate::loaders::csv::{ColumnType, CsvFile};
+    use crate::souffle::parse_souffle;
+    use datalog_rule_macro::rule;
+    use datalog_syntax::*;
+
+    const PROGRAM: &str = r#"
+        /* Reachability, as Soufflé's tutorial has it */
+        .decl edge(x: symbol, y: symbol)
+        .decl blocked(x: symbol)
+        .decl weight(x: symbol, w: number)
+        .decl path(x: symbol, y: symbol)
+        .decl heavy(x: symbol, y: symbol)
+        .input edge
+        .output path(IO=file, filename="path.tsv", delimiter=",")
+
+        edge("a", "b"). edge("b", "c"). edge("c", "a"). edge("c", "d").
+        blocked("d").
+        weight("a", 10). weight("b", -2).
+
+        path(X, Y) :- edge(X, Y), !blocked(Y).
+        path(X, Z) :- path(X, Y), edge(Y, Z), X != Z, !blocked(Z).
+        heavy(X, Y), heavy(Y, X) :- path(X, Y), weight(X, W), W >= 10.
+    "#;
+
+    #[test]
+    fn test_parse_souffle() {
+        let source = parse_souffle(PROGRAM).unwrap();
+
+        let mut input = CsvFile::new("edge", "edge.facts");
+        input.format.delimiter = '\t';
+        input.types = vec![ColumnType::Str, ColumnType::Str];
+        assert_eq!(vec![input], source.inputs);
+        let mut output = CsvFile::new("path", "path.tsv");
+        output.format.delimiter = ',';
+        assert_eq!(vec![output], source.outputs);
+        assert!(source
+            .facts
+            .contains(&("weight".to_string(), vec!["b".into(), (-2i64).into()])));
+        assert_eq!(
+            rule! { path(?X, ?Z) <- [path(?X, ?Y), edge(?Y, ?Z), ne(?X, ?Z), !blocked(?Z)] },
+            source.rules[1]
+        );
+        assert_eq!(
+            rule! { heavy(?X, ?Y) <- [path(?X, ?Y), weight(?X, ?W), ge(?W, 10i64)] },
+            source.rules[2]
+        );
+        assert_eq!(4, source.rules.len());
+
+        let externals = ExternalRelations::builtins();
+        let program = Program::from(source.rules);
+        let mut runtime =
+            MicroRuntime::try_with_externals(program, StorageLayout::default(), externals).unwrap();
+        for (relation, fact) in source.facts {
+            runtime.insert(&relation, fact);
+        }
+        runtime.poll();
+
+        let mut paths: Vec<_> = runtime
+            .query(&build_query!(path(_, _)))
+            .unwrap()
+            .map(|fact| format!("{}{}", fact[0], fact[1]))
+            .collect();
+        paths.sort();
+        assert_eq!(vec!["ab", "ac", "ba", "bc", "ca", "cb"], paths);
+        let heavy: Vec<_> = runtime.query(&build_query!(heavy(_, _))).unwrap().collect();
+        assert_eq!(4, heavy.len());
+    }
+
+    #[test]
+    fn test_wildcards_and_types() {
+        let source = parse_souffle(
+            r#"
+            .decl e(x: unsigned, y: float)
+            .decl n(x: unsigned)
+            .decl sink(x: unsigned)
+            sink(X) :- n(X), !e(X, _), X < 3.
+            e(1, 2). n(1).
+            "#,
+        )
+        .unwrap();
+
+        // Wildcards become variables no Soufflé program can name
+        let mut expected = rule! { sink(?X) <- [n(?X), !e(?X, ?_1), lt(?X, 3)] };
+        expected.body[1].terms[1] = Term::Variable("_#1".to_string());
+        assert_eq!(expected, source.rules[0]);
+        assert_eq!(("e".to_string(), vec![1usize.into(), 2.0.into()]), source.facts[0]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| parse_souffle(source).unwrap_err();
+
+        assert_eq!("1:1: relation e is not declared", error("e(\"a\")."));
+        assert_eq!(
+            "1:20: relation e has 1 columns, found 2",
+            error(".decl e(x: number) e(1, 2).")
+        );
+        assert_eq!("1:20: \"a\" is not a number", error(".decl e(x: number) e(\"a\")."));
+        assert_eq!("1:22: 1.5 is not a unsigned", error(".decl e(x: unsigned) e(1.5)."));
+        assert_eq!(
+            "1:12: unknown type list, expected symbol, number, unsigned or float",
+            error(".decl e(x: list)")
+        );
+        assert_eq!("1:1: unsupported directive .printsize", error(".printsize e"));
+        assert_eq!(
+            "1:1: relation lt is reserved for a built-in",
+            error(".decl lt(x: number, y: number)")
+        );
+        assert_eq!(
+            "1:20: unsupported parameter headers=\"yes\" of e",
+            error(".decl e(x: number) .input e(headers=\"yes\")")
+        );
+        assert_eq!(
+            "1:20: invalid parameter delimiter=\"ab\" of e",
+            error(".decl e(x: number) .input e(delimiter=\"ab\")")
+        );
+        assert_eq!(
+            "1:20: the head cannot have wildcards",
+            error(".decl e(x: number) e(_) :- e(1).")
+        );
+        assert_eq!(
+            "1:36: unexpected character '+'",
+            error(".decl e(x: number) e(X) :- e(X), X + 1 = 2.")
+        );
+        assert_eq!(
+            "1:36: expected a comparison, found e",
+            error(".decl e(x: number) e(X) :- e(X), X e.")
+        );
+    }
+}
@@ -0,0 +1,9 @@
+pub mod emitter;
+pub mod expressions;
+pub mod importer;
+pub mod lints;
+pub mod matrix;
+pub mod schedule;
+pub mod simulation;
+pub mod vocabulary;
+pub mod yaml;
@@ -0,0 +1,892 @@
+use crate::engine::datalog::MicroRuntime;
+use crate::workflows::expressions::{parse_expression, Expression};
+use crate::workflows::vocabulary::*;
+use crate::workflows::yaml::{render, Yaml};
+use datalog_syntax::{AnonymousGroundAtom, QueryBuilder, TypedValue};
+use std::collections::HashMap;
+
+// Returns the columns following `prefix` of every fact in `relation` starting with it, sorted, so
+// that whatever order facts were derived in, the emitted document is always the same.
+pub(crate) fn select(
+    runtime: &MicroRuntime,
+    relation: &str,
+    prefix: &[TypedValue],
+) -> Result<Vec<AnonymousGroundAtom>, String> {
+    let mut builder = QueryBuilder::new(relation);
+    prefix
+        .iter()
+        .for_each(|value| builder.with_constant(value.clone()));
+
+    let mut facts: Vec<_> = runtime
+        .query(&builder.query)?
+        .filter(|fact| fact.len() > prefix.len())
+        .map(|fact| fact[prefix.len()..].to_vec())
+        .collect();
+    facts.sort();
+    facts.dedup();
+
+    Ok(facts)
+}
+
+fn select_column(
+    runtime: &MicroRuntime,
+    relation: &str,
+    prefix: &[TypedValue],
+) -> Result<Vec<TypedValue>, String> {
+    Ok(select(runtime, relation, prefix)?
+        .into_iter()
+        .map(|mut fact| fact.swap_remove(0))
+        .collect())
+}
+
+fn select_single(
+    runtime: &MicroRuntime,
+    relation: &str,
+    prefix: &[TypedValue],
+) -> Result<Option<TypedValue>, String> {
+    let mut values = select_column(runtime, relation, prefix)?;
+
+    if values.len() > 1 {
+        let location: Vec<_> = prefix.iter().map(|value| value.to_string()).collect();
+
+        return Err(format!(
+            "conflicting {} facts for {}: {:?}",
+            relation,
+            location.join("/"),
+            values
+        ));
+    }
+
+    Ok(values.pop())
+}
+
+pub(crate) fn to_yaml(value: &TypedValue) -> Yaml {
+    match value {
+        TypedValue::Str(inner) => Yaml::Str(inner.clone()),
+        TypedValue::Int(inner) => Yaml::Int(*inner as i64),
+        TypedValue::Bool(inner) => Yaml::Bool(*inner),
+        TypedValue::I64(inner) => Yaml::Int(*inner),
+        TypedValue::Float(inner) => Yaml::Real(match inner.get() {
+            inner if inner.is_nan() => ".nan".to_string(),
+            inner if inner.is_infinite() && inner > 0.0 => ".inf".to_string(),
+            inner if inner.is_infinite() => "-.inf".to_string(),
+            inner => format!("{:?}", inner),
+        }),
+        // Workflows have no binary scalars
+        TypedValue::Bytes(inner) => Yaml::Str(String::from_utf8_lossy(inner).into_owned()),
+        TypedValue::List(items) => Yaml::List(items.iter().map(to_yaml).collect()),
+        TypedValue::Custom(inner) => Yaml::Str(inner.to_string()),
+    }
+}
+
+fn to_key(value: &TypedValue) -> String {
+    value.to_string()
+}
+
+// Builds a map out of (key, value) pairs, where repeated keys collect their values in a list.
+fn key_value_map(pairs: Vec<AnonymousGroundAtom>, always_list: bool) -> Yaml {
+    let mut entries: Vec<(String, Yaml)> = vec![];
+
+    pairs.iter().for_each(|pair| {
+        let key = to_key(&pair[0]);
+        let value = to_yaml(&pair[1]);
+
+        match entries.last_mut() {
+            Some((last_key, Yaml::List(values))) if *last_key == key => values.push(value),
+            Some((last_key, last_value)) if *last_key == key => {
+                *last_value = Yaml::List(vec![last_value.clone(), value])
+            }
+            _ if always_list => entries.push((key, Yaml::List(vec![value]))),
+            _ => entries.push((key, value)),
+        }
+    });
+
+    Yaml::Map(entries)
+}
+
+// A lone "*" scope is a blanket level, e.g. `permissions: read-all`, "none" standing for
+// `permissions: {}`.
+fn permissions(pairs: Vec<AnonymousGroundAtom>) -> Yaml {
+    match pairs.as_slice() {
+        [pair] if pair[0] == TypedValue::from("*") && pair[1] == TypedValue::from("none") => {
+            Yaml::Map(vec![])
+        }
+        [pair] if pair[0] == TypedValue::from("*") => to_yaml(&pair[1]),
+        _ => key_value_map(pairs, false),
+    }
+}
+
+// Attributes are appended as they are, in key order.
+fn push_attributes(entries: &mut Vec<(String, Yaml)>, attributes: Vec<AnonymousGroundAtom>) {
+    attributes
+        .iter()
+        .for_each(|pair| entries.push((to_key(&pair[0]), to_yaml(&pair[1]))));
+}
+
+// Groups (entry, key, value) rows into one map per entry, in entry order.
+fn entry_maps(rows: Vec<AnonymousGroundAtom>) -> Vec<Yaml> {
+    let mut entries: Vec<(TypedValue, Vec<AnonymousGroundAtom>)> = vec![];
+    rows.into_iter().for_each(|row| match entries.last_mut() {
+        Some((entry, pairs)) if *entry == row[0] => pairs.push(row[1..].to_vec()),
+        _ => entries.push((row[0].clone(), vec![row[1..].to_vec()])),
+    });
+
+    entries
+        .into_iter()
+        .map(|(_, pairs)| key_value_map(pairs, false))
+        .collect()
+}
+
+// The strategy.matrix block, which GitHub expands by itself.
+fn native_matrix(runtime: &MicroRuntime, key: &[TypedValue]) -> Result<Option<Yaml>, String> {
+    let axes = select(runtime, MATRIX_AXIS, key)?;
+    let include = entry_maps(select(runtime, MATRIX_INCLUDE, key)?);
+    let exclude = entry_maps(select(runtime, MATRIX_EXCLUDE, key)?);
+    if axes.is_empty() && include.is_empty() {
+        return Ok(None);
+    }
+
+    let mut entries = match key_value_map(axes, true) {
+        Yaml::Map(entries) => entries,
+        _ => vec![],
+    };
+    if !include.is_empty() {
+        entries.push(("include".to_string(), Yaml::List(include)));
+    }
+    if !exclude.is_empty() {
+        entries.push(("exclude".to_string(), Yaml::List(exclude)));
+    }
+
+    Ok(Some(Yaml::Map(entries)))
+}
+
+// The cells of a job's matrix as derived by the matrix rules, each as its (key, value) pairs in
+// key order.
+fn matrix_cells(
+    runtime: &MicroRuntime,
+    key: &[TypedValue],
+) -> Result<Vec<Vec<(String, TypedValue)>>, String> {
+    let mut cells: Vec<(TypedValue, Vec<(String, TypedValue)>)> = vec![];
+    select(runtime, MATRIX_CELL, key)?
+        .into_iter()
+        .for_each(|row| {
+            let pair = (to_key(&row[1]), row[2].clone());
+            match cells.last_mut() {
+                Some((cell, pairs)) if *cell == row[0] => pairs.push(pair),
+                _ => cells.push((row[0].clone(), vec![pair])),
+            }
+        });
+
+    Ok(cells.into_iter().map(|(_, pairs)| pairs).collect())
+}
+
+// Replaces references to matrix values, e.g. `${{ matrix.os }}`, with the values of `cell`. Any
+// other use of the matrix context is returned as an error, since it can't be unrolled.
+fn substitute_matrix(document: &Yaml, cell: &[(String, TypedValue)]) -> Result<Yaml, String> {
+    match document {
+        Yaml::Str(scalar) => {
+            let mut scalar = scalar.clone();
+            for (key, value) in cell {
+                for reference in [
+                    format!("${{{{ matrix.{} }}}}", key),
+                    format!("${{{{matrix.{}}}}}", key),
+                ] {
+                    if scalar == reference {
+                        return Ok(to_yaml(value));
+                    }
+                    scalar = scalar.replace(&reference, &value.to_string());
+                }
+            }
+
+            let remaining = scalar
+                .split("${{")
+                .skip(1)
+                .map(|expression| expression.split("}}").next().unwrap_or(expression))
+                .find(|expression| expression.contains("matrix"));
+            match remaining {
+                Some(expression) => Err(format!("${{{{{}}}}}", expression)),
+                None => Ok(Yaml::Str(scalar)),
+            }
+        }
+        Yaml::List(items) => Ok(Yaml::List(
+            items
+                .iter()
+                .map(|item| substitute_matrix(item, cell))
+                .collect::<Result<_, _>>()?,
+        )),
+        Yaml::Map(entries) => Ok(Yaml::Map(
+            entries
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), substitute_matrix(value, cell)?)))
+                .collect::<Result<_, String>>()?,
+        )),
+        scalar => Ok(scalar.clone()),
+    }
+}
+
+// One job per cell, named after the cell's values, e.g. `test-ubuntu-latest-stable`.
+fn unroll_job(
+    file: &TypedValue,
+    job: &TypedValue,
+    compiled_job: &Yaml,
+    cells: Vec<Vec<(String, TypedValue)>>,
+) -> Result<Vec<(String, Yaml)>, String> {
+    let mut unrolled_jobs: Vec<(String, Yaml)> = vec![];
+    for cell in cells {
+        let values: Vec<_> = cell.iter().map(|(_, value)| value.to_string()).collect();
+        let suffix: String = values
+            .join("-")
+            .chars()
+            .map(|character| match character {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => character,
+                _ => '_',
+            })
+            .collect();
+        let id = format!("{}-{}", job, suffix);
+        if unrolled_jobs.iter().any(|(unrolled_id, _)| *unrolled_id == id) {
+            return Err(format!("job {} in {} unrolls into {} twice", job, file, id));
+        }
+
+        let mut unrolled_job = substitute_matrix(compiled_job, &cell).map_err(|expression| {
+            format!("job {} in {} uses {}, which can't be unrolled", job, file, expression)
+        })?;
+        if let Yaml::Map(entries) = &mut unrolled_job {
+            if entries.iter().all(|(key, _)| key != "name") {
+                let name = format!("{} ({})", job, values.join(", "));
+                entries.insert(0, ("name".to_string(), Yaml::Str(name)));
+            }
+        }
+        unrolled_jobs.push((id, unrolled_job));
+    }
+
+    Ok(unrolled_jobs)
+}
+
+// Jobs needing an unrolled job need every job it was unrolled into.
+fn rename_needs(job: &mut Yaml, unrolled_ids: &HashMap<String, Vec<String>>) {
+    let Yaml::Map(entries) = job else {
+        return;
+    };
+    entries
+        .iter_mut()
+        .filter(|(key, _)| key == "needs")
+        .for_each(|(_, needs)| {
+            if let Yaml::List(needed_jobs) = needs {
+                *needed_jobs = needed_jobs
+                    .iter()
+                    .flat_map(|needed_job| match needed_job {
+                        Yaml::Str(id) if unrolled_ids.contains_key(id) => unrolled_ids[id]
+                            .iter()
+                            .map(|unrolled_id| Yaml::Str(unrolled_id.clone()))
+                            .collect(),
+                        needed_job => vec![needed_job.clone()],
+                    })
+                    .collect();
+            }
+        });
+}
+
+// Combines the written condition, e.g. a job_if fact, with the ones derived out of `run_if` and
+// `run_unless` facts. A written condition on its own is kept as it is.
+fn condition(
+    runtime: &MicroRuntime,
+    written: Option<TypedValue>,
+    run_if: &str,
+    run_unless: &str,
+    key: &[TypedValue],
+) -> Result<Option<Yaml>, String> {
+    let required = select(runtime, run_if, key)?;
+    let excluded = select(runtime, run_unless, key)?;
+    if required.is_empty() && excluded.is_empty() {
+        return Ok(written.as_ref().map(to_yaml));
+    }
+
+    let mut conditions = vec![];
+    if let Some(written) = written {
+        conditions.push(parse_expression(&written.to_string())?);
+    }
+    // Values of the same context are alternatives
+    let mut alternatives: Vec<(TypedValue, Vec<Expression>)> = vec![];
+    required.iter().for_each(|pair| {
+        let alternative = Expression::context(&to_key(&pair[0])).equals((&pair[1]).into());
+        match alternatives.last_mut() {
+            Some((context, expressions)) if *context == pair[0] => expressions.push(alternative),
+            _ => alternatives.push((pair[0].clone(), vec![alternative])),
+        }
+    });
+    conditions.extend(
+        alternatives
+            .into_iter()
+            .filter_map(|(_, expressions)| Expression::any(expressions)),
+    );
+    conditions.extend(
+        excluded
+            .iter()
+            .map(|pair| Expression::context(&to_key(&pair[0])).not_equals((&pair[1]).into())),
+    );
+
+    Ok(Expression::all(conditions).map(|condition| Yaml::Str(condition.to_condition())))
+}
+
+fn one_or_many(mut values: Vec<TypedValue>) -> Yaml {
+    if values.len() == 1 {
+        return to_yaml(&values.remove(0));
+    }
+
+    Yaml::List(values.iter().map(to_yaml).collect())
+}
+
+fn compile_step(
+    runtime: &MicroRuntime,
+    file: &TypedValue,
+    job: &TypedValue,
+    position: &TypedValue,
+) -> Result<Yaml, String> {
+    let key = [file.clone(), job.clone(), position.clone()];
+    let mut entries = vec![];
+
+    if let Some(name) = select_single(runtime, STEP_NAME, &key)? {
+        entries.push(("name".to_string(), to_yaml(&name)));
+    }
+    let written = select_single(runtime, STEP_IF, &key)?;
+    let condition = condition(runtime, written, STEP_RUN_IF, STEP_RUN_UNLESS, &key)
+        .map_err(|err| format!("condition of step {} of job {} in {}: {}", position, job, file, err))?;
+    if let Some(condition) = condition {
+        entries.push(("if".to_string(), condition));
+    }
+    push_attributes(&mut entries, select(runtime, STEP_ATTRIBUTE, &key)?);
+
+    match (
+        select_single(runtime, USES, &key)?,
+        select_single(runtime, RUN, &key)?,
+    ) {
+        (Some(action), None) => entries.push(("uses".to_string(), to_yaml(&action))),
+        (None, Some(command)) => entries.push(("run".to_string(), to_yaml(&command))),
+        _ => {
+            return Err(format!(
+                "step {} of job {} in {} needs exactly one of uses or run",
+                position, job, file
+            ))
+        }
+    }
+
+    let with = select(runtime, WITH, &key)?;
+    if !with.is_empty() {
+        entries.push(("with".to_string(), key_value_map(with, false)));
+    }
+    let env = select(runtime, STEP_ENV, &key)?;
+    if !env.is_empty() {
+        entries.push(("env".to_string(), key_value_map(env, false)));
+    }
+
+    Ok(Yaml::Map(entries))
+}
+
+fn compile_job(
+    runtime: &MicroRuntime,
+    file: &TypedValue,
+    job: &TypedValue,
+    with_matrix: bool,
+) -> Result<Yaml, String> {
+    let key = [file.clone(), job.clone()];
+    let mut entries = vec![];
+
+    if let Some(name) = select_single(runtime, JOB_NAME, &key)? {
+        entries.push(("name".to_string(), to_yaml(&name)));
+    }
+
+    let runners = select_column(runtime, RUNS_ON, &key)?;
+    if runners.is_empty() {
+        return Err(format!("job {} in {} has no runs_on", job, file));
+    }
+    entries.push(("runs-on".to_string(), one_or_many(runners)));
+
+    let needs = select_column(runtime, NEEDS, &key)?;
+    if !needs.is_empty() {
+        entries.push((
+            "needs".to_string(),
+            Yaml::List(needs.iter().map(to_yaml).collect()),
+        ));
+    }
+    let written = select_single(runtime, JOB_IF, &key)?;
+    let condition = condition(runtime, written, RUN_IF, RUN_UNLESS, &key)
+        .map_err(|err| format!("condition of job {} in {}: {}", job, file, err))?;
+    if let Some(condition) = condition {
+        entries.push(("if".to_string(), condition));
+    }
+    let job_permissions = select(runtime, JOB_PERMISSION, &key)?;
+    if !job_permissions.is_empty() {
+        entries.push(("permissions".to_string(), permissions(job_permissions)));
+    }
+    let env = select(runtime, JOB_ENV, &key)?;
+    if !env.is_empty() {
+        entries.push(("env".to_string(), key_value_map(env, false)));
+    }
+    push_attributes(&mut entries, select(runtime, JOB_ATTRIBUTE, &key)?);
+    if with_matrix {
+        if let Some(matrix) = native_matrix(runtime, &key)? {
+            entries.push((
+                "strategy".to_string(),
+                Yaml::Map(vec![("matrix".to_string(), matrix)]),
+            ));
+        }
+    }
+
+    let mut positions = select_column(runtime, STEP, &key)?;
+    if positions.is_empty() {
+        return Err(format!("job {} in {} has no steps", job, file));
+    }
+    positions.sort_by_key(|position| match position {
+        TypedValue::Int(position) => *position,
+        _ => usize::MAX,
+    });
+    let steps = positions
+        .iter()
+        .map(|position| compile_step(runtime, file, job, position))
+        .collect::<Result<_, _>>()?;
+    entries.push(("steps".to_string(), Yaml::List(steps)));
+
+    Ok(Yaml::Map(entries))
+}
+
+pub fn compile_workflow(runtime: &MicroRuntime, file: &str) -> Result<Yaml, String> {
+    let file = TypedValue::from(file);
+    let key = [file.clone()];
+    let mut entries = vec![];
+
+    if let Some(name) = select_single(runtime, WORKFLOW_NAME, &key)? {
+        entries.push(("name".to_string(), to_yaml(&name)));
+    }
+    push_attributes(&mut entries, select(runtime, WORKFLOW_ATTRIBUTE, &key)?);
+
+    let events = select_column(runtime, ON_TRIGGER, &key)?;
+    if events.is_empty() {
+        return Err(format!("workflow {} has no on_trigger", file));
+    }
+    let on = events
+        .iter()
+        .map(|event| {
+            let filters = select(runtime, ON_FILTER, &[file.clone(), event.clone()])?;
+            let filters = if filters.is_empty() {
+                Yaml::Null
+            } else if *event == TypedValue::from("schedule") {
+                // Schedules are a list of single entry maps, one per cron expression
+                Yaml::List(
+                    filters
+                        .iter()
+                        .map(|pair| Yaml::Map(vec![(to_key(&pair[0]), to_yaml(&pair[1]))]))
+                        .collect(),
+                )
+            } else {
+                // A list stands for as many patterns, e.g. branches: [main, release/*]
+                let filters = filters
+                    .into_iter()
+                    .flat_map(|filter| match &filter[1] {
+                        TypedValue::List(items) => items
+                            .iter()
+                            .map(|item| vec![filter[0].clone(), item.clone()])
+                            .collect(),
+                        _ => vec![filter],
+                    })
+                    .collect();
+                key_value_map(filters, true)
+            };
+
+            Ok((to_key(event), filters))
+        })
+        .collect::<Result<_, String>>()?;
+    entries.push(("on".to_string(), Yaml::Map(on)));
+
+    let workflow_permissions = select(runtime, PERMISSION, &key)?;
+    if !workflow_permissions.is_empty() {
+        entries.push(("permissions".to_string(), permissions(workflow_permissions)));
+    }
+    let env = select(runtime, ENV, &key)?;
+    if !env.is_empty() {
+        entries.push(("env".to_string(), key_value_map(env, false)));
+    }
+
+    let unrolled = match select_single(runtime, MATRIX_EMISSION, &key)? {
+        None => false,
+        Some(TypedValue::Str(mode)) if mode == "native" => false,
+        Some(TypedValue::Str(mode)) if mode == "unrolled" => true,
+        Some(mode) => return Err(format!("unknown matrix emission {} for {}", mode, file)),
+    };
+
+    let job_ids = select_column(runtime, JOB, &key)?;
+    if job_ids.is_empty() {
+        return Err(format!("workflow {} has no jobs", file));
+    }
+    let mut jobs = vec![];
+    let mut unrolled_ids = HashMap::new();
+    for job in &job_ids {
+        let job_key = [file.clone(), job.clone()];
+        if !unrolled || native_matrix(runtime, &job_key)?.is_none() {
+            jobs.push((to_key(job), compile_job(runtime, &file, job, true)?));
+            continue;
+        }
+
+        let cells = matrix_cells(runtime, &job_key)?;
+        if cells.is_empty() {
+            return Err(format!(
+                "the matrix of job {} in {} has no cells, are the matrix rules missing?",
+                job, file
+            ));
+        }
+        let compiled_job = compile_job(runtime, &file, job, false)?;
+        let unrolled_jobs = unroll_job(&file, job, &compiled_job, cells)?;
+
+        unrolled_ids.insert(
+            to_key(job),
+            unrolled_jobs.iter().map(|(id, _)| id.clone()).collect(),
+        );
+        jobs.extend(unrolled_jobs);
+    }
+    jobs.iter_mut()
+        .for_each(|(_, job)| rename_needs(job, &unrolled_ids));
+    entries.push(("jobs".to_string(), Yaml::Map(jobs)));
+
+    Ok(Yaml::Map(entries))
+}
+
+pub fn emit_workflow(runtime: &MicroRuntime, file: &str) -> Result<String, String> {
+    Ok(render(&compile_workflow(runtime, file)?))
+}
+
+// Emits every workflow declared with a `workflow(file)` fact, as (file, document) pairs.
+pub fn emit_workflows(runtime: &MicroRuntime) -> Result<Vec<(String, String)>, String> {
+    select_column(runtime, WORKFLOW, &[])?
+        .iter()
+        .map(|file| {
+            let file = to_key(file);
+            let document = emit_workflow(runtime, &file)?;
+
+            Ok((file, document))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::workflows::emitter::{emit_workflow, emit_workflows};
+    use crate::workflows::matrix::with_matrix_rules;
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+
+    #[test]
+    fn test_emit_workflow_from_derived_relations() {
+        // Every crate gets a job testing it, after a shared lint job.
+        let program = program! {
+            workflow(?file)                 <- [ci(?file)],
+            workflow_name(?file, "CI")      <- [ci(?file)],
+            on_trigger(?file, "push")       <- [ci(?file)],
+            on_filter(?file, "push", "branches", "main") <- [ci(?file)],
+            on_trigger(?file, "pull_request") <- [ci(?file)],
+            permission(?file, "contents", "read") <- [ci(?file)],
+            job(?file, ?krate)              <- [ci(?file), krate(?krate)],
+            job(?file, "lint")              <- [ci(?file)],
+            runs_on(?file, "lint", "ubuntu-latest") <- [ci(?file)],
+            step(?file, "lint", 0)          <- [ci(?file)],
+            run(?file, "lint", 0, "cargo clippy -- -D warnings") <- [ci(?file)],
+            runs_on(?file, ?krate, "ubuntu-latest") <- [ci(?file), krate(?krate)],
+            needs(?file, ?krate, "lint")    <- [ci(?file), krate(?krate)],
+            matrix_axis(?file, ?krate, "rust", ?toolchain) <- [ci(?file), krate(?krate), toolchain(?toolchain)],
+            step(?file, ?krate, 0)          <- [ci(?file), krate(?krate)],
+            uses(?file, ?krate, 0, "actions/checkout@v4") <- [ci(?file), krate(?krate)],
+            step(?file, ?krate, 1)          <- [ci(?file), krate(?krate)],
+            run(?file, ?krate, 1, ?command) <- [ci(?file), test_command(?krate, ?command)],
+            step_name(?file, ?krate, 1, "Test") <- [ci(?file), krate(?krate)]
+        };
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("ci", vec!["ci.yml".into()]);
+        runtime.insert("krate", vec!["core".into()]);
+        runtime.insert("toolchain", vec!["stable".into()]);
+        runtime.insert("toolchain", vec!["1.70".into()]);
+        runtime.insert(
+            "test_command",
+            vec!["core".into(), "cargo test -p core\ncargo test -p core --release\n".into()],
+        );
+        runtime.poll();
+
+        let expected = r#"name: CI
+on:
+  pull_request:
+  push:
+    branches:
+      - main
+permissions:
+  contents: read
+jobs:
+  core:
+    runs-on: ubuntu-latest
+    needs:
+      - lint
+    strategy:
+      matrix:
+        rust:
+          - "1.70"
+          - stable
+    steps:
+      - uses: actions/checkout@v4
+      - name: Test
+        run: |
+          cargo test -p core
+          cargo test -p core --release
+  lint:
+    runs-on: ubuntu-latest
+    steps:
+      - run: cargo clippy -- -D warnings
+"#;
+
+        assert_eq!(expected, emit_workflow(&runtime, "ci.yml").unwrap());
+        assert_eq!(
+            vec![("ci.yml".to_string(), expected.to_string())],
+            emit_workflows(&runtime).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_emit_derived_conditions() {
+        let program = program! {
+            workflow(?file)                 <- [ci(?file)],
+            on_trigger(?file, ?event)       <- [ci(?file), deploy_on(?event)],
+            job(?file, "deploy")            <- [ci(?file)],
+            runs_on(?file, "deploy", "ubuntu-latest") <- [ci(?file)],
+            job_if(?file, "deploy", "github.ref == 'refs/heads/main'") <- [ci(?file)],
+            run_if(?file, "deploy", "github.event_name", ?event) <- [ci(?file), deploy_on(?event)],
+            run_unless(?file, "deploy", "github.actor", "dependabot[bot]") <- [ci(?file)],
+            step(?file, "deploy", 0)        <- [ci(?file)],
+            run(?file, "deploy", 0, "make deploy") <- [ci(?file)],
+            step_run_if(?file, "deploy", 0, "inputs.dry_run", false) <- [ci(?file)]
+        };
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("ci", vec!["cd.yml".into()]);
+        runtime.insert("deploy_on", vec!["push".into()]);
+        runtime.insert("deploy_on", vec!["workflow_dispatch".into()]);
+        runtime.poll();
+
+        let expected = r#"on:
+  push:
+  workflow_dispatch:
+jobs:
+  deploy:
+    runs-on: ubuntu-latest
+    if: ${{ github.ref == 'refs/heads/main' && (github.event_name == 'push' || github.event_name == 'workflow_dispatch') && github.actor != 'dependabot[bot]' }}
+    steps:
+      - if: ${{ inputs.dry_run == false }}
+        run: make deploy
+"#;
+        assert_eq!(expected, emit_workflow(&runtime, "cd.yml").unwrap());
+
+
+        let mut runtime = MicroRuntime::new(program! {
+            workflow(?file)                 <- [ci(?file)],
+            on_trigger(?file, "push")       <- [ci(?file)],
+            job(?file, "deploy")            <- [ci(?file)],
+            runs_on(?file, "deploy", "ubuntu-latest") <- [ci(?file)],
+            job_if(?file, "deploy", "github.ref ==") <- [ci(?file)],
+            run_if(?file, "deploy", "github.event_name", "push") <- [ci(?file)],
+            step(?file, "deploy", 0)        <- [ci(?file)],
+            run(?file, "deploy", 0, "make deploy") <- [ci(?file)]
+        });
+        runtime.insert("ci", vec!["cd.yml".into()]);
+        runtime.poll();
+        assert_eq!(
+            Err("condition of job deploy in cd.yml: expected an operand, found the end of the expression"
+                .to_string()),
+            emit_workflow(&runtime, "cd.yml")
+        );
+    }
+
+    #[test]
+    fn test_emit_unrolled_matrix() {
+        let program = with_matrix_rules(program! {
+            workflow(?file)                 <- [ci(?file)],
+            matrix_emission(?file, "unrolled") <- [ci(?file)],
+            on_trigger(?file, "push")       <- [ci(?file)],
+            job(?file, "test")              <- [ci(?file)],
+            runs_on(?file, "test", "${{ matrix.os }}") <- [ci(?file)],
+            matrix_axis(?file, "test", "os", ?os) <- [ci(?file), os(?os)],
+            matrix_axis(?file, "test", "rust", ?toolchain) <- [ci(?file), toolchain(?toolchain)],
+            matrix_exclude(?file, "test", 0, "os", "windows-latest") <- [ci(?file)],
+            matrix_exclude(?file, "test", 0, "rust", "1.70") <- [ci(?file)],
+            step(?file, "test", 0)          <- [ci(?file)],
+            run(?file, "test", 0, "cargo +${{ matrix.rust }} test") <- [ci(?file)],
+            job(?file, "release")           <- [ci(?file)],
+            runs_on(?file, "release", "ubuntu-latest") <- [ci(?file)],
+            needs(?file, "release", "test") <- [ci(?file)],
+            step(?file, "release", 0)       <- [ci(?file)],
+            run(?file, "release", 0, "cargo publish") <- [ci(?file)]
+        });
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("ci", vec!["ci.yml".into()]);
+        runtime.insert("os", vec!["ubuntu-latest".into()]);
+        runtime.insert("os", vec!["windows-latest".into()]);
+        runtime.insert("toolchain", vec!["stable".into()]);
+        runtime.insert("toolchain", vec!["1.70".into()]);
+        runtime.poll();
+
+        let expected = r#"on:
+  push:
+jobs:
+  release:
+    runs-on: ubuntu-latest
+    needs:
+      - test-ubuntu-latest-1_70
+      - test-ubuntu-latest-stable
+      - test-windows-latest-stable
+    steps:
+      - run: cargo publish
+  test-ubuntu-latest-1_70:
+    name: test (ubuntu-latest, 1.70)
+    runs-on: ubuntu-latest
+    steps:
+      - run: cargo +1.70 test
+  test-ubuntu-latest-stable:
+    name: test (ubuntu-latest, stable)
+    runs-on: ubuntu-latest
+    steps:
+      - run: cargo +stable test
+  test-windows-latest-stable:
+    name: test (windows-latest, stable)
+    runs-on: windows-latest
+    steps:
+      - run: cargo +stable test
+"#;
+        assert_eq!(expected, emit_workflow(&runtime, "ci.yml").unwrap());
+
+        // The native block leaves the expansion to GitHub
+        let mut runtime = MicroRuntime::new(program! {
+            workflow(?file)                 <- [ci(?file)],
+            on_trigger(?file, "push")       <- [ci(?file)],
+            job(?file, "test")              <- [ci(?file)],
+            runs_on(?file, "test", "ubuntu-latest") <- [ci(?file)],
+            matrix_axis(?file, "test", "os", "ubuntu-latest") <- [ci(?file)],
+            matrix_exclude(?file, "test", 0, "os", "ubuntu-latest") <- [ci(?file)],
+            matrix_include(?file, "test", 0, "os", "macos-latest") <- [ci(?file)],
+            matrix_include(?file, "test", 0, "xcode", "15") <- [ci(?file)],
+            job_if(?file, "test", "${{ matrix.os != 'windows-latest' }}") <- [ci(?file)],
+            step(?file, "test", 0)          <- [ci(?file)],
+            run(?file, "test", 0, "make")   <- [ci(?file)]
+        });
+        runtime.insert("ci", vec!["ci.yml".into()]);
+        runtime.poll();
+        let expected = r#"on:
+  push:
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    if: ${{ matrix.os != 'windows-latest' }}
+    strategy:
+      matrix:
+        os:
+          - ubuntu-latest
+        include:
+          - os: macos-latest
+            xcode: "15"
+        exclude:
+          - os: ubuntu-latest
+    steps:
+      - run: make
+"#;
+        assert_eq!(expected, emit_workflow(&runtime, "ci.yml").unwrap());
+
+        runtime.insert("matrix_emission", vec!["ci.yml".into(), "unrolled".into()]);
+        runtime.poll();
+        assert_eq!(
+            Err("the matrix of job test in ci.yml has no cells, are the matrix rules missing?"
+                .to_string()),
+            emit_workflow(&runtime, "ci.yml")
+        );
+    }
+
+    #[test]
+    fn test_unrolling_rejects_matrix_expressions() {
+        let program = with_matrix_rules(program! {
+            workflow(?file)                 <- [ci(?file)],
+            matrix_emission(?file, "unrolled") <- [ci(?file)],
+            on_trigger(?file, "push")       <- [ci(?file)],
+            job(?file, "test")              <- [ci(?file)],
+            runs_on(?file, "test", "ubuntu-latest") <- [ci(?file)],
+            matrix_axis(?file, "test", "os", "ubuntu-latest") <- [ci(?file)],
+            job_if(?file, "test", "${{ matrix.os != 'windows-latest' }}") <- [ci(?file)],
+            step(?file, "test", 0)          <- [ci(?file)],
+            run(?file, "test", 0, "make")   <- [ci(?file)]
+        });
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("ci", vec!["ci.yml".into()]);
+        runtime.poll();
+        assert_eq!(
+            Err("job test in ci.yml uses ${{ matrix.os != 'windows-latest' }}, which can't be unrolled"
+                .to_string()),
+            emit_workflow(&runtime, "ci.yml")
+        );
+    }
+
+    #[test]
+    fn test_emit_value_kinds() {
+        let program = program! {
+            workflow(?file)                 <- [ci(?file)],
+            on_trigger(?file, "push")       <- [ci(?file)],
+            on_filter(?file, "push", "branches", ["main", "release/*"]) <- [ci(?file)],
+            job(?file, "test")              <- [ci(?file)],
+            runs_on(?file, "test", "ubuntu-latest") <- [ci(?file)],
+            job_attribute(?file, "test", "timeout-minutes", 7.5) <- [ci(?file)],
+            job_env(?file, "test", "OFFSET", -3) <- [ci(?file)],
+            step(?file, "test", 0)          <- [ci(?file)],
+            run(?file, "test", 0, "make")   <- [ci(?file)]
+        };
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("ci", vec!["ci.yml".into()]);
+        runtime.poll();
+        let expected = r#"on:
+  push:
+    branches:
+      - main
+      - release/*
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    env:
+      OFFSET: -3
+    timeout-minutes: 7.5
+    steps:
+      - run: make
+"#;
+        assert_eq!(expected, emit_workflow(&runtime, "ci.yml").unwrap());
+    }
+
+    #[test]
+    fn test_emit_reports_invalid_workflows() {
+        let program = program! {
+            workflow(?file)           <- [ci(?file)],
+            on_trigger(?file, "push") <- [ci(?file)],
+            job(?file, "build")       <- [ci(?file)],
+            step(?file, "build", 0)   <- [ci(?file)],
+            uses(?file, "build", 0, "actions/checkout@v4") <- [ci(?file)],
+            run(?file, "build", 0, "make") <- [ci(?file)]
+        };
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("ci", vec!["ci.yml".into()]);
+        runtime.poll();
+
+        assert_eq!(
+            Err("job build in ci.yml has no runs_on".to_string()),
+            emit_workflow(&runtime, "ci.yml")
+        );
+
+        runtime.insert("runs_on", vec!["ci.yml".into(), "build".into(), "ubuntu-latest".into()]);
+        runtime.poll();
+        assert_eq!(
+            Err("step 0 of job build in ci.yml needs exactly one of uses or run".to_string()),
+            emit_workflow(&runtime, "ci.yml")
+        );
+    }
+}
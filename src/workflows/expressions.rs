@@ -0,0 +1,446 @@
+use datalog_syntax::TypedValue;
+use std::fmt::{Display, Formatter};
+
+// The expression language of `if:` conditions and `${{ }}` substitutions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    // A context such as `github`, `matrix` or `steps`
+    Context(String),
+    // `object.name`, where name can be `*` to map over arrays
+    Property(Box<Expression>, String),
+    // `object[index]`
+    Index(Box<Expression>, Box<Expression>),
+    Call(String, Vec<Expression>),
+    Not(Box<Expression>),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Or => "||",
+            Operator::And => "&&",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+        }
+    }
+    // Higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Equal | Operator::NotEqual => 3,
+            _ => 4,
+        }
+    }
+}
+
+const NOT_PRECEDENCE: u8 = 5;
+
+impl Expression {
+    // A dotted path such as `github.event_name`.
+    pub fn context(path: &str) -> Self {
+        let mut segments = path.split('.');
+        let root = Expression::Context(segments.next().unwrap_or_default().to_string());
+
+        segments.fold(root, |object, name| {
+            Expression::Property(Box::new(object), name.to_string())
+        })
+    }
+    pub fn binary(self, operator: Operator, other: Expression) -> Self {
+        Expression::Binary(Box::new(self), operator, Box::new(other))
+    }
+    pub fn equals(self, other: Expression) -> Self {
+        self.binary(Operator::Equal, other)
+    }
+    pub fn not_equals(self, other: Expression) -> Self {
+        self.binary(Operator::NotEqual, other)
+    }
+    pub fn negate(self) -> Self {
+        Expression::Not(Box::new(self))
+    }
+    // Folds the operands with `operator`, or returns None when there are none.
+    fn fold(operands: Vec<Expression>, operator: Operator) -> Option<Self> {
+        operands
+            .into_iter()
+            .reduce(|left, right| left.binary(operator, right))
+    }
+    pub fn all(operands: Vec<Expression>) -> Option<Self> {
+        Self::fold(operands, Operator::And)
+    }
+    pub fn any(operands: Vec<Expression>) -> Option<Self> {
+        Self::fold(operands, Operator::Or)
+    }
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Binary(_, operator, _) => operator.precedence(),
+            Expression::Not(_) => NOT_PRECEDENCE,
+            _ => u8::MAX,
+        }
+    }
+    // The expression wrapped in `${{ }}`, as it goes in a workflow.
+    pub fn to_condition(&self) -> String {
+        format!("${{{{ {} }}}}", self)
+    }
+}
+
+impl From<&TypedValue> for Expression {
+    fn from(value: &TypedValue) -> Self {
+        match value {
+            TypedValue::Str(value) => Expression::Str(value.clone()),
+            TypedValue::Int(value) => Expression::Number(*value as f64),
+            TypedValue::Bool(value) => Expression::Bool(*value),
+            TypedValue::I64(value) => Expression::Number(*value as f64),
+            TypedValue::Float(value) => Expression::Number(value.get()),
+            // Expressions have no list literals, lists are compared by their text
+            value => Expression::Str(value.to_string()),
+        }
+    }
+}
+
+fn fmt_operand(f: &mut Formatter<'_>, operand: &Expression, parenthesized: bool) -> std::fmt::Result {
+    if parenthesized {
+        write!(f, "({})", operand)
+    } else {
+        write!(f, "{}", operand)
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Null => write!(f, "null"),
+            Expression::Bool(value) => write!(f, "{}", value),
+            Expression::Number(value) => write!(f, "{}", value),
+            Expression::Str(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            Expression::Context(name) => write!(f, "{}", name),
+            Expression::Property(object, name) => write!(f, "{}.{}", object, name),
+            Expression::Index(object, index) => write!(f, "{}[{}]", object, index),
+            Expression::Call(function, arguments) => {
+                let arguments: Vec<_> = arguments.iter().map(|argument| argument.to_string()).collect();
+                write!(f, "{}({})", function, arguments.join(", "))
+            }
+            Expression::Not(operand) => {
+                write!(f, "!")?;
+                fmt_operand(f, operand, operand.precedence() < NOT_PRECEDENCE)
+            }
+            // Operators are left associative
+            Expression::Binary(left, operator, right) => {
+                fmt_operand(f, left, left.precedence() < operator.precedence())?;
+                write!(f, " {} ", operator.symbol())?;
+                fmt_operand(f, right, right.precedence() <= operator.precedence())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Identifier(String),
+    Literal(Expression),
+    Operator(Operator),
+    Punctuation(&'static str),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut characters = text.char_indices().peekable();
+
+    while let Some(&(index, character)) = characters.peek() {
+        let token = match character {
+            character if character.is_whitespace() => {
+                characters.next();
+                continue;
+            }
+            '\'' => {
+                characters.next();
+                let mut value = String::new();
+                loop {
+                    match characters.next() {
+                        Some((_, '\'')) if characters.peek().is_some_and(|(_, c)| *c == '\'') => {
+                            characters.next();
+                            value.push('\'');
+                        }
+                        Some((_, '\'')) => break,
+                        Some((_, character)) => value.push(character),
+                        None => return Err(format!("unterminated string at {}", index)),
+                    }
+                }
+                Token::Literal(Expression::Str(value))
+            }
+            character if character.is_ascii_digit() || character == '-' => {
+                let mut number = String::new();
+                while let Some(&(_, character)) = characters.peek() {
+                    if !(character.is_ascii_alphanumeric() || ".-+".contains(character)) {
+                        break;
+                    }
+                    number.push(character);
+                    characters.next();
+                }
+                let value = match number.strip_prefix("0x") {
+                    Some(hexadecimal) => i64::from_str_radix(hexadecimal, 16).map(|value| value as f64).ok(),
+                    None => number.parse::<f64>().ok(),
+                };
+                match value {
+                    Some(value) => Token::Literal(Expression::Number(value)),
+                    None => return Err(format!("invalid number {} at {}", number, index)),
+                }
+            }
+            character if character.is_alphabetic() || character == '_' => {
+                let mut name = String::new();
+                while let Some(&(_, character)) = characters.peek() {
+                    if !(character.is_alphanumeric() || character == '_' || character == '-') {
+                        break;
+                    }
+                    name.push(character);
+                    characters.next();
+                }
+                match name.as_str() {
+                    "true" => Token::Literal(Expression::Bool(true)),
+                    "false" => Token::Literal(Expression::Bool(false)),
+                    "null" => Token::Literal(Expression::Null),
+                    _ => Token::Identifier(name),
+                }
+            }
+            _ => {
+                characters.next();
+                let next = characters.peek().map(|(_, character)| *character);
+                let mut two_characters = |token| {
+                    characters.next();
+                    token
+                };
+                match (character, next) {
+                    ('=', Some('=')) => two_characters(Token::Operator(Operator::Equal)),
+                    ('!', Some('=')) => two_characters(Token::Operator(Operator::NotEqual)),
+                    ('<', Some('=')) => two_characters(Token::Operator(Operator::LessOrEqual)),
+                    ('>', Some('=')) => two_characters(Token::Operator(Operator::GreaterOrEqual)),
+                    ('&', Some('&')) => two_characters(Token::Operator(Operator::And)),
+                    ('|', Some('|')) => two_characters(Token::Operator(Operator::Or)),
+                    ('<', _) => Token::Operator(Operator::Less),
+                    ('>', _) => Token::Operator(Operator::Greater),
+                    ('!', _) => Token::Punctuation("!"),
+                    ('(', _) => Token::Punctuation("("),
+                    (')', _) => Token::Punctuation(")"),
+                    ('[', _) => Token::Punctuation("["),
+                    (']', _) => Token::Punctuation("]"),
+                    ('.', _) => Token::Punctuation("."),
+                    (',', _) => Token::Punctuation(","),
+                    ('*', _) => Token::Punctuation("*"),
+                    _ => return Err(format!("unexpected character {:?} at {}", character, index)),
+                }
+            }
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+    fn eat(&mut self, punctuation: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Punctuation(found)) if *found == punctuation);
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+    fn expect(&mut self, punctuation: &str) -> Result<(), String> {
+        if !self.eat(punctuation) {
+            return Err(format!("expected {}, found {}", punctuation, self.describe_next()));
+        }
+        Ok(())
+    }
+    fn describe_next(&self) -> String {
+        match self.peek() {
+            Some(Token::Identifier(name)) => name.clone(),
+            Some(Token::Literal(literal)) => literal.to_string(),
+            Some(Token::Operator(operator)) => operator.symbol().to_string(),
+            Some(Token::Punctuation(punctuation)) => punctuation.to_string(),
+            None => "the end of the expression".to_string(),
+        }
+    }
+    // Precedence climbing over the binary operators
+    fn binary(&mut self, minimum_precedence: u8) -> Result<Expression, String> {
+        let mut left = self.unary()?;
+        while let Some(Token::Operator(operator)) = self.peek().cloned() {
+            if operator.precedence() < minimum_precedence {
+                break;
+            }
+            self.index += 1;
+            let right = self.binary(operator.precedence() + 1)?;
+            left = left.binary(operator, right);
+        }
+
+        Ok(left)
+    }
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.eat("!") {
+            return Ok(self.unary()?.negate());
+        }
+
+        let mut expression = match self.next() {
+            Some(Token::Literal(literal)) => literal,
+            Some(Token::Punctuation("(")) => {
+                let expression = self.binary(0)?;
+                self.expect(")")?;
+                expression
+            }
+            Some(Token::Identifier(name)) if self.eat("(") => {
+                let mut arguments = vec![];
+                while !self.eat(")") {
+                    arguments.push(self.binary(0)?);
+                    if !self.eat(",") {
+                        self.expect(")")?;
+                        break;
+                    }
+                }
+                Expression::Call(name, arguments)
+            }
+            Some(Token::Identifier(name)) => Expression::Context(name),
+            _ => {
+                self.index -= 1;
+                return Err(format!("expected an operand, found {}", self.describe_next()));
+            }
+        };
+
+        loop {
+            if self.eat(".") {
+                let name = match self.next() {
+                    Some(Token::Identifier(name)) => name,
+                    Some(Token::Punctuation("*")) => "*".to_string(),
+                    _ => {
+                        self.index -= 1;
+                        return Err(format!("expected a property, found {}", self.describe_next()));
+                    }
+                };
+                expression = Expression::Property(Box::new(expression), name);
+            } else if self.eat("[") {
+                let index = self.binary(0)?;
+                self.expect("]")?;
+                expression = Expression::Index(Box::new(expression), Box::new(index));
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+}
+
+// Parses an expression, with or without its `${{ }}` wrapping.
+pub fn parse_expression(text: &str) -> Result<Expression, String> {
+    let trimmed = text.trim();
+    let text = trimmed
+        .strip_prefix("${{")
+        .and_then(|inner| inner.strip_suffix("}}"))
+        .unwrap_or(trimmed);
+
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        index: 0,
+    };
+    let expression = parser.binary(0)?;
+    if parser.peek().is_some() {
+        return Err(format!("unexpected {} in {}", parser.describe_next(), text.trim()));
+    }
+
+    Ok(expression)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::workflows::expressions::{parse_expression, Expression, Operator};
+
+    #[test]
+    fn test_display() {
+        let on_push = Expression::context("github.event_name").equals(Expression::Str("push".into()));
+        let on_main = Expression::context("github.ref").equals(Expression::Str("refs/heads/main".into()));
+        let labelled = Expression::Call(
+            "contains".to_string(),
+            vec![
+                Expression::Property(
+                    Box::new(Expression::context("github.event.pull_request.labels")),
+                    "*".to_string(),
+                )
+                .binary(Operator::Or, Expression::Null),
+                Expression::Str("it's fine".into()),
+            ],
+        );
+
+        let condition = Expression::any(vec![
+            Expression::all(vec![on_push, on_main]).unwrap(),
+            labelled.negate(),
+        ])
+        .unwrap();
+        assert_eq!(
+            "${{ github.event_name == 'push' && github.ref == 'refs/heads/main' || !contains(github.event.pull_request.labels.* || null, 'it''s fine') }}",
+            condition.to_condition()
+        );
+
+        let grouped = Expression::Bool(true)
+            .binary(Operator::Or, Expression::Bool(false))
+            .binary(Operator::And, Expression::Number(1.5).binary(Operator::Less, Expression::Number(2.0)).negate());
+        assert_eq!("(true || false) && !(1.5 < 2)", grouped.to_string());
+        assert_eq!(None, Expression::all(vec![]));
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        let sources = [
+            "github.event_name == 'push' && github.ref == 'refs/heads/main' || !contains(github.event.pull_request.labels.*.name, 'it''s fine')",
+            "(true || false) && !(1.5 < 2)",
+            "steps.build-step.outputs['cache-hit'] != 'true' && matrix.os >= 3",
+            "success() && fromJSON(needs.setup.outputs.matrix)[0] == null",
+        ];
+        for source in sources {
+            assert_eq!(source, parse_expression(source).unwrap().to_string());
+        }
+
+        assert_eq!(
+            parse_expression("a == 'b'").unwrap(),
+            parse_expression("${{ a == 'b' }}").unwrap()
+        );
+        // Redundant parentheses go away
+        assert_eq!("a && b && c", parse_expression("(a && b) && c").unwrap().to_string());
+        assert_eq!("a && (b || c)", parse_expression("a && (b || c)").unwrap().to_string());
+
+        assert_eq!(
+            Err("expected ), found the end of the expression".to_string()),
+            parse_expression("contains(a, 'b'")
+        );
+        assert!(parse_expression("a == 'b").is_err());
+        assert!(parse_expression("a = b").is_err());
+        assert!(parse_expression("a b").is_err());
+    }
+}
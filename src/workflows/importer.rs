@@ -0,0 +1,593 @@
+use crate::engine::datalog::MicroRuntime;
+use crate::workflows::vocabulary::*;
+use crate::workflows::yaml::{parse, Yaml};
+use datalog_syntax::{AnonymousGroundAtom, TypedValue};
+use std::path::Path;
+
+// Negative integers become I64 values. Floats become Float values when those are emitted with the
+// same spelling, e.g. 7.5, and strings otherwise, e.g. 3.10, which must not turn into 3.1.
+fn to_value(value: &Yaml, location: &str) -> Result<TypedValue, String> {
+    match value {
+        Yaml::Real(inner) => match inner.parse::<f64>() {
+            Ok(float) if float.is_finite() && format!("{:?}", float) == *inner => Ok(float.into()),
+            _ => Ok(inner.as_str().into()),
+        },
+        Yaml::Str(inner) => Ok(inner.as_str().into()),
+        Yaml::Int(inner) if *inner >= 0 => Ok((*inner as usize).into()),
+        Yaml::Int(inner) => Ok((*inner).into()),
+        Yaml::Bool(inner) => Ok((*inner).into()),
+        _ => Err(format!("expected a scalar at {}", location)),
+    }
+}
+
+// Accepts either a single scalar or a list of them.
+fn to_values(value: &Yaml, location: &str) -> Result<Vec<TypedValue>, String> {
+    match value {
+        Yaml::List(items) => items.iter().map(|item| to_value(item, location)).collect(),
+        value => Ok(vec![to_value(value, location)?]),
+    }
+}
+
+fn to_entries<'a>(value: &'a Yaml, location: &str) -> Result<&'a [(String, Yaml)], String> {
+    match value {
+        Yaml::Map(entries) => Ok(entries),
+        _ => Err(format!("expected a mapping at {}", location)),
+    }
+}
+
+fn unsupported(key: &str, location: &str) -> String {
+    format!("unsupported key {} at {}", key, location)
+}
+
+// Collects the facts of a workflow, which are only inserted once all of it has been validated.
+struct Importer {
+    file: TypedValue,
+    facts: Vec<(String, AnonymousGroundAtom)>,
+}
+
+impl Importer {
+    fn insert(&mut self, relation: &str, columns: Vec<TypedValue>) -> Result<(), String> {
+        let mut ground_atom = vec![self.file.clone()];
+        ground_atom.extend(columns);
+        self.facts.push((relation.to_string(), ground_atom));
+
+        Ok(())
+    }
+
+    fn insert_pairs(
+        &mut self,
+        relation: &str,
+        prefix: &[TypedValue],
+        value: &Yaml,
+        location: &str,
+    ) -> Result<(), String> {
+        for (key, value) in to_entries(value, location)? {
+            let location = format!("{}.{}", location, key);
+            let mut columns = prefix.to_vec();
+            columns.push(key.as_str().into());
+            columns.push(to_value(value, &location)?);
+
+            self.insert(relation, columns)?;
+        }
+
+        Ok(())
+    }
+
+    fn import_permissions(
+        &mut self,
+        relation: &str,
+        prefix: &[TypedValue],
+        value: &Yaml,
+        location: &str,
+    ) -> Result<(), String> {
+        match value {
+            Yaml::Str(level) => {
+                let mut columns = prefix.to_vec();
+                columns.push("*".into());
+                columns.push(level.as_str().into());
+
+                self.insert(relation, columns)
+            }
+            // No permission at all, the least privilege GitHub recommends
+            Yaml::Map(entries) if entries.is_empty() => {
+                let mut columns = prefix.to_vec();
+                columns.push("*".into());
+                columns.push("none".into());
+
+                self.insert(relation, columns)
+            }
+            value => self.insert_pairs(relation, prefix, value, location),
+        }
+    }
+
+    fn import_on(&mut self, value: &Yaml) -> Result<(), String> {
+        let entries = match value {
+            Yaml::Map(entries) => entries.clone(),
+            events => to_values(events, "on")?
+                .into_iter()
+                .map(|event| (event.to_string(), Yaml::Null))
+                .collect(),
+        };
+
+        for (event, filters) in &entries {
+            let location = format!("on.{}", event);
+            self.insert(ON_TRIGGER, vec![event.as_str().into()])?;
+
+            let filters = match filters {
+                Yaml::Null => vec![],
+                // Schedules come as a list of single entry maps
+                Yaml::List(items) => items
+                    .iter()
+                    .map(|item| to_entries(item, &location))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .concat(),
+                filters => to_entries(filters, &location)?.to_vec(),
+            };
+            for (filter, values) in &filters {
+                let location = format!("{}.{}", location, filter);
+                for filter_value in to_values(values, &location)? {
+                    self.insert(
+                        ON_FILTER,
+                        vec![event.as_str().into(), filter.as_str().into(), filter_value],
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn import_step(
+        &mut self,
+        job: &TypedValue,
+        position: usize,
+        value: &Yaml,
+    ) -> Result<(), String> {
+        let location = format!("jobs.{}.steps[{}]", job, position);
+        let key = vec![job.clone(), position.into()];
+        self.insert(STEP, key.clone())?;
+
+        for (step_key, value) in to_entries(value, &location)? {
+            let location = format!("{}.{}", location, step_key);
+            let relation = match step_key.as_str() {
+                "with" => {
+                    self.insert_pairs(WITH, &key, value, &location)?;
+                    continue;
+                }
+                "env" => {
+                    self.insert_pairs(STEP_ENV, &key, value, &location)?;
+                    continue;
+                }
+                "name" => STEP_NAME,
+                "if" => STEP_IF,
+                "uses" => USES,
+                "run" => RUN,
+                _ => {
+                    let attribute =
+                        to_value(value, &location).map_err(|_| unsupported(step_key, &location))?;
+                    let mut columns = key.clone();
+                    columns.extend([step_key.as_str().into(), attribute]);
+                    self.insert(STEP_ATTRIBUTE, columns)?;
+                    continue;
+                }
+            };
+
+            let mut columns = key.clone();
+            columns.push(to_value(value, &location)?);
+            self.insert(relation, columns)?;
+        }
+
+        Ok(())
+    }
+
+    // Entries are told apart by their position in the list.
+    fn import_matrix_entries(
+        &mut self,
+        relation: &str,
+        job: &TypedValue,
+        value: &Yaml,
+        location: &str,
+    ) -> Result<(), String> {
+        let Yaml::List(entries) = value else {
+            return Err(format!("expected a list at {}", location));
+        };
+
+        for (position, entry) in entries.iter().enumerate() {
+            let location = format!("{}.{}", location, position);
+            for (key, value) in to_entries(entry, &location)? {
+                let location = format!("{}.{}", location, key);
+                let columns = vec![
+                    job.clone(),
+                    position.into(),
+                    key.as_str().into(),
+                    to_value(value, &location)?,
+                ];
+                self.insert(relation, columns)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn import_strategy(&mut self, job: &TypedValue, value: &Yaml) -> Result<(), String> {
+        let location = format!("jobs.{}.strategy", job);
+
+        for (strategy_key, value) in to_entries(value, &location)? {
+            let location = format!("{}.{}", location, strategy_key);
+            if strategy_key != "matrix" {
+                return Err(unsupported(strategy_key, &location));
+            }
+
+            for (matrix_key, values) in to_entries(value, &location)? {
+                let location = format!("{}.{}", location, matrix_key);
+                let relation = match matrix_key.as_str() {
+                    "include" => MATRIX_INCLUDE,
+                    "exclude" => MATRIX_EXCLUDE,
+                    _ => MATRIX_AXIS,
+                };
+                if relation != MATRIX_AXIS {
+                    self.import_matrix_entries(relation, job, values, &location)?;
+                    continue;
+                }
+
+                for matrix_value in to_values(values, &location)? {
+                    self.insert(
+                        MATRIX_AXIS,
+                        vec![job.clone(), matrix_key.as_str().into(), matrix_value],
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn import_job(&mut self, job: &str, value: &Yaml) -> Result<(), String> {
+        let location = format!("jobs.{}", job);
+        let job = TypedValue::from(job);
+        let key = vec![job.clone()];
+        self.insert(JOB, key.clone())?;
+
+        for (job_key, value) in to_entries(value, &location)? {
+            let location = format!("{}.{}", location, job_key);
+            let relation = match job_key.as_str() {
+                "steps" => {
+                    let Yaml::List(steps) = value else {
+                        return Err(format!("expected a list at {}", location));
+                    };
+                    for (position, step) in steps.iter().enumerate() {
+                        self.import_step(&job, position, step)?;
+                    }
+                    continue;
+                }
+                "strategy" => {
+                    self.import_strategy(&job, value)?;
+                    continue;
+                }
+                "permissions" => {
+                    self.import_permissions(JOB_PERMISSION, &key, value, &location)?;
+                    continue;
+                }
+                "env" => {
+                    self.insert_pairs(JOB_ENV, &key, value, &location)?;
+                    continue;
+                }
+                "name" => JOB_NAME,
+                "if" => JOB_IF,
+                "runs-on" => RUNS_ON,
+                "needs" => NEEDS,
+                _ => {
+                    let attribute =
+                        to_value(value, &location).map_err(|_| unsupported(job_key, &location))?;
+                    self.insert(
+                        JOB_ATTRIBUTE,
+                        vec![job.clone(), job_key.as_str().into(), attribute],
+                    )?;
+                    continue;
+                }
+            };
+
+            for column in to_values(value, &location)? {
+                self.insert(relation, vec![job.clone(), column])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn import_workflow(&mut self, document: &Yaml) -> Result<(), String> {
+        self.insert(WORKFLOW, vec![])?;
+
+        for (key, value) in to_entries(document, "the top level")? {
+            match key.as_str() {
+                "name" => self.insert(WORKFLOW_NAME, vec![to_value(value, key)?])?,
+                "on" => self.import_on(value)?,
+                "permissions" => self.import_permissions(PERMISSION, &[], value, key)?,
+                "env" => self.insert_pairs(ENV, &[], value, key)?,
+                "jobs" => {
+                    for (job, value) in to_entries(value, key)? {
+                        self.import_job(job, value)?;
+                    }
+                }
+                _ => {
+                    let attribute = to_value(value, key).map_err(|_| unsupported(key, key))?;
+                    self.insert(WORKFLOW_ATTRIBUTE, vec![key.as_str().into(), attribute])?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Inserts the workflow in `source` as facts keyed by `file`, using the same relations the emitter
+// reads. Constructs the vocabulary can't express are reported rather than dropped, so that a
+// workflow never silently changes meaning on its way through the runtime, in which case nothing is
+// inserted. Nothing is materialised until the next poll.
+pub fn import_workflow(runtime: &mut MicroRuntime, file: &str, source: &str) -> Result<(), String> {
+    let document = parse(source).map_err(|err| format!("{}: {}", file, err))?;
+    let mut importer = Importer {
+        file: file.into(),
+        facts: vec![],
+    };
+    importer
+        .import_workflow(&document)
+        .map_err(|err| format!("{}: {}", file, err))?;
+
+    runtime.try_insert_all(importer.facts)
+}
+
+// Same as import_workflow, keyed by the file name, e.g. "ci.yml".
+pub fn import_workflow_file(
+    runtime: &mut MicroRuntime,
+    path: impl AsRef<Path>,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    let source =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let file = path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .ok_or_else(|| format!("{} has no file name", path.display()))?;
+
+    import_workflow(runtime, file, &source)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::workflows::emitter::emit_workflow;
+    use crate::workflows::importer::import_workflow;
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+
+    const WORKFLOW: &str = r#"name: CI
+run-name: CI for ${{ github.ref }}
+on:
+  push:
+    branches:
+      - main
+  schedule:
+    - cron: "0 3 * * 1"
+permissions: read-all
+env:
+  CARGO_TERM_COLOR: always
+jobs:
+  test:
+    runs-on: ${{ matrix.os }}
+    needs: lint
+    timeout-minutes: 30
+    strategy:
+      matrix:
+        os:
+          - macos-latest
+          - ubuntu-latest
+        rust:
+          - "1.70"
+          - stable
+        exclude:
+          - os: macos-latest
+            rust: "1.70"
+        include:
+          - os: ubuntu-latest
+            rust: nightly
+            experimental: true
+    steps:
+      - uses: actions/checkout@v4
+        with:
+          fetch-depth: 0
+      - name: Test
+        id: test
+        run: |
+          cargo test
+          cargo test --release
+        env:
+          RUST_BACKTRACE: 1
+  lint:
+    runs-on: ubuntu-latest
+    permissions:
+      contents: read
+      checks: write
+    steps:
+      - run: cargo clippy -- -D warnings
+"#;
+
+    #[test]
+    fn test_import_emit_roundtrip() {
+        // Finds every job checking out the repository
+        let program = program! {
+            checks_out(?file, ?job) <- [uses(?file, ?job, ?position, "actions/checkout@v4")]
+        };
+        let mut runtime = MicroRuntime::new(program);
+        import_workflow(&mut runtime, "ci.yml", WORKFLOW).unwrap();
+        runtime.poll();
+
+        let checks_out: Vec<_> = runtime
+            .query(&build_query!(checks_out(_, _)))
+            .unwrap()
+            .collect();
+        assert_eq!(
+            vec![vec![TypedValue::from("ci.yml"), "test".into()]],
+            checks_out
+        );
+        assert!(runtime
+            .contains(
+                "step_env",
+                &vec![
+                    "ci.yml".into(),
+                    "test".into(),
+                    1usize.into(),
+                    "RUST_BACKTRACE".into(),
+                    1usize.into()
+                ]
+            )
+            .unwrap());
+
+        // Keys come out in the emitter's order, jobs in alphabetical order
+        let expected = r#"name: CI
+run-name: CI for ${{ github.ref }}
+on:
+  push:
+    branches:
+      - main
+  schedule:
+    - cron: 0 3 * * 1
+permissions: read-all
+env:
+  CARGO_TERM_COLOR: always
+jobs:
+  lint:
+    runs-on: ubuntu-latest
+    permissions:
+      checks: write
+      contents: read
+    steps:
+      - run: cargo clippy -- -D warnings
+  test:
+    runs-on: ${{ matrix.os }}
+    needs:
+      - lint
+    timeout-minutes: 30
+    strategy:
+      matrix:
+        os:
+          - macos-latest
+          - ubuntu-latest
+        rust:
+          - "1.70"
+          - stable
+        include:
+          - experimental: true
+            os: ubuntu-latest
+            rust: nightly
+        exclude:
+          - os: macos-latest
+            rust: "1.70"
+    steps:
+      - uses: actions/checkout@v4
+        with:
+          fetch-depth: 0
+      - name: Test
+        id: test
+        run: |
+          cargo test
+          cargo test --release
+        env:
+          RUST_BACKTRACE: 1
+"#;
+        let emitted = emit_workflow(&runtime, "ci.yml").unwrap();
+        assert_eq!(expected, emitted);
+
+        // Importing the emitted workflow again yields the very same document
+        let mut reimported = MicroRuntime::new(program! { unused(?x) <- [nothing(?x)] });
+        import_workflow(&mut reimported, "ci.yml", &emitted).unwrap();
+        reimported.poll();
+        assert_eq!(emitted, emit_workflow(&reimported, "ci.yml").unwrap());
+    }
+
+    #[test]
+    fn test_import_reports_unsupported_constructs() {
+        let mut runtime = MicroRuntime::new(program! { unused(?x) <- [nothing(?x)] });
+
+        let source = "on: push
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    strategy:
+      matrix:
+        include:
+          - os: [windows-latest]
+    steps:
+      - run: make
+";
+        assert_eq!(
+            Err("ci.yml: expected a scalar at jobs.test.strategy.matrix.include.0.os".to_string()),
+            import_workflow(&mut runtime, "ci.yml", source)
+        );
+        assert_eq!(
+            Err("ci.yml: unsupported key services at jobs.test.services".to_string()),
+            import_workflow(
+                &mut runtime,
+                "ci.yml",
+                "on: push\njobs:\n  test:\n    services:\n      redis:\n        image: redis\n"
+            )
+        );
+        assert!(import_workflow(&mut runtime, "ci.yml", "on: [push\n").is_err());
+        // Facts that don't fit the program, e.g. by arity
+        let mut narrow = MicroRuntime::new(program! { named(?x) <- [job(?x)] });
+        assert_eq!(
+            Err("job: relation of arity 1 cannot hold a fact of arity 2".to_string()),
+            import_workflow(&mut narrow, "ci.yml", "on: push\njobs:\n  test:\n    steps: []\n")
+        );
+        assert!(narrow.safe());
+        // Not even the facts before the error are inserted
+        assert!(runtime.safe());
+    }
+
+    #[test]
+    fn test_import_empty_permissions() {
+        let source = "on:
+  push:
+permissions: {}
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    permissions: {}
+    steps:
+      - run: make
+";
+        let mut runtime = MicroRuntime::new(program! { unused(?x) <- [nothing(?x)] });
+        import_workflow(&mut runtime, "ci.yml", source).unwrap();
+        runtime.poll();
+
+        let none = vec![TypedValue::from("ci.yml"), "*".into(), "none".into()];
+        assert!(runtime.contains("permission", &none).unwrap());
+        assert_eq!(source, emit_workflow(&runtime, "ci.yml").unwrap());
+    }
+
+    #[test]
+    fn test_import_floats() {
+        let source = "on:
+  push:
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    timeout-minutes: 7.5
+    steps:
+      - uses: actions/setup-python@v5
+        with:
+          python-version: 3.10
+";
+        let mut runtime = MicroRuntime::new(program! { unused(?x) <- [nothing(?x)] });
+        import_workflow(&mut runtime, "ci.yml", source).unwrap();
+        runtime.poll();
+
+        let timeout = vec!["ci.yml".into(), "test".into(), "timeout-minutes".into(), 7.5.into()];
+        assert!(runtime.contains("job_attribute", &timeout).unwrap());
+        // 3.10 is kept as it is spelled, hence as a string
+        assert_eq!(
+            source.replace("3.10", "\"3.10\""),
+            emit_workflow(&runtime, "ci.yml").unwrap()
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use crate::engine::datalog::MicroRuntime;
+use crate::modules::pack;
+use crate::workflows::emitter::select;
+use crate::workflows::importer::import_workflow;
+use crate::workflows::vocabulary::DIAGNOSTIC;
+use datalog_rule_macro::program;
+use datalog_syntax::*;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+pub const LINT_NAMESPACE: &str = "lints";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub file: String,
+    pub job: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}: job {}: {}", self.severity, self.file, self.job, self.message)
+    }
+}
+
+// Actions are pinned when they can't change under our feet: local ones, and those referenced by a
+// full commit SHA or image digest.
+fn is_pinned(action: &str) -> bool {
+    if action.starts_with("./") {
+        return true;
+    }
+    if let Some(image) = action.strip_prefix("docker://") {
+        return image.contains("@sha256:");
+    }
+
+    action.rsplit_once('@').is_some_and(|(_, reference)| {
+        reference.len() == 40 && reference.chars().all(|character| character.is_ascii_hexdigit())
+    })
+}
+
+fn pinned(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    match args["a"] {
+        TypedValue::Str(action) => is_pinned(action).into(),
+        _ => false.into(),
+    }
+}
+
+fn references_secrets(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    match args["v"] {
+        TypedValue::Str(value) => ["secrets.", "secrets[", "github.token"]
+            .iter()
+            .any(|pattern| value.contains(pattern))
+            .into(),
+        _ => false.into(),
+    }
+}
+
+fn unpinned_message(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    format!("{} is not pinned to a commit SHA", args["a"]).into()
+}
+
+fn undefined_job_message(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    format!("needs undefined job {}", args["n"]).into()
+}
+
+// Derives on_cycle(file, job) for the jobs on a cycle of needs, along with needs_path, the jobs
+// each job needs directly or not. Shared by the packs that have to tell cycles apart, each of them
+// keeping the relations in its own namespace.
+pub(crate) fn cycle_rules() -> Program {
+    program! {
+        needs_path(?f, ?j, ?n)   <- [needs(?f, ?j, ?n)],
+        needs_path(?f, ?j, ?m)   <- [needs_path(?f, ?j, ?n), needs(?f, ?n, ?m)],
+        same_job(?f, ?j, ?j)     <- [job(?f, ?j)],
+        on_cycle(?f, ?j)         <- [needs_path(?f, ?j, ?n), same_job(?f, ?j, ?n)],
+    }
+}
+
+// Derives diagnostic facts out of the workflow vocabulary. Add them to a program with
+// with_lint_rules so that generated workflows are checked as well. Their other relations are
+// private to the lints namespace, e.g. lints::needs_path.
+pub fn lint_rules() -> Program {
+    let mut rules = cycle_rules().inner;
+    rules.extend(program! {
+        // Cycles in needs
+        diagnostic("error", ?f, ?j, "job is part of a cycle of needs") <- [on_cycle(?f, ?j)],
+        // Undefined jobs
+        diagnostic("error", ?f, ?j, undefined_job_message(?n)) <- [needs(?f, ?j, ?n), !job(?f, ?n)],
+        // Unpinned actions
+        action_pinned(?a, pinned(?a)) <- [uses(?f, ?j, ?p, ?a)],
+        diagnostic("warning", ?f, ?j, unpinned_message(?a)) <- [uses(?f, ?j, ?p, ?a), action_pinned(?a, false)],
+        // Missing permissions, at the workflow and at the job level alike
+        diagnostic("warning", ?f, ?j, "job does not restrict the permissions of GITHUB_TOKEN") <- [job(?f, ?j), !permission(?f, ?s, ?l), !job_permission(?f, ?j, ?t, ?m)],
+        // Secrets exposed to pull_request_target, which runs with access to them for any fork
+        secret_reference(?v, references_secrets(?v)) <- [run(?f, ?j, ?p, ?v)],
+        secret_reference(?v, references_secrets(?v)) <- [with(?f, ?j, ?p, ?k, ?v)],
+        secret_reference(?v, references_secrets(?v)) <- [step_env(?f, ?j, ?p, ?k, ?v)],
+        secret_reference(?v, references_secrets(?v)) <- [job_env(?f, ?j, ?k, ?v)],
+        uses_secrets(?f, ?j)     <- [run(?f, ?j, ?p, ?v), secret_reference(?v, true)],
+        uses_secrets(?f, ?j)     <- [with(?f, ?j, ?p, ?k, ?v), secret_reference(?v, true)],
+        uses_secrets(?f, ?j)     <- [step_env(?f, ?j, ?p, ?k, ?v), secret_reference(?v, true)],
+        uses_secrets(?f, ?j)     <- [job_env(?f, ?j, ?k, ?v), secret_reference(?v, true)],
+        diagnostic("error", ?f, ?j, "secrets are used in a pull_request_target workflow") <- [on_trigger(?f, "pull_request_target"), uses_secrets(?f, ?j)],
+    }.inner);
+
+    pack(LINT_NAMESPACE, Program::from(rules), &[DIAGNOSTIC])
+}
+
+pub fn with_lint_rules(program: Program) -> Program {
+    let mut rules = program.inner;
+    rules.extend(lint_rules().inner);
+
+    Program::from(rules)
+}
+
+// Reads every diagnostic fact, errors first within a job.
+pub fn collect_diagnostics(runtime: &MicroRuntime) -> Result<Vec<Diagnostic>, String> {
+    let mut diagnostics = select(runtime, DIAGNOSTIC, &[])?
+        .into_iter()
+        .map(|fact| {
+            let [severity, file, job, message] = fact.as_slice() else {
+                return Err(format!("malformed diagnostic {:?}", fact));
+            };
+            let severity = match severity.to_string().as_str() {
+                "error" => Severity::Error,
+                "warning" => Severity::Warning,
+                other => return Err(format!("unknown diagnostic severity {}", other)),
+            };
+
+            Ok(Diagnostic {
+                file: file.to_string(),
+                job: job.to_string(),
+                severity,
+                message: message.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    diagnostics.sort();
+
+    Ok(diagnostics)
+}
+
+// Lints (file, source) pairs of workflow YAML.
+pub fn lint_workflows(sources: &[(&str, &str)]) -> Result<Vec<Diagnostic>, String> {
+    let mut runtime = MicroRuntime::new(lint_rules());
+    for (file, source) in sources {
+        import_workflow(&mut runtime, file, source)?;
+    }
+    runtime.try_poll()?;
+
+    collect_diagnostics(&runtime)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::workflows::lints::{
+        collect_diagnostics, is_pinned, lint_workflows, with_lint_rules, Diagnostic, Severity,
+    };
+    use datalog_rule_macro::program;
+    use datalog_syntax::*;
+
+    fn diagnostic(severity: Severity, file: &str, job: &str, message: &str) -> Diagnostic {
+        Diagnostic {
+            file: file.to_string(),
+            job: job.to_string(),
+            severity,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_is_pinned() {
+        assert!(is_pinned("actions/checkout@b4ffde65f46336ab88eb53be808477a3936bae11"));
+        assert!(is_pinned("./.github/actions/setup"));
+        assert!(is_pinned("docker://alpine@sha256:8a1f59ffb675680d47db6337b49d22281a139e9d"));
+        assert!(!is_pinned("actions/checkout@v4"));
+        assert!(!is_pinned("docker://alpine:3.19"));
+        assert!(!is_pinned("actions/checkout"));
+    }
+
+    #[test]
+    fn test_lint_workflows() {
+        let release = "on: pull_request_target
+permissions:
+  contents: read
+jobs:
+  build:
+    runs-on: ubuntu-latest
+    needs: [test]
+    steps:
+      - uses: actions/checkout@b4ffde65f46336ab88eb53be808477a3936bae11
+      - run: make
+  test:
+    runs-on: ubuntu-latest
+    needs: [build, lint]
+    steps:
+      - run: make test
+        env:
+          TOKEN: ${{ secrets.TOKEN }}
+";
+        let ci = "on: push
+jobs:
+  lint:
+    runs-on: ubuntu-latest
+    permissions:
+      contents: read
+    steps:
+      - uses: actions/checkout@v4
+  test:
+    runs-on: ubuntu-latest
+    needs: lint
+    steps:
+      - run: cargo test
+";
+
+        assert_eq!(
+            vec![
+                diagnostic(
+                    Severity::Warning,
+                    "ci.yml",
+                    "lint",
+                    "actions/checkout@v4 is not pinned to a commit SHA",
+                ),
+                diagnostic(
+                    Severity::Warning,
+                    "ci.yml",
+                    "test",
+                    "job does not restrict the permissions of GITHUB_TOKEN",
+                ),
+                diagnostic(
+                    Severity::Error,
+                    "release.yml",
+                    "build",
+                    "job is part of a cycle of needs",
+                ),
+                diagnostic(
+                    Severity::Error,
+                    "release.yml",
+                    "test",
+                    "job is part of a cycle of needs",
+                ),
+                diagnostic(Severity::Error, "release.yml", "test", "needs undefined job lint"),
+                diagnostic(
+                    Severity::Error,
+                    "release.yml",
+                    "test",
+                    "secrets are used in a pull_request_target workflow",
+                ),
+            ],
+            lint_workflows(&[("release.yml", release), ("ci.yml", ci)]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lint_rules_follow_insertions() {
+        let program = with_lint_rules(program! {
+            job(?file, "deploy") <- [deployable(?file)],
+            needs(?file, "deploy", "build") <- [deployable(?file)]
+        });
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("deployable", vec!["cd.yml".into()]);
+        runtime.insert("permission", vec!["cd.yml".into(), "contents".into(), "read".into()]);
+        runtime.poll();
+        assert_eq!(
+            vec![diagnostic(Severity::Error, "cd.yml", "deploy", "needs undefined job build")],
+            collect_diagnostics(&runtime).unwrap()
+        );
+
+        // Defining the job withdraws the diagnostic
+        runtime.insert("job", vec!["cd.yml".into(), "build".into()]);
+        runtime.poll();
+        assert!(collect_diagnostics(&runtime).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lint_rules_keep_to_their_namespace() {
+        // The program's own same_job relation, of another arity, is left alone
+        let program = with_lint_rules(program! {
+            same_job(?x) <- [job(?file, ?x)]
+        });
+        assert!(program
+            .inner
+            .iter()
+            .any(|rule| rule.head.symbol == "lints::same_job"));
+
+        let mut runtime = MicroRuntime::new(program);
+        runtime.insert("job", vec!["ci.yml".into(), "test".into()]);
+        runtime.insert("permission", vec!["ci.yml".into(), "contents".into(), "read".into()]);
+        runtime.insert("needs_path", vec!["serde".into()]);
+        runtime.poll();
+        assert!(runtime.contains("same_job", &vec!["test".into()]).unwrap());
+        assert!(collect_diagnostics(&runtime).unwrap().is_empty());
+    }
+}
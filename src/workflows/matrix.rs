@@ -0,0 +1,194 @@
+use crate::modules::pack;
+use crate::workflows::vocabulary::MATRIX_CELL;
+use datalog_rule_macro::program;
+use datalog_syntax::*;
+use std::collections::HashMap;
+
+pub const MATRIX_NAMESPACE: &str = "matrix";
+
+fn less(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    (args["k"] < args["l"]).into()
+}
+
+// Cells are named after their values in key order, e.g. "os=ubuntu-latest,rust=stable".
+fn first_cell(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    format!("{}={}", args["k"], args["v"]).into()
+}
+
+fn extend_cell(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    format!("{},{}={}", args["c"], args["k"], args["v"]).into()
+}
+
+fn included_cell(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    format!("include={}", args["i"]).into()
+}
+
+// Expands matrix_axis, matrix_include and matrix_exclude facts into matrix_cell facts, the way
+// GitHub does: the cartesian product of the axes, without the cells matching every pair of an
+// exclude entry. Include entries add their other keys to the remaining cells matching all of their
+// axis values, and become cells of their own when no cell matches them. Unlike GitHub, include
+// entries can't overwrite the values added by earlier ones. The other relations live in the
+// matrix namespace, e.g. matrix::axis_key.
+pub fn matrix_rules() -> Program {
+    let rules = program! {
+        // Axes in key order, so that cells are built one axis after another
+        axis_key(?f, ?j, ?k)             <- [matrix_axis(?f, ?j, ?k, ?v)],
+        axis_order(?f, ?j, ?k, ?l, less(?k, ?l)) <- [axis_key(?f, ?j, ?k), axis_key(?f, ?j, ?l)],
+        axis_between(?f, ?j, ?k, ?m)     <- [axis_order(?f, ?j, ?k, ?l, true), axis_order(?f, ?j, ?l, ?m, true)],
+        next_axis(?f, ?j, ?k, ?l)        <- [axis_order(?f, ?j, ?k, ?l, true), !axis_between(?f, ?j, ?k, ?l)],
+        later_axis(?f, ?j, ?l)           <- [axis_order(?f, ?j, ?k, ?l, true)],
+        earlier_axis(?f, ?j, ?k)         <- [axis_order(?f, ?j, ?k, ?l, true)],
+        first_axis(?f, ?j, ?k)           <- [axis_key(?f, ?j, ?k), !later_axis(?f, ?j, ?k)],
+        last_axis(?f, ?j, ?k)            <- [axis_key(?f, ?j, ?k), !earlier_axis(?f, ?j, ?k)],
+        // Cells covering the axes up to ?k, along with their values
+        partial_cell(?f, ?j, first_cell(?k, ?v), ?k) <- [first_axis(?f, ?j, ?k), matrix_axis(?f, ?j, ?k, ?v)],
+        axis_value(?f, ?j, first_cell(?k, ?v), ?k, ?v) <- [first_axis(?f, ?j, ?k), matrix_axis(?f, ?j, ?k, ?v)],
+        partial_cell(?f, ?j, extend_cell(?c, ?k, ?v), ?k) <- [partial_cell(?f, ?j, ?c, ?p), next_axis(?f, ?j, ?p, ?k), matrix_axis(?f, ?j, ?k, ?v)],
+        axis_value(?f, ?j, extend_cell(?c, ?k, ?v), ?k, ?v) <- [partial_cell(?f, ?j, ?c, ?p), next_axis(?f, ?j, ?p, ?k), matrix_axis(?f, ?j, ?k, ?v)],
+        axis_value(?f, ?j, extend_cell(?c, ?k, ?v), ?q, ?w) <- [partial_cell(?f, ?j, ?c, ?p), next_axis(?f, ?j, ?p, ?k), matrix_axis(?f, ?j, ?k, ?v), axis_value(?f, ?j, ?c, ?q, ?w)],
+        full_cell(?f, ?j, ?c)            <- [partial_cell(?f, ?j, ?c, ?k), last_axis(?f, ?j, ?k)],
+        // Exclusions
+        exclude_mismatch(?f, ?j, ?c, ?e) <- [full_cell(?f, ?j, ?c), matrix_exclude(?f, ?j, ?e, ?k, ?v), !axis_value(?f, ?j, ?c, ?k, ?v)],
+        excluded_cell(?f, ?j, ?c)        <- [full_cell(?f, ?j, ?c), matrix_exclude(?f, ?j, ?e, ?k, ?v), !exclude_mismatch(?f, ?j, ?c, ?e)],
+        kept_cell(?f, ?j, ?c)            <- [full_cell(?f, ?j, ?c), !excluded_cell(?f, ?j, ?c)],
+        // Inclusions
+        include_mismatch(?f, ?j, ?c, ?i) <- [kept_cell(?f, ?j, ?c), matrix_include(?f, ?j, ?i, ?k, ?v), axis_key(?f, ?j, ?k), !axis_value(?f, ?j, ?c, ?k, ?v)],
+        extended_cell(?f, ?j, ?c, ?i)    <- [kept_cell(?f, ?j, ?c), matrix_include(?f, ?j, ?i, ?k, ?v), !include_mismatch(?f, ?j, ?c, ?i)],
+        include_used(?f, ?j, ?i)         <- [extended_cell(?f, ?j, ?c, ?i)],
+        matrix_cell(?f, ?j, ?c, ?k, ?v)  <- [kept_cell(?f, ?j, ?c), axis_value(?f, ?j, ?c, ?k, ?v)],
+        matrix_cell(?f, ?j, ?c, ?k, ?v)  <- [extended_cell(?f, ?j, ?c, ?i), matrix_include(?f, ?j, ?i, ?k, ?v), !axis_key(?f, ?j, ?k)],
+        matrix_cell(?f, ?j, included_cell(?i), ?k, ?v) <- [matrix_include(?f, ?j, ?i, ?k, ?v), !include_used(?f, ?j, ?i)],
+    };
+
+    pack(MATRIX_NAMESPACE, rules, &[MATRIX_CELL])
+}
+
+pub fn with_matrix_rules(program: Program) -> Program {
+    let mut rules = program.inner;
+    rules.extend(matrix_rules().inner);
+
+    Program::from(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::workflows::emitter::select;
+    use crate::workflows::matrix::{matrix_rules, with_matrix_rules};
+    use crate::workflows::vocabulary::MATRIX_CELL;
+    use datalog_syntax::*;
+    use std::collections::BTreeMap;
+
+    fn cells(runtime: &MicroRuntime) -> BTreeMap<String, Vec<String>> {
+        let mut cells = BTreeMap::<String, Vec<String>>::new();
+        select(runtime, MATRIX_CELL, &["ci.yml".into(), "test".into()])
+            .unwrap()
+            .into_iter()
+            .for_each(|fact| {
+                cells
+                    .entry(fact[0].to_string())
+                    .or_default()
+                    .push(format!("{}={}", fact[1], fact[2]));
+            });
+
+        cells
+    }
+
+    fn insert(runtime: &mut MicroRuntime, relation: &str, columns: Vec<TypedValue>) {
+        let mut fact = vec!["ci.yml".into(), "test".into()];
+        fact.extend(columns);
+        runtime.insert(relation, fact);
+    }
+
+    #[test]
+    fn test_cartesian_product() {
+        let mut runtime = MicroRuntime::new(matrix_rules());
+        for os in ["ubuntu-latest", "macos-latest"] {
+            insert(&mut runtime, "matrix_axis", vec!["os".into(), os.into()]);
+        }
+        for rust in ["stable", "beta", "1.70"] {
+            insert(&mut runtime, "matrix_axis", vec!["rust".into(), rust.into()]);
+        }
+        insert(&mut runtime, "matrix_axis", vec!["features".into(), "default".into()]);
+        runtime.poll();
+
+        let cells = cells(&runtime);
+        assert_eq!(6, cells.len());
+        assert_eq!(
+            vec!["features=default", "os=macos-latest", "rust=1.70"],
+            cells["features=default,os=macos-latest,rust=1.70"]
+        );
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let mut runtime = MicroRuntime::new(matrix_rules());
+        for os in ["ubuntu-latest", "windows-latest"] {
+            insert(&mut runtime, "matrix_axis", vec!["os".into(), os.into()]);
+        }
+        for rust in ["stable", "nightly"] {
+            insert(&mut runtime, "matrix_axis", vec!["rust".into(), rust.into()]);
+        }
+        let exclude = vec![
+            ("os", "windows-latest"),
+            ("rust", "nightly"),
+        ];
+        for (key, value) in exclude {
+            insert(&mut runtime, "matrix_exclude", vec![0usize.into(), key.into(), value.into()]);
+        }
+        // Extends every nightly cell
+        insert(
+            &mut runtime,
+            "matrix_include",
+            vec![0usize.into(), "rust".into(), "nightly".into()],
+        );
+        insert(
+            &mut runtime,
+            "matrix_include",
+            vec![0usize.into(), "experimental".into(), true.into()],
+        );
+        // Matches no cell, hence stands on its own
+        insert(&mut runtime, "matrix_include", vec![1usize.into(), "os".into(), "macos-latest".into()]);
+        insert(&mut runtime, "matrix_include", vec![1usize.into(), "rust".into(), "stable".into()]);
+        runtime.poll();
+
+        let expected: Vec<(&str, Vec<&str>)> = vec![
+            ("include=1", vec!["os=macos-latest", "rust=stable"]),
+            (
+                "os=ubuntu-latest,rust=nightly",
+                vec!["experimental=true", "os=ubuntu-latest", "rust=nightly"],
+            ),
+            ("os=ubuntu-latest,rust=stable", vec!["os=ubuntu-latest", "rust=stable"]),
+            ("os=windows-latest,rust=stable", vec!["os=windows-latest", "rust=stable"]),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(cell, values)| {
+                (
+                    cell.to_string(),
+                    values.into_iter().map(|value| value.to_string()).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(expected, cells(&runtime).into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_matrix_rules_keep_to_their_namespace() {
+        // The program's own kept_cell relation, of another arity, is left alone
+        let mut runtime = MicroRuntime::new(with_matrix_rules(Program::from(vec![])));
+        insert(&mut runtime, "matrix_axis", vec!["os".into(), "ubuntu-latest".into()]);
+        runtime.insert("kept_cell", vec!["serde".into()]);
+        runtime.insert("axis_key", vec!["serde".into()]);
+        runtime.poll();
+
+        assert_eq!(vec!["os=ubuntu-latest"], cells(&runtime)["os=ubuntu-latest"]);
+        assert!(runtime.contains("kept_cell", &vec!["serde".into()]).unwrap());
+        assert!(runtime
+            .contains(
+                "matrix::kept_cell",
+                &vec!["ci.yml".into(), "test".into(), "os=ubuntu-latest".into()]
+            )
+            .unwrap());
+    }
+}
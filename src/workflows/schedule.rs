@@ -0,0 +1,266 @@
+use crate::engine::datalog::MicroRuntime;
+use crate::modules::pack;
+use crate::workflows::emitter::select;
+use crate::workflows::lints::cycle_rules;
+use crate::workflows::vocabulary::*;
+use datalog_rule_macro::program;
+use datalog_syntax::*;
+use std::collections::{BTreeMap, HashMap};
+
+pub const SCHEDULE_NAMESPACE: &str = "schedule";
+
+fn less(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    (args["a"] < args["b"]).into()
+}
+
+// Estimates may be given as any non-negative number, fractions of a second being rounded up.
+fn integer(value: &TypedValue) -> Result<usize, String> {
+    match value {
+        TypedValue::Int(integer) => Ok(*integer),
+        TypedValue::I64(integer) if *integer >= 0 => Ok(*integer as usize),
+        TypedValue::Float(float) if float.get() >= 0.0 && float.get().is_finite() => {
+            Ok(float.get().ceil() as usize)
+        }
+        other => Err(format!("{} is not a number of seconds", other)),
+    }
+}
+
+fn seconds(args: HashMap<&str, &TypedValue>) -> Result<TypedValue, String> {
+    Ok(integer(args["d"])?.into())
+}
+
+fn successor(args: HashMap<&str, &TypedValue>) -> Result<TypedValue, String> {
+    Ok((integer(args["l"])? + 1).into())
+}
+
+fn sum(args: HashMap<&str, &TypedValue>) -> Result<TypedValue, String> {
+    Ok((integer(args["t"])? + integer(args["d"])?).into())
+}
+
+// Derives job_level and earliest_finish out of needs and estimated_duration facts, jobs without an
+// estimate taking no time. The engine has no aggregation, hence every candidate level and finish
+// time is derived, and the greatest one is kept by negation. Jobs on a cycle of needs, needing an
+// undefined job or needing such a job get neither, and estimates that aren't numbers of seconds
+// make try_poll fail. The other relations live in the schedule namespace, e.g.
+// schedule::schedulable.
+pub fn schedule_rules() -> Program {
+    let mut rules = cycle_rules().inner;
+    rules.extend(program! {
+        unschedulable(?f, ?j)            <- [on_cycle(?f, ?j)],
+        unschedulable(?f, ?j)            <- [needs(?f, ?j, ?n), !job(?f, ?n)],
+        unschedulable(?f, ?j)            <- [needs_path(?f, ?j, ?n), unschedulable(?f, ?n)],
+        schedulable(?f, ?j)              <- [job(?f, ?j), !unschedulable(?f, ?j)],
+        // Levels
+        level_candidate(?f, ?j, 0)       <- [schedulable(?f, ?j)],
+        level_candidate(?f, ?j, successor(?l)) <- [schedulable(?f, ?j), needs(?f, ?j, ?n), level_candidate(?f, ?n, ?l)],
+        level_order(?f, ?j, ?a, ?b, less(?a, ?b)) <- [level_candidate(?f, ?j, ?a), level_candidate(?f, ?j, ?b)],
+        exceeded_level(?f, ?j, ?a)       <- [level_order(?f, ?j, ?a, ?b, true)],
+        job_level(?f, ?j, ?l)            <- [level_candidate(?f, ?j, ?l), !exceeded_level(?f, ?j, ?l)],
+        // Finish times, given as many runners as needed
+        has_estimate(?f, ?j)             <- [estimated_duration(?f, ?j, ?d)],
+        job_duration(?f, ?j, seconds(?d)) <- [schedulable(?f, ?j), estimated_duration(?f, ?j, ?d)],
+        job_duration(?f, ?j, 0)          <- [schedulable(?f, ?j), !has_estimate(?f, ?j)],
+        finish_candidate(?f, ?j, ?d)     <- [job_duration(?f, ?j, ?d)],
+        finish_candidate(?f, ?j, sum(?t, ?d)) <- [job_duration(?f, ?j, ?d), needs(?f, ?j, ?n), finish_candidate(?f, ?n, ?t)],
+        finish_order(?f, ?j, ?a, ?b, less(?a, ?b)) <- [finish_candidate(?f, ?j, ?a), finish_candidate(?f, ?j, ?b)],
+        exceeded_finish(?f, ?j, ?a)      <- [finish_order(?f, ?j, ?a, ?b, true)],
+        earliest_finish(?f, ?j, ?t)      <- [finish_candidate(?f, ?j, ?t), !exceeded_finish(?f, ?j, ?t)],
+    }.inner);
+
+    pack(SCHEDULE_NAMESPACE, Program::from(rules), &[JOB_LEVEL, EARLIEST_FINISH])
+}
+
+pub fn with_schedule_rules(program: Program) -> Program {
+    let mut rules = program.inner;
+    rules.extend(schedule_rules().inner);
+
+    Program::from(rules)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    pub file: String,
+    // Jobs by level, in name order. Jobs of a level only need jobs of earlier levels.
+    pub levels: Vec<Vec<String>>,
+    // The chain of needs that takes the longest, from its first job to its last
+    pub critical_path: Vec<String>,
+    // Seconds until every job has finished
+    pub duration: usize,
+}
+
+impl Schedule {
+    // How many jobs of each level can run at once
+    pub fn parallelism(&self) -> Vec<usize> {
+        self.levels.iter().map(|level| level.len()).collect()
+    }
+}
+
+// The first of the jobs finishing last.
+fn last_to_finish(jobs: impl Iterator<Item = (String, usize)>) -> Option<(String, usize)> {
+    jobs.reduce(|latest, candidate| match candidate.1 > latest.1 {
+        true => candidate,
+        false => latest,
+    })
+}
+
+// Reads job_level and earliest_finish, one schedule per workflow. Ties between chains of needs that
+// take as long go to the job coming first by name.
+pub fn collect_schedules(runtime: &MicroRuntime) -> Result<Vec<Schedule>, String> {
+    let mut levels: BTreeMap<String, BTreeMap<usize, Vec<String>>> = BTreeMap::new();
+    for fact in select(runtime, JOB_LEVEL, &[])? {
+        levels
+            .entry(fact[0].to_string())
+            .or_default()
+            .entry(integer(&fact[2])?)
+            .or_default()
+            .push(fact[1].to_string());
+    }
+    let mut finishes: HashMap<(String, String), usize> = HashMap::new();
+    for fact in select(runtime, EARLIEST_FINISH, &[])? {
+        finishes.insert((fact[0].to_string(), fact[1].to_string()), integer(&fact[2])?);
+    }
+    let finish = |file: &str, job: &str| finishes.get(&(file.to_string(), job.to_string())).copied();
+
+    let mut schedules = vec![];
+    for (file, file_levels) in levels {
+        let levels: Vec<Vec<String>> = file_levels
+            .into_values()
+            .map(|mut level| {
+                level.sort();
+                level
+            })
+            .collect();
+
+        // The last job to finish, then the needed job finishing last, and so on
+        let mut jobs: Vec<_> = levels.iter().flatten().collect();
+        jobs.sort();
+        let mut current = last_to_finish(
+            jobs.into_iter()
+                .filter_map(|job| Some((job.clone(), finish(&file, job)?))),
+        );
+        let duration = current.as_ref().map_or(0, |(_, finish)| *finish);
+        let mut critical_path = vec![];
+        while let Some((job, _)) = current {
+            let mut needs: Vec<_> = select(runtime, NEEDS, &[file.as_str().into(), job.as_str().into()])?
+                .into_iter()
+                .map(|needed_job| needed_job[0].to_string())
+                .collect();
+            needs.sort();
+            current = last_to_finish(
+                needs
+                    .into_iter()
+                    .filter_map(|needed_job| Some((needed_job.clone(), finish(&file, &needed_job)?))),
+            );
+            critical_path.push(job);
+        }
+        critical_path.reverse();
+
+        schedules.push(Schedule {
+            file,
+            levels,
+            critical_path,
+            duration,
+        });
+    }
+
+    Ok(schedules)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::workflows::schedule::{
+        collect_schedules, schedule_rules, with_schedule_rules, Schedule,
+    };
+    use datalog_syntax::*;
+
+    fn insert(runtime: &mut MicroRuntime, relation: &str, columns: Vec<TypedValue>) {
+        let mut fact = vec!["ci.yml".into()];
+        fact.extend(columns);
+        runtime.insert(relation, fact);
+    }
+
+    #[test]
+    fn test_collect_schedules() {
+        let mut runtime = MicroRuntime::new(schedule_rules());
+        let durations = [
+            ("lint", 60usize),
+            ("build", 300),
+            ("docs", 120),
+            ("test", 600),
+            ("bench", 900),
+            ("deploy", 30),
+        ];
+        for (job, seconds) in durations {
+            insert(&mut runtime, "job", vec![job.into()]);
+            insert(&mut runtime, "estimated_duration", vec![job.into(), seconds.into()]);
+        }
+        let needs = [
+            ("test", "build"),
+            ("test", "lint"),
+            ("bench", "build"),
+            ("deploy", "test"),
+            ("deploy", "docs"),
+        ];
+        for (job, needed_job) in needs {
+            insert(&mut runtime, "needs", vec![job.into(), needed_job.into()]);
+        }
+        // Cycles leave the jobs involved out
+        insert(&mut runtime, "job", vec!["a".into()]);
+        insert(&mut runtime, "job", vec!["b".into()]);
+        insert(&mut runtime, "needs", vec!["a".into(), "b".into()]);
+        insert(&mut runtime, "needs", vec!["b".into(), "a".into()]);
+        runtime.poll();
+
+        let levels = vec![
+            vec!["build".to_string(), "docs".to_string(), "lint".to_string()],
+            vec!["bench".to_string(), "test".to_string()],
+            vec!["deploy".to_string()],
+        ];
+        let expected = Schedule {
+            file: "ci.yml".to_string(),
+            levels,
+            critical_path: vec!["build".to_string(), "bench".to_string()],
+            duration: 1200,
+        };
+        let schedules = collect_schedules(&runtime).unwrap();
+        assert_eq!(vec![expected], schedules);
+        assert_eq!(vec![3, 2, 1], schedules[0].parallelism());
+
+        // A release after deploy becomes the last job to finish
+        insert(&mut runtime, "job", vec!["release".into()]);
+        insert(&mut runtime, "needs", vec!["release".into(), "deploy".into()]);
+        insert(&mut runtime, "estimated_duration", vec!["release".into(), 600usize.into()]);
+        runtime.poll();
+        let schedules = collect_schedules(&runtime).unwrap();
+        let critical_path = ["build", "test", "deploy", "release"];
+        assert_eq!(
+            critical_path.map(|job| job.to_string()).to_vec(),
+            schedules[0].critical_path
+        );
+        assert_eq!(1530, schedules[0].duration);
+        assert_eq!(vec![3, 2, 1, 1], schedules[0].parallelism());
+    }
+
+    #[test]
+    fn test_estimates() {
+        let mut runtime = MicroRuntime::new(with_schedule_rules(Program::from(vec![])));
+        insert(&mut runtime, "job", vec!["lint".into()]);
+        insert(&mut runtime, "job", vec!["test".into()]);
+        insert(&mut runtime, "needs", vec!["test".into(), "lint".into()]);
+        insert(&mut runtime, "estimated_duration", vec!["lint".into(), 7.5.into()]);
+        insert(&mut runtime, "estimated_duration", vec!["test".into(), 60i64.into()]);
+        // The program's own schedulable relation, of another arity, is left alone
+        runtime.insert("schedulable", vec!["serde".into()]);
+        runtime.poll();
+        assert_eq!(68, collect_schedules(&runtime).unwrap()[0].duration);
+        assert!(runtime.contains("schedulable", &vec!["serde".into()]).unwrap());
+
+        insert(&mut runtime, "job", vec!["docs".into()]);
+        insert(&mut runtime, "estimated_duration", vec!["docs".into(), "a minute".into()]);
+        assert_eq!(
+            Err("seconds(d): a minute is not a number of seconds".to_string()),
+            runtime.try_poll()
+        );
+    }
+}
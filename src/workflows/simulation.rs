@@ -0,0 +1,474 @@
+use crate::engine::datalog::MicroRuntime;
+use crate::modules::pack;
+use crate::workflows::emitter::select;
+use crate::workflows::importer::import_workflow;
+use crate::workflows::vocabulary::*;
+use datalog_rule_macro::program;
+use datalog_syntax::*;
+use std::collections::{BTreeMap, HashMap};
+
+pub const SIMULATION_NAMESPACE: &str = "simulation";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Quantifier {
+    One,
+    Optional,
+    OneOrMore,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum PatternToken {
+    // `*`, anything but a slash
+    Star,
+    // `**`, anything at all
+    DoubleStar,
+    Character(char, Quantifier),
+    Class(Vec<(char, char)>, Quantifier),
+}
+
+fn tokenize_pattern(pattern: &str) -> Vec<PatternToken> {
+    let mut tokens = vec![];
+    let mut characters = pattern.chars().peekable();
+    while let Some(character) = characters.next() {
+        let token = match character {
+            '*' if characters.peek() == Some(&'*') => {
+                characters.next();
+                PatternToken::DoubleStar
+            }
+            '*' => PatternToken::Star,
+            '[' => {
+                let mut ranges = vec![];
+                while let Some(start) = characters.next() {
+                    if start == ']' {
+                        break;
+                    }
+                    let mut lookahead = characters.clone();
+                    match (lookahead.next(), lookahead.next()) {
+                        (Some('-'), Some(end)) if end != ']' => {
+                            characters.next();
+                            characters.next();
+                            ranges.push((start, end));
+                        }
+                        _ => ranges.push((start, start)),
+                    }
+                }
+                PatternToken::Class(ranges, Quantifier::One)
+            }
+            '\\' => PatternToken::Character(characters.next().unwrap_or('\\'), Quantifier::One),
+            character => PatternToken::Character(character, Quantifier::One),
+        };
+        tokens.push(token);
+
+        // `?` and `+` apply to the character before them
+        let quantifier = match characters.peek() {
+            Some('?') => Quantifier::Optional,
+            Some('+') => Quantifier::OneOrMore,
+            _ => continue,
+        };
+        if let Some(PatternToken::Character(_, last) | PatternToken::Class(_, last)) =
+            tokens.last_mut()
+        {
+            characters.next();
+            *last = quantifier;
+        }
+    }
+
+    tokens
+}
+
+fn matches_tokens(tokens: &[PatternToken], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+
+    match token {
+        PatternToken::DoubleStar => {
+            // `src/**/*.rs` matches `src/lib.rs` as well
+            if let Some((PatternToken::Character('/', Quantifier::One), after_slash)) =
+                rest.split_first()
+            {
+                if matches_tokens(after_slash, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|start| matches_tokens(rest, &text[start..]))
+        }
+        PatternToken::Star => {
+            let end = text
+                .iter()
+                .position(|character| *character == '/')
+                .unwrap_or(text.len());
+            (0..=end).any(|start| matches_tokens(rest, &text[start..]))
+        }
+        PatternToken::Character(_, quantifier) | PatternToken::Class(_, quantifier) => {
+            let matches_first = text.first().is_some_and(|first| match token {
+                PatternToken::Character(character, _) => first == character,
+                PatternToken::Class(ranges, _) => ranges
+                    .iter()
+                    .any(|(start, end)| (start..=end).contains(&first)),
+                _ => false,
+            });
+
+            match quantifier {
+                Quantifier::One => matches_first && matches_tokens(rest, &text[1..]),
+                Quantifier::Optional => {
+                    matches_tokens(rest, text) || (matches_first && matches_tokens(rest, &text[1..]))
+                }
+                Quantifier::OneOrMore => {
+                    matches_first
+                        && (matches_tokens(rest, &text[1..]) || matches_tokens(tokens, &text[1..]))
+                }
+            }
+        }
+    }
+}
+
+// The filter pattern syntax of GitHub, e.g. `releases/**` or `docs/*.md`.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let text: Vec<_> = text.chars().collect();
+
+    matches_tokens(&tokenize_pattern(pattern), &text)
+}
+
+// Whether the value is selected by the pattern, excluded by a negative one such as `!docs/**`,
+// or neither. A list of patterns excludes the value if any of its negative patterns matches it.
+fn outcome(pattern: &TypedValue, value: &str) -> &'static str {
+    if let TypedValue::List(patterns) = pattern {
+        let outcomes: Vec<_> = patterns.iter().map(|pattern| outcome(pattern, value)).collect();
+        return ["exclude", "include"]
+            .into_iter()
+            .find(|expected| outcomes.contains(expected))
+            .unwrap_or("none");
+    }
+
+    let pattern = pattern.to_string();
+    match pattern.strip_prefix('!') {
+        Some(negated) if glob_matches(negated, value) => "exclude",
+        None if glob_matches(&pattern, value) => "include",
+        _ => "none",
+    }
+}
+
+fn pattern_outcome(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    outcome(args["p"], &args["v"].to_string()).into()
+}
+
+fn branch_ref(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    format!("refs/heads/{}", args["b"]).into()
+}
+
+fn tag_ref(args: HashMap<&str, &TypedValue>) -> TypedValue {
+    format!("refs/tags/{}", args["t"]).into()
+}
+
+// Derives would_run and would_run_step out of the workflow vocabulary and an event. Branch, tag
+// and path filters are applied with GitHub's patterns, although negative patterns exclude values
+// wherever they appear in the list. run_if and run_unless conditions are evaluated against the
+// event's contexts, and jobs are skipped along with the jobs they need. Written job_if and step_if
+// conditions are assumed to hold. context_value is derived as well as given by events, and the
+// other relations live in the simulation namespace, e.g. simulation::skipped.
+pub fn simulation_rules() -> Program {
+    let rules = program! {
+        // The values each filter is matched against
+        event_value("branches", ?b)        <- [branch(?b)],
+        event_value("branches-ignore", ?b) <- [branch(?b)],
+        event_value("tags", ?t)            <- [tag(?t)],
+        event_value("tags-ignore", ?t)     <- [tag(?t)],
+        event_value("paths", ?p)           <- [changed_path(?p)],
+        event_value("paths-ignore", ?p)    <- [changed_path(?p)],
+        filter_key(?f, ?e, ?k)             <- [on_filter(?f, ?e, ?k, ?p)],
+        pattern_result(?f, ?e, ?k, ?v, pattern_outcome(?p, ?v)) <- [on_filter(?f, ?e, ?k, ?p), event_value(?k, ?v)],
+        selected_value(?f, ?e, ?k, ?v)     <- [pattern_result(?f, ?e, ?k, ?v, "include"), !pattern_result(?f, ?e, ?k, ?v, "exclude")],
+        filter_passes(?f, ?e, "branches")  <- [selected_value(?f, ?e, "branches", ?v)],
+        filter_passes(?f, ?e, "tags")      <- [selected_value(?f, ?e, "tags", ?v)],
+        filter_passes(?f, ?e, "paths")     <- [selected_value(?f, ?e, "paths", ?v)],
+        filter_passes(?f, ?e, "branches-ignore") <- [filter_key(?f, ?e, "branches-ignore"), branch(?b), !selected_value(?f, ?e, "branches-ignore", ?b)],
+        filter_passes(?f, ?e, "tags-ignore") <- [filter_key(?f, ?e, "tags-ignore"), tag(?t), !selected_value(?f, ?e, "tags-ignore", ?t)],
+        filter_passes(?f, ?e, "paths-ignore") <- [filter_key(?f, ?e, "paths-ignore"), changed_path(?p), !selected_value(?f, ?e, "paths-ignore", ?p)],
+        // Branch and tag filters are alternatives, whereas path filters have to pass as well
+        ref_filter(?f, ?e)                 <- [filter_key(?f, ?e, "branches")],
+        ref_filter(?f, ?e)                 <- [filter_key(?f, ?e, "branches-ignore")],
+        ref_filter(?f, ?e)                 <- [filter_key(?f, ?e, "tags")],
+        ref_filter(?f, ?e)                 <- [filter_key(?f, ?e, "tags-ignore")],
+        ref_filter_passes(?f, ?e)          <- [filter_passes(?f, ?e, "branches")],
+        ref_filter_passes(?f, ?e)          <- [filter_passes(?f, ?e, "branches-ignore")],
+        ref_filter_passes(?f, ?e)          <- [filter_passes(?f, ?e, "tags")],
+        ref_filter_passes(?f, ?e)          <- [filter_passes(?f, ?e, "tags-ignore")],
+        filter_blocks(?f, ?e)              <- [ref_filter(?f, ?e), !ref_filter_passes(?f, ?e)],
+        filter_blocks(?f, ?e)              <- [filter_key(?f, ?e, "paths"), !filter_passes(?f, ?e, "paths")],
+        filter_blocks(?f, ?e)              <- [filter_key(?f, ?e, "paths-ignore"), !filter_passes(?f, ?e, "paths-ignore")],
+        triggered(?f)                      <- [on_trigger(?f, ?e), event(?e), !filter_blocks(?f, ?e)],
+        // Contexts
+        context_value("github.event_name", ?e) <- [event(?e)],
+        context_value("github.ref_name", ?b) <- [branch(?b)],
+        context_value("github.ref", branch_ref(?b)) <- [branch(?b)],
+        context_value("github.ref_name", ?t) <- [tag(?t)],
+        context_value("github.ref", tag_ref(?t)) <- [tag(?t)],
+        // Jobs
+        run_if_holds(?f, ?j, ?c)           <- [run_if(?f, ?j, ?c, ?v), context_value(?c, ?v)],
+        skipped(?f, ?j)                    <- [run_if(?f, ?j, ?c, ?v), !run_if_holds(?f, ?j, ?c)],
+        skipped(?f, ?j)                    <- [run_unless(?f, ?j, ?c, ?v), context_value(?c, ?v)],
+        skipped(?f, ?j)                    <- [needs(?f, ?j, ?n), !job(?f, ?n)],
+        skipped(?f, ?j)                    <- [needs(?f, ?j, ?n), skipped(?f, ?n)],
+        would_run(?f, ?j)                  <- [triggered(?f), job(?f, ?j), !skipped(?f, ?j)],
+        // Steps
+        step_run_if_holds(?f, ?j, ?p, ?c)  <- [step_run_if(?f, ?j, ?p, ?c, ?v), context_value(?c, ?v)],
+        step_skipped(?f, ?j, ?p)           <- [step_run_if(?f, ?j, ?p, ?c, ?v), !step_run_if_holds(?f, ?j, ?p, ?c)],
+        step_skipped(?f, ?j, ?p)           <- [step_run_unless(?f, ?j, ?p, ?c, ?v), context_value(?c, ?v)],
+        would_run_step(?f, ?j, ?p)         <- [would_run(?f, ?j), step(?f, ?j, ?p), !step_skipped(?f, ?j, ?p)],
+    };
+
+    pack(SIMULATION_NAMESPACE, rules, &[CONTEXT_VALUE, WOULD_RUN, WOULD_RUN_STEP])
+}
+
+pub fn with_simulation_rules(program: Program) -> Program {
+    let mut rules = program.inner;
+    rules.extend(simulation_rules().inner);
+
+    Program::from(rules)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Event {
+    // e.g. "push" or "pull_request"
+    pub kind: String,
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    pub changed_paths: Vec<String>,
+    // Further contexts, e.g. ("github.actor", "octocat")
+    pub contexts: Vec<(String, String)>,
+}
+
+impl Event {
+    pub fn new(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// Nothing is materialised until the next poll.
+pub fn insert_event(runtime: &mut MicroRuntime, event: &Event) -> Result<(), String> {
+    runtime.try_insert(EVENT, vec![event.kind.as_str().into()])?;
+    if let Some(branch) = &event.branch {
+        runtime.try_insert(BRANCH, vec![branch.as_str().into()])?;
+    }
+    if let Some(tag) = &event.tag {
+        runtime.try_insert(TAG, vec![tag.as_str().into()])?;
+    }
+    for path in &event.changed_paths {
+        runtime.try_insert(CHANGED_PATH, vec![path.as_str().into()])?;
+    }
+    for (context, value) in &event.contexts {
+        runtime.try_insert(CONTEXT_VALUE, vec![context.as_str().into(), value.as_str().into()])?;
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimulatedJob {
+    pub file: String,
+    pub job: String,
+    // The jobs that have to finish first
+    pub needs: Vec<String>,
+    // Positions of the steps that would run
+    pub steps: Vec<usize>,
+}
+
+// Reads would_run and would_run_step. Within a workflow, jobs come after the jobs they need, and
+// in name order otherwise.
+pub fn collect_simulation(runtime: &MicroRuntime) -> Result<Vec<SimulatedJob>, String> {
+    let mut workflows: BTreeMap<String, Vec<SimulatedJob>> = BTreeMap::new();
+    for fact in select(runtime, WOULD_RUN, &[])? {
+        let key = [fact[0].clone(), fact[1].clone()];
+        let needs = select(runtime, NEEDS, &key)?
+            .into_iter()
+            .map(|needed_job| needed_job[0].to_string())
+            .collect();
+        let steps = select(runtime, WOULD_RUN_STEP, &key)?
+            .into_iter()
+            .filter_map(|position| match position[0] {
+                TypedValue::Int(position) => Some(position),
+                _ => None,
+            })
+            .collect();
+
+        workflows
+            .entry(fact[0].to_string())
+            .or_default()
+            .push(SimulatedJob {
+                file: fact[0].to_string(),
+                job: fact[1].to_string(),
+                needs,
+                steps,
+            });
+    }
+
+    let mut simulation = vec![];
+    for (_, mut pending) in workflows {
+        let mut done: Vec<String> = vec![];
+        while !pending.is_empty() {
+            // Cycles of needs are reported by the lints, hence jobs in one come in name order
+            let ready = pending
+                .iter()
+                .position(|job| job.needs.iter().all(|needed_job| done.contains(needed_job)))
+                .unwrap_or(0);
+            let job = pending.remove(ready);
+
+            done.push(job.job.clone());
+            simulation.push(job);
+        }
+    }
+
+    Ok(simulation)
+}
+
+// Simulates `event` over (file, source) pairs of workflow YAML.
+pub fn simulate(workflows: &[(&str, &str)], event: &Event) -> Result<Vec<SimulatedJob>, String> {
+    let mut runtime = MicroRuntime::new(simulation_rules());
+    for (file, source) in workflows {
+        import_workflow(&mut runtime, file, source)?;
+    }
+    insert_event(&mut runtime, event)?;
+    runtime.try_poll()?;
+
+    collect_simulation(&runtime)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::datalog::MicroRuntime;
+    use crate::workflows::importer::import_workflow;
+    use crate::workflows::simulation::{
+        collect_simulation, glob_matches, insert_event, outcome, simulate, simulation_rules, Event,
+        SimulatedJob,
+    };
+    use datalog_syntax::TypedValue;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("main", "main"));
+        assert!(!glob_matches("main", "mainline"));
+        assert!(glob_matches("releases/*", "releases/v1"));
+        assert!(!glob_matches("releases/*", "releases/v1/hotfix"));
+        assert!(glob_matches("releases/**", "releases/v1/hotfix"));
+        assert!(glob_matches("src/**/*.rs", "src/lib.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/engine/datalog.rs"));
+        assert!(glob_matches("**.md", "docs/guide/intro.md"));
+        assert!(!glob_matches("*.md", "docs/intro.md"));
+        assert!(glob_matches("v[12].[0-9]+.?", "v1.10"));
+        assert!(glob_matches("v[12].[0-9]+.?", "v2.3."));
+        assert!(!glob_matches("v[12].[0-9]+.?", "v1.10.x"));
+        assert!(!glob_matches("v[12].[0-9]+", "v3.1"));
+        assert!(glob_matches("feature?", "feature"));
+
+        let patterns = TypedValue::List(vec!["releases/**".into(), "!releases/**-alpha".into()]);
+        assert_eq!("include", outcome(&patterns, "releases/v1"));
+        assert_eq!("exclude", outcome(&patterns, "releases/v1-alpha"));
+        assert_eq!("none", outcome(&patterns, "main"));
+    }
+
+    const CI: &str = "on:
+  push:
+    branches: [main, 'releases/**']
+    paths-ignore: ['docs/**']
+  pull_request:
+jobs:
+  lint:
+    runs-on: ubuntu-latest
+    steps:
+      - run: cargo clippy
+  test:
+    runs-on: ubuntu-latest
+    needs: lint
+    steps:
+      - run: cargo test
+  deploy:
+    runs-on: ubuntu-latest
+    needs: test
+    steps:
+      - run: make deploy
+";
+
+    fn job(job: &str, needs: &[&str], steps: &[usize]) -> SimulatedJob {
+        SimulatedJob {
+            file: "ci.yml".to_string(),
+            job: job.to_string(),
+            needs: needs.iter().map(|needed_job| needed_job.to_string()).collect(),
+            steps: steps.to_vec(),
+        }
+    }
+
+    fn push(branch: &str, paths: &[&str]) -> Event {
+        Event {
+            branch: Some(branch.to_string()),
+            changed_paths: paths.iter().map(|path| path.to_string()).collect(),
+            ..Event::new("push")
+        }
+    }
+
+    #[test]
+    fn test_simulate_filters() {
+        let every_job = vec![
+            job("lint", &[], &[0]),
+            job("test", &["lint"], &[0]),
+            job("deploy", &["test"], &[0]),
+        ];
+
+        assert_eq!(every_job, simulate(&[("ci.yml", CI)], &push("main", &["src/lib.rs"])).unwrap());
+        assert_eq!(
+            every_job,
+            simulate(&[("ci.yml", CI)], &push("releases/v1", &["docs/a.md", "src/lib.rs"])).unwrap()
+        );
+        // Only ignored paths changed
+        assert!(simulate(&[("ci.yml", CI)], &push("main", &["docs/a.md"])).unwrap().is_empty());
+        assert!(simulate(&[("ci.yml", CI)], &push("feature", &["src/lib.rs"])).unwrap().is_empty());
+        let tag = Event {
+            tag: Some("v1.0".to_string()),
+            ..Event::new("push")
+        };
+        assert!(simulate(&[("ci.yml", CI)], &tag).unwrap().is_empty());
+        assert!(simulate(&[("ci.yml", CI)], &Event::new("schedule")).unwrap().is_empty());
+        assert_eq!(every_job, simulate(&[("ci.yml", CI)], &Event::new("pull_request")).unwrap());
+    }
+
+    #[test]
+    fn test_simulate_conditions() {
+        let mut runtime = MicroRuntime::new(simulation_rules());
+        import_workflow(&mut runtime, "ci.yml", CI).unwrap();
+        let deploy_key = |columns: Vec<TypedValue>| {
+            let mut fact = vec!["ci.yml".into(), "deploy".into()];
+            fact.extend(columns);
+            fact
+        };
+        // The program's own skipped relation, of another arity, is left alone
+        runtime.insert("skipped", vec!["lint".into()]);
+        runtime.insert("run_if", deploy_key(vec!["github.ref".into(), "refs/heads/main".into()]));
+        runtime.insert("run_unless", deploy_key(vec!["github.actor".into(), "dependabot[bot]".into()]));
+        runtime.insert("step", vec!["ci.yml".into(), "test".into(), 1usize.into()]);
+        runtime.insert("run", vec!["ci.yml".into(), "test".into(), 1usize.into(), "cargo bench".into()]);
+        runtime.insert(
+            "step_run_if",
+            vec![
+                "ci.yml".into(),
+                "test".into(),
+                1usize.into(),
+                "github.event_name".into(),
+                "push".into(),
+            ],
+        );
+        let event = Event {
+            contexts: vec![("github.actor".to_string(), "dependabot[bot]".to_string())],
+            ..Event::new("pull_request")
+        };
+        insert_event(&mut runtime, &event).unwrap();
+        runtime.poll();
+
+        assert_eq!(
+            vec![job("lint", &[], &[0]), job("test", &["lint"], &[0])],
+            collect_simulation(&runtime).unwrap()
+        );
+    }
+}
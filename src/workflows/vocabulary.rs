@@ -0,0 +1,102 @@
+// The well-known relations a workflow is made of. Every one of them is keyed by the workflow
+// file, e.g. "ci.yml", so that a single runtime can hold many workflows. Steps are identified by
+// their position within the job, starting at 0.
+
+// workflow(file)
+pub const WORKFLOW: &str = "workflow";
+// workflow_name(file, name)
+pub const WORKFLOW_NAME: &str = "workflow_name";
+// on_trigger(file, event)
+pub const ON_TRIGGER: &str = "on_trigger";
+// on_filter(file, event, filter, value), where filter is e.g. "branches" or "paths", and "cron"
+// for schedules
+pub const ON_FILTER: &str = "on_filter";
+// workflow_attribute(file, key, value), for scalar keys without a relation of their own
+pub const WORKFLOW_ATTRIBUTE: &str = "workflow_attribute";
+// permission(file, scope, level), where scope "*" stands for a blanket level like "read-all", or
+// "none" for `permissions: {}`
+pub const PERMISSION: &str = "permission";
+// env(file, key, value)
+pub const ENV: &str = "env";
+// job(file, job)
+pub const JOB: &str = "job";
+// job_name(file, job, name)
+pub const JOB_NAME: &str = "job_name";
+// runs_on(file, job, runner)
+pub const RUNS_ON: &str = "runs_on";
+// needs(file, job, needed_job)
+pub const NEEDS: &str = "needs";
+// job_if(file, job, condition)
+pub const JOB_IF: &str = "job_if";
+// run_if(file, job, context, value): the job only runs when the context, e.g. "github.event_name",
+// has one of the values it is given. Conditions on different contexts all have to hold, along
+// with job_if.
+pub const RUN_IF: &str = "run_if";
+// run_unless(file, job, context, value): the job doesn't run when the context has this value
+pub const RUN_UNLESS: &str = "run_unless";
+// job_permission(file, job, scope, level)
+pub const JOB_PERMISSION: &str = "job_permission";
+// job_env(file, job, key, value)
+pub const JOB_ENV: &str = "job_env";
+// job_attribute(file, job, key, value), e.g. "timeout-minutes"
+pub const JOB_ATTRIBUTE: &str = "job_attribute";
+// matrix_axis(file, job, key, value), one fact per value the key takes
+pub const MATRIX_AXIS: &str = "matrix_axis";
+// matrix_include(file, job, entry, key, value), where entry tells the objects of the include list
+// apart, e.g. by their position
+pub const MATRIX_INCLUDE: &str = "matrix_include";
+// matrix_exclude(file, job, entry, key, value)
+pub const MATRIX_EXCLUDE: &str = "matrix_exclude";
+// matrix_cell(file, job, cell, key, value), every combination the matrix runs, as derived by the
+// matrix rules
+pub const MATRIX_CELL: &str = "matrix_cell";
+// matrix_emission(file, mode), where mode is "native", the default, for a strategy.matrix block or
+// "unrolled" for one job per cell
+pub const MATRIX_EMISSION: &str = "matrix_emission";
+// step(file, job, position)
+pub const STEP: &str = "step";
+// step_name(file, job, position, name)
+pub const STEP_NAME: &str = "step_name";
+// step_if(file, job, position, condition)
+pub const STEP_IF: &str = "step_if";
+// step_run_if(file, job, position, context, value), the step counterpart of run_if
+pub const STEP_RUN_IF: &str = "step_run_if";
+// step_run_unless(file, job, position, context, value)
+pub const STEP_RUN_UNLESS: &str = "step_run_unless";
+// uses(file, job, position, action)
+pub const USES: &str = "uses";
+// run(file, job, position, command)
+pub const RUN: &str = "run";
+// with(file, job, position, key, value)
+pub const WITH: &str = "with";
+// step_env(file, job, position, key, value)
+pub const STEP_ENV: &str = "step_env";
+// step_attribute(file, job, position, key, value), e.g. "id" or "working-directory"
+pub const STEP_ATTRIBUTE: &str = "step_attribute";
+// diagnostic(severity, file, job, message), where severity is "error" or "warning"
+pub const DIAGNOSTIC: &str = "diagnostic";
+
+// The synthetic event workflows are simulated against, see simulation.rs.
+// event(kind), e.g. "push"
+pub const EVENT: &str = "event";
+// branch(name), the branch the event is about
+pub const BRANCH: &str = "branch";
+// tag(name), the tag the event is about
+pub const TAG: &str = "tag";
+// changed_path(path)
+pub const CHANGED_PATH: &str = "changed_path";
+// context_value(context, value), e.g. ("github.actor", "octocat"), on top of the ones derived out of
+// the event
+pub const CONTEXT_VALUE: &str = "context_value";
+// would_run(file, job)
+pub const WOULD_RUN: &str = "would_run";
+// would_run_step(file, job, position)
+pub const WOULD_RUN_STEP: &str = "would_run_step";
+
+// Scheduling, see schedule.rs.
+// estimated_duration(file, job, seconds)
+pub const ESTIMATED_DURATION: &str = "estimated_duration";
+// job_level(file, job, level), the length of the longest chain of needs leading to the job
+pub const JOB_LEVEL: &str = "job_level";
+// earliest_finish(file, job, seconds), given unlimited runners
+pub const EARLIEST_FINISH: &str = "earliest_finish";
@@ -0,0 +1,360 @@
+use std::fmt::Write;
+
+// The subset of YAML that workflow files need. Maps keep their insertion order, which is what
+// makes the emitted documents stable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Yaml {
+    Null,
+    Bool(bool),
+    Int(i64),
+    // A float, as it is spelled
+    Real(String),
+    Str(String),
+    List(Vec<Yaml>),
+    Map(Vec<(String, Yaml)>),
+}
+
+impl Yaml {
+    pub fn get(&self, key: &str) -> Option<&Yaml> {
+        match self {
+            Yaml::Map(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+const RESERVED_WORDS: [&str; 17] = [
+    "true", "false", "yes", "no", "on", "off", "y", "n", "null", "~", "", ".inf", "-.inf", "+.inf",
+    ".nan", "<<", "=",
+];
+
+// Numbers as YAML 1.1 or 1.2 parsers read them: in any base, with underscores, sexagesimal such
+// as 1:30, or timestamps such as 2024-01-31, which some parsers turn into dates.
+fn is_number(scalar: &str) -> bool {
+    let lowercase = scalar.to_lowercase();
+    let unsigned = lowercase.strip_prefix(['+', '-']).unwrap_or(&lowercase);
+    let digits = unsigned.replace('_', "");
+    let prefixed = [("0x", 16), ("0o", 8), ("0b", 2)]
+        .into_iter()
+        .find_map(|(prefix, radix)| digits.strip_prefix(prefix).map(|rest| (rest, radix)));
+
+    match prefixed {
+        Some((rest, radix)) => !rest.is_empty() && rest.chars().all(|c| c.is_digit(radix)),
+        None => {
+            digits.parse::<f64>().is_ok()
+                || (digits.contains(':')
+                    && digits.split(':').all(|part| part.parse::<f64>().is_ok()))
+                || is_timestamp(unsigned)
+        }
+    }
+}
+
+fn is_timestamp(scalar: &str) -> bool {
+    let date = scalar.split(['t', ' ']).next().unwrap_or_default();
+    let parts: Vec<_> = date.split('-').collect();
+
+    parts.len() == 3
+        && parts[0].len() == 4
+        && parts[1..].iter().all(|part| (1..=2).contains(&part.len()))
+        && parts.iter().all(|part| part.chars().all(|c| c.is_ascii_digit()))
+}
+
+// Anything a YAML parser would not read back as a string is quoted, as are scalars that would not
+// parse at all.
+fn needs_quotes(scalar: &str) -> bool {
+    let lowercase = scalar.to_lowercase();
+
+    RESERVED_WORDS.contains(&lowercase.as_str())
+        || is_number(scalar)
+        || scalar.starts_with(|character: char| {
+            character.is_whitespace() || "-?:,[]{}#&*!|>'\"%@`".contains(character)
+        })
+        || scalar.ends_with(|character: char| character.is_whitespace() || character == ':')
+        || scalar.contains(": ")
+        || scalar.contains(" #")
+        || scalar.contains(|character: char| character.is_control())
+}
+
+pub fn render_scalar(scalar: &str) -> String {
+    if !needs_quotes(scalar) {
+        return scalar.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    scalar.chars().for_each(|character| match character {
+        '"' => quoted.push_str("\\\""),
+        '\\' => quoted.push_str("\\\\"),
+        '\n' => quoted.push_str("\\n"),
+        '\t' => quoted.push_str("\\t"),
+        '\r' => quoted.push_str("\\r"),
+        character if character.is_control() => {
+            write!(quoted, "\\u{:04x}", character as u32).unwrap()
+        }
+        character => quoted.push(character),
+    });
+    quoted.push('"');
+
+    quoted
+}
+
+// Workflow keys are always read as strings by GitHub, so `on:` stays as everybody writes it.
+fn render_key(key: &str) -> String {
+    if !key.is_empty() && RESERVED_WORDS.contains(&key.to_lowercase().as_str()) {
+        return key.to_string();
+    }
+
+    render_scalar(key)
+}
+
+// Multi-line strings, like scripts, read far better as literal blocks.
+fn is_block(scalar: &str) -> bool {
+    scalar.contains('\n')
+        && !scalar.starts_with([' ', '\n'])
+        && !scalar.ends_with("\n\n")
+        && !scalar.contains(['\r', '\t'])
+        && scalar
+            .lines()
+            .all(|line| line == line.trim_end() && !line.contains(|c: char| c.is_control()))
+}
+
+fn render_inline(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::Null => Some(String::new()),
+        Yaml::Bool(inner) => Some(inner.to_string()),
+        Yaml::Int(inner) => Some(inner.to_string()),
+        Yaml::Real(inner) => Some(inner.clone()),
+        Yaml::Str(inner) if !is_block(inner) => Some(render_scalar(inner)),
+        Yaml::List(items) if items.is_empty() => Some("[]".to_string()),
+        Yaml::Map(entries) if entries.is_empty() => Some("{}".to_string()),
+        _ => None,
+    }
+}
+
+fn render_value(prefix: &str, value: &Yaml, indent: usize, out: &mut String) {
+    let padding = " ".repeat(indent);
+
+    if let Some(inline) = render_inline(value) {
+        if inline.is_empty() {
+            writeln!(out, "{}", prefix).unwrap();
+        } else {
+            writeln!(out, "{} {}", prefix, inline).unwrap();
+        }
+        return;
+    }
+
+    match value {
+        Yaml::Str(inner) => {
+            let chomping = if inner.ends_with('\n') { "" } else { "-" };
+            writeln!(out, "{} |{}", prefix, chomping).unwrap();
+            inner.lines().for_each(|line| {
+                if line.is_empty() {
+                    out.push('\n');
+                } else {
+                    writeln!(out, "{}{}", padding, line).unwrap();
+                }
+            });
+        }
+        Yaml::List(items) => {
+            writeln!(out, "{}", prefix).unwrap();
+            render_list(items, indent, out);
+        }
+        Yaml::Map(entries) => {
+            writeln!(out, "{}", prefix).unwrap();
+            render_map(entries, indent, out);
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn render_map(entries: &[(String, Yaml)], indent: usize, out: &mut String) {
+    let padding = " ".repeat(indent);
+
+    entries.iter().for_each(|(key, value)| {
+        let prefix = format!("{}{}:", padding, render_key(key));
+
+        render_value(&prefix, value, indent + 2, out);
+    });
+}
+
+fn render_list(items: &[Yaml], indent: usize, out: &mut String) {
+    let padding = " ".repeat(indent);
+
+    items.iter().for_each(|item| match item {
+        // The first entry shares its line with the dash, the others align with it.
+        Yaml::Map(entries) if !entries.is_empty() => {
+            let mut rendered = String::new();
+            render_map(entries, indent + 2, &mut rendered);
+            out.push_str(&padding);
+            out.push_str("- ");
+            out.push_str(&rendered[indent + 2..]);
+        }
+        item => render_value(&format!("{}-", padding), item, indent + 2, out),
+    });
+}
+
+pub fn render(document: &Yaml) -> String {
+    let mut out = String::new();
+
+    match document {
+        Yaml::Map(entries) => render_map(entries, 0, &mut out),
+        Yaml::List(items) => render_list(items, 0, &mut out),
+        scalar => render_value("", scalar, 0, &mut out),
+    }
+
+    out
+}
+
+fn scalar_key(key: yaml_rust2::Yaml) -> Result<String, String> {
+    match key {
+        yaml_rust2::Yaml::String(inner) | yaml_rust2::Yaml::Real(inner) => Ok(inner),
+        yaml_rust2::Yaml::Integer(inner) => Ok(inner.to_string()),
+        yaml_rust2::Yaml::Boolean(inner) => Ok(inner.to_string()),
+        key => Err(format!("unsupported mapping key {:?}", key)),
+    }
+}
+
+fn from_yaml_rust(value: yaml_rust2::Yaml) -> Result<Yaml, String> {
+    match value {
+        yaml_rust2::Yaml::Null => Ok(Yaml::Null),
+        yaml_rust2::Yaml::Boolean(inner) => Ok(Yaml::Bool(inner)),
+        yaml_rust2::Yaml::Integer(inner) => Ok(Yaml::Int(inner)),
+        yaml_rust2::Yaml::String(inner) => Ok(Yaml::Str(inner)),
+        // Floats keep their spelling, "3.10" must not turn into "3.1"
+        yaml_rust2::Yaml::Real(inner) => Ok(Yaml::Real(inner)),
+        yaml_rust2::Yaml::Array(items) => Ok(Yaml::List(
+            items
+                .into_iter()
+                .map(from_yaml_rust)
+                .collect::<Result<_, _>>()?,
+        )),
+        yaml_rust2::Yaml::Hash(entries) => Ok(Yaml::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| Ok((scalar_key(key)?, from_yaml_rust(value)?)))
+                .collect::<Result<_, String>>()?,
+        )),
+        yaml_rust2::Yaml::Alias(_) | yaml_rust2::Yaml::BadValue => {
+            Err("aliases are not supported".to_string())
+        }
+    }
+}
+
+// Parses the first document of `source`.
+pub fn parse(source: &str) -> Result<Yaml, String> {
+    let documents = yaml_rust2::YamlLoader::load_from_str(source).map_err(|err| err.to_string())?;
+
+    match documents.into_iter().next() {
+        Some(document) => from_yaml_rust(document),
+        None => Ok(Yaml::Null),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::workflows::yaml::{parse, render, render_scalar, Yaml};
+
+    #[test]
+    fn test_render_scalar() {
+        assert_eq!("ubuntu-latest", render_scalar("ubuntu-latest"));
+        assert_eq!("${{ matrix.os }}", render_scalar("${{ matrix.os }}"));
+        assert_eq!("\"on\"", render_scalar("on"));
+        assert_eq!("\"1.70\"", render_scalar("1.70"));
+        let numbers = [
+            ".nan", ".NaN", "-.inf", "0x1F", "0o17", "0b101", "1_000", "1:30", "2024-01-31",
+        ];
+        for number in numbers {
+            assert_eq!(format!("\"{}\"", number), render_scalar(number));
+            let document = Yaml::Map(vec![("key".to_string(), Yaml::Str(number.to_string()))]);
+            assert_eq!(document, parse(&render(&document)).unwrap());
+        }
+        assert_eq!("v1.2.3", render_scalar("v1.2.3"));
+        assert_eq!("0xyz", render_scalar("0xyz"));
+        assert_eq!("\"*.md\"", render_scalar("*.md"));
+        assert_eq!("\"a: b\"", render_scalar("a: b"));
+        assert_eq!("\"\"", render_scalar(""));
+        assert_eq!("say \"hi\"", render_scalar("say \"hi\""));
+        assert_eq!("\"\\\"hi\\\" she said\"", render_scalar("\"hi\" she said"));
+    }
+
+    #[test]
+    fn test_render_document() {
+        let document = Yaml::Map(vec![
+            ("name".to_string(), Yaml::Str("CI".to_string())),
+            (
+                "on".to_string(),
+                Yaml::Map(vec![("workflow_dispatch".to_string(), Yaml::Null)]),
+            ),
+            (
+                "steps".to_string(),
+                Yaml::List(vec![
+                    Yaml::Map(vec![
+                        ("uses".to_string(), Yaml::Str("actions/checkout@v4".to_string())),
+                        (
+                            "with".to_string(),
+                            Yaml::Map(vec![("fetch-depth".to_string(), Yaml::Int(0))]),
+                        ),
+                    ]),
+                    Yaml::Map(vec![(
+                        "run".to_string(),
+                        Yaml::Str("cargo build\n\ncargo test\n".to_string()),
+                    )]),
+                ]),
+            ),
+            ("needs".to_string(), Yaml::List(vec![])),
+        ]);
+
+        let expected = "name: CI
+on:
+  workflow_dispatch:
+steps:
+  - uses: actions/checkout@v4
+    with:
+      fetch-depth: 0
+  - run: |
+      cargo build
+
+      cargo test
+needs: []
+";
+
+        assert_eq!(expected, render(&document));
+    }
+
+    #[test]
+    fn test_parse_render_roundtrip() {
+        let source = "name: CI
+on:
+  push:
+    branches:
+      - main
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    steps:
+      - run: |
+          cargo test
+      - uses: actions/setup-python@v5
+        with:
+          python-version: 3.10
+          cache: true
+          retries: 3
+";
+
+        let document = parse(source).unwrap();
+        assert_eq!(
+            Some(&Yaml::Real("3.10".to_string())),
+            document
+                .get("jobs")
+                .and_then(|jobs| jobs.get("test"))
+                .and_then(|test| match test.get("steps") {
+                    Some(Yaml::List(steps)) => steps[1].get("with"),
+                    _ => None,
+                })
+                .and_then(|with| with.get("python-version"))
+        );
+        assert_eq!(parse(&render(&document)).unwrap(), document);
+    }
+}